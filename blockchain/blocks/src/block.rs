@@ -4,6 +4,7 @@
 use super::BlockHeader;
 use cid::Cid;
 use encoding::tuple::*;
+use encoding::Cbor;
 use message::{SignedMessage, UnsignedMessage};
 
 /// A complete block
@@ -33,6 +34,17 @@ impl Block {
     }
 }
 
+/// Block message used as the payload for blocks gossiped over pubsub, referencing its
+/// messages by Cid
+#[derive(Clone, Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct GossipBlock {
+    pub header: BlockHeader,
+    pub bls_messages: Vec<Cid>,
+    pub secpk_messages: Vec<Cid>,
+}
+
+impl Cbor for GossipBlock {}
+
 /// Tracks the merkleroots of both secp and bls messages separately
 #[derive(Serialize_tuple, Deserialize_tuple)]
 pub struct TxMeta {
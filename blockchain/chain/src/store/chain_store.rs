@@ -7,12 +7,14 @@ use beacon::BeaconEntry;
 use blake2b_simd::Params;
 use blocks::{Block, BlockHeader, FullTipset, Tipset, TipsetKeys, TxMeta};
 use byteorder::{BigEndian, WriteBytesExt};
-use cid::multihash::Blake2b256;
-use cid::Cid;
+use cid::multihash::{Blake2b256, Code};
+use cid::{Cid, Codec};
 use clock::ChainEpoch;
 use crypto::DomainSeparationTag;
 use encoding::{blake2b_256, de::DeserializeOwned, from_slice, Cbor};
 use flo_stream::{MessagePublisher, Publisher, Subscriber};
+use forest_car::{CarHeader, CarWriter};
+use forest_ipld::Ipld;
 use ipld_amt::Amt;
 use ipld_blockstore::BlockStore;
 use log::{info, warn};
@@ -20,6 +22,7 @@ use message::{SignedMessage, UnsignedMessage};
 use num_bigint::BigUint;
 use num_traits::Zero;
 use state_tree::StateTree;
+use std::collections::HashSet;
 use std::io::Write;
 use std::sync::Arc;
 
@@ -35,10 +38,21 @@ const BLOCKS_PER_EPOCH: u64 = 5;
 // A cap on the size of the future_sink
 const SINK_CAP: usize = 1000;
 
+/// Enum for pubsub channel that defines message type variant and data contained in message type.
+#[derive(Clone, Debug)]
+pub enum HeadChange {
+    /// Tipset set as the head without a previous head to diff against
+    Current(Arc<Tipset>),
+    /// Tipset applied to the head of the chain
+    Apply(Arc<Tipset>),
+    /// Tipset reverted from the head of the chain
+    Revert(Arc<Tipset>),
+}
+
 /// Generic implementation of the datastore trait and structures
 pub struct ChainStore<DB> {
     // TODO add IPLD Store
-    publisher: Publisher<Arc<Tipset>>,
+    publisher: Publisher<HeadChange>,
 
     // key-value datastore
     pub db: Arc<DB>,
@@ -67,16 +81,45 @@ where
         }
     }
 
-    /// Sets heaviest tipset within ChainStore and store its tipset cids under HEAD_KEY
+    /// Sets heaviest tipset within ChainStore and store its tipset cids under HEAD_KEY.
+    /// Publishes the tipsets reverted and applied from the previous heaviest tipset.
     pub async fn set_heaviest_tipset(&mut self, ts: Arc<Tipset>) -> Result<(), Error> {
+        let changes = match &self.heaviest {
+            Some(prev) => {
+                let (revert, apply) = reorg_ops(self.blockstore(), prev, &ts)?;
+                let mut changes = Vec::with_capacity(revert.len() + apply.len());
+                changes.extend(revert.into_iter().map(|t| HeadChange::Revert(Arc::new(t))));
+                changes.extend(
+                    apply
+                        .into_iter()
+                        .rev()
+                        .map(|t| HeadChange::Apply(Arc::new(t))),
+                );
+                changes
+            }
+            None => vec![HeadChange::Current(ts.clone())],
+        };
+
+        self.db.write(HEAD_KEY, ts.key().marshal_cbor()?)?;
+        self.heaviest = Some(ts);
+        for change in changes {
+            self.publisher.publish(change).await;
+        }
+        Ok(())
+    }
+
+    /// Sets a trusted checkpoint as the heaviest tipset. The changes from the previous heaviest
+    /// tipset are not computed, as the history before the checkpoint may not be in the store,
+    /// and the checkpoint is published as the current head instead.
+    pub async fn set_checkpoint(&mut self, ts: Arc<Tipset>) -> Result<(), Error> {
         self.db.write(HEAD_KEY, ts.key().marshal_cbor()?)?;
         self.heaviest = Some(ts.clone());
-        self.publisher.publish(ts).await;
+        self.publisher.publish(HeadChange::Current(ts)).await;
         Ok(())
     }
 
     // subscribing returns a future sink that we can essentially iterate over using future streams
-    pub fn subscribe(&mut self) -> Subscriber<Arc<Tipset>> {
+    pub fn subscribe(&mut self) -> Subscriber<HeadChange> {
         self.publisher.subscribe()
     }
 
@@ -97,6 +140,11 @@ where
         set_genesis(self.blockstore(), header)
     }
 
+    /// Writes tipset block headers to data store without updating the heaviest tipset
+    pub fn persist_headers(&self, ts: &Tipset) -> Result<(), Error> {
+        persist_headers(self.blockstore(), ts.blocks())
+    }
+
     /// Writes tipset block headers to data store and updates heaviest tipset
     pub async fn put_tipsets(&mut self, ts: &Tipset) -> Result<(), Error> {
         persist_headers(self.blockstore(), ts.blocks())?;
@@ -120,7 +168,9 @@ where
         // set as heaviest tipset
         let heaviest_ts = Arc::new(heaviest_ts);
         self.heaviest = Some(heaviest_ts.clone());
-        self.publisher.publish(heaviest_ts).await;
+        self.publisher
+            .publish(HeadChange::Current(heaviest_ts))
+            .await;
         Ok(())
     }
 
@@ -180,6 +230,17 @@ where
         // the given tipset has already been verified, so this cannot fail
         Ok(FullTipset::new(blocks).unwrap())
     }
+
+    /// Exports the chain behind the tipset as a CAR snapshot, see `export`
+    pub fn export<W: Write>(
+        &self,
+        ts: &Tipset,
+        lookback: Option<ChainEpoch>,
+        recent_roots: ChainEpoch,
+        writer: W,
+    ) -> Result<W, Error> {
+        export(self.blockstore(), ts, lookback, recent_roots, writer)
+    }
     /// Determines if provided tipset is heavier than existing known heaviest tipset
     async fn update_heaviest(&mut self, ts: &Tipset) -> Result<(), Error> {
         match &self.heaviest {
@@ -187,7 +248,6 @@ where
                 let new_weight = weight(self.blockstore(), ts)?;
                 let curr_weight = weight(self.blockstore(), &heaviest)?;
                 if new_weight > curr_weight {
-                    // TODO potentially need to deal with re-orgs here
                     info!("New heaviest tipset");
                     self.set_heaviest_tipset(Arc::new(ts.clone())).await?;
                 }
@@ -357,6 +417,35 @@ where
     let ts = Tipset::new(block_headers)?;
     Ok(ts)
 }
+/// Returns the tipsets that need to be reverted and applied to move the head of the chain
+/// from one tipset to another. Both vectors are ordered from the respective head back to
+/// (but not including) the common ancestor.
+pub fn reorg_ops<DB>(
+    db: &DB,
+    from: &Tipset,
+    to: &Tipset,
+) -> Result<(Vec<Tipset>, Vec<Tipset>), Error>
+where
+    DB: BlockStore,
+{
+    let mut left = from.clone();
+    let mut right = to.clone();
+    let mut left_chain = Vec::new();
+    let mut right_chain = Vec::new();
+    while left != right {
+        if left.epoch() > right.epoch() {
+            let parent = tipset_from_keys(db, left.parents())?;
+            left_chain.push(left);
+            left = parent;
+        } else {
+            let parent = tipset_from_keys(db, right.parents())?;
+            right_chain.push(right);
+            right = parent;
+        }
+    }
+    Ok((left_chain, right_chain))
+}
+
 /// Returns the tipset behind `tsk` at a given `height`. If the given height
 /// is a null round:
 /// if `prev` is `true`, the tipset before the null round is returned.
@@ -412,6 +501,94 @@ where
     Ok(cids)
 }
 
+/// Writes a CAR snapshot of the chain behind the tipset, rooted at its keys. Walks back
+/// `lookback` epochs, or to genesis if `None`, writing the headers with their messages and
+/// receipts, and the state trees of the tipsets within `recent_roots` epochs of the tipset.
+/// Each block is written once, and the writer is returned when the snapshot is complete.
+pub fn export<DB, W>(
+    db: &DB,
+    ts: &Tipset,
+    lookback: Option<ChainEpoch>,
+    recent_roots: ChainEpoch,
+    writer: W,
+) -> Result<W, Error>
+where
+    DB: BlockStore,
+    W: Write,
+{
+    let header = CarHeader::new(ts.cids().to_vec(), 1);
+    let mut car = CarWriter::new(writer, &header).map_err(|e| Error::Other(e.to_string()))?;
+    let mut seen = HashSet::new();
+
+    let last_epoch = lookback.map(|n| ts.epoch().saturating_sub(n));
+    let state_epoch = ts.epoch().saturating_sub(recent_roots);
+    let mut current = ts.clone();
+    loop {
+        for header in current.blocks() {
+            if !seen.insert(header.cid().clone()) {
+                continue;
+            }
+            car.write_block(header.cid(), &header.marshal_cbor()?)
+                .map_err(|e| Error::Other(e.to_string()))?;
+            walk_dag(db, header.messages(), &mut seen, &mut car)?;
+            walk_dag(db, header.message_receipts(), &mut seen, &mut car)?;
+            if header.epoch() >= state_epoch {
+                walk_dag(db, header.state_root(), &mut seen, &mut car)?;
+            }
+        }
+
+        let reached_last = last_epoch.map_or(false, |epoch| current.epoch() <= epoch);
+        if reached_last || current.parents().cids().is_empty() {
+            break;
+        }
+        current = tipset_from_keys(db, current.parents())?;
+    }
+
+    car.finish().map_err(|e| Error::Other(e.to_string()))
+}
+
+/// Writes the blocks of the DAG under the root which were not seen yet. Only DAG-CBOR blocks
+/// are followed, as other links such as actor code Cids do not refer to stored blocks.
+fn walk_dag<DB, W>(
+    db: &DB,
+    root: &Cid,
+    seen: &mut HashSet<Cid>,
+    car: &mut CarWriter<W>,
+) -> Result<(), Error>
+where
+    DB: BlockStore,
+    W: Write,
+{
+    let mut stack = vec![root.clone()];
+    while let Some(cid) = stack.pop() {
+        if cid.codec != Codec::DagCBOR
+            || cid.hash.algorithm() == Code::Identity
+            || !seen.insert(cid.clone())
+        {
+            continue;
+        }
+        let bz = db
+            .get_bytes(&cid)
+            .map_err(|e| Error::Other(e.to_string()))?
+            .ok_or_else(|| Error::UndefinedKey(cid.to_string()))?;
+        let ipld: Ipld = from_slice(&bz)?;
+        push_links(&ipld, &mut stack);
+        car.write_block(&cid, &bz)
+            .map_err(|e| Error::Other(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Pushes the Cids linked to from the IPLD node
+fn push_links(ipld: &Ipld, links: &mut Vec<Cid>) {
+    match ipld {
+        Ipld::Link(cid) => links.push(cid.clone()),
+        Ipld::List(list) => list.iter().for_each(|i| push_links(i, links)),
+        Ipld::Map(map) => map.values().for_each(|i| push_links(i, links)),
+        _ => (),
+    }
+}
+
 /// Returns the genesis block
 pub fn genesis<DB>(db: &DB) -> Result<Option<BlockHeader>, Error>
 where
@@ -497,4 +674,136 @@ mod tests {
         cs.set_genesis(gen_block.clone()).unwrap();
         assert_eq!(cs.genesis().unwrap(), Some(gen_block));
     }
+
+    #[test]
+    fn reorg_ops_test() {
+        let db = db::MemoryDB::default();
+
+        let header = |epoch: u64, miner: u64, parent: &BlockHeader| {
+            BlockHeader::builder()
+                .epoch(epoch)
+                .parents(TipsetKeys::new(vec![parent.cid().clone()]))
+                .miner_address(Address::new_id(miner))
+                .build_and_validate()
+                .unwrap()
+        };
+        let gen = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build_and_validate()
+            .unwrap();
+        let a1 = header(1, 1, &gen);
+        let a2 = header(2, 1, &a1);
+        let b1 = header(1, 2, &gen);
+        persist_headers(&db, &[gen.clone(), a1.clone(), a2.clone(), b1.clone()]).unwrap();
+
+        let ts = |h: &BlockHeader| Tipset::new(vec![h.clone()]).unwrap();
+        let (revert, apply) = reorg_ops(&db, &ts(&a2), &ts(&b1)).unwrap();
+        assert_eq!(revert, vec![ts(&a2), ts(&a1)]);
+        assert_eq!(apply, vec![ts(&b1)]);
+
+        // Moving forward on the same chain only applies tipsets
+        let (revert, apply) = reorg_ops(&db, &ts(&gen), &ts(&a2)).unwrap();
+        assert!(revert.is_empty());
+        assert_eq!(apply, vec![ts(&a2), ts(&a1)]);
+    }
+
+    #[test]
+    fn set_checkpoint_without_history() {
+        let db = Arc::new(db::MemoryDB::default());
+        let mut cs = ChainStore::new(db.clone());
+
+        let gen = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build_and_validate()
+            .unwrap();
+        // Parent of the checkpoint is not in the store
+        let parent = BlockHeader::builder()
+            .epoch(9)
+            .miner_address(Address::new_id(1))
+            .build_and_validate()
+            .unwrap();
+        let checkpoint = BlockHeader::builder()
+            .epoch(10)
+            .parents(TipsetKeys::new(vec![parent.cid().clone()]))
+            .miner_address(Address::new_id(1))
+            .build_and_validate()
+            .unwrap();
+        persist_headers(db.as_ref(), &[gen.clone(), checkpoint.clone()]).unwrap();
+
+        let gen_ts = Arc::new(Tipset::new(vec![gen]).unwrap());
+        let checkpoint_ts = Arc::new(Tipset::new(vec![checkpoint]).unwrap());
+        async_std::task::block_on(async {
+            cs.set_heaviest_tipset(gen_ts.clone()).await.unwrap();
+            assert!(cs.set_heaviest_tipset(checkpoint_ts.clone()).await.is_err());
+
+            cs.set_checkpoint(checkpoint_ts.clone()).await.unwrap();
+            assert_eq!(cs.heaviest_tipset(), Some(checkpoint_ts.clone()));
+            assert_eq!(
+                get_heaviest_tipset(db.as_ref()).unwrap(),
+                Some(checkpoint_ts.as_ref().clone())
+            );
+        });
+    }
+
+    #[test]
+    fn export_snapshot() {
+        let db = db::MemoryDB::default();
+
+        // Objects shared by the tipsets are written once
+        let leaf = db.put(&"leaf", Blake2b256).unwrap();
+        let state = |epoch: u64| db.put(&(epoch, leaf.clone()), Blake2b256).unwrap();
+        let header = |epoch: u64, parents: Vec<Cid>| {
+            BlockHeader::builder()
+                .epoch(epoch)
+                .parents(TipsetKeys::new(parents))
+                .messages(leaf.clone())
+                .message_receipts(leaf.clone())
+                .state_root(state(epoch))
+                .miner_address(Address::new_id(0))
+                .build_and_validate()
+                .unwrap()
+        };
+        let gen = header(0, vec![]);
+        let h1 = header(1, vec![gen.cid().clone()]);
+        let h2 = header(2, vec![h1.cid().clone()]);
+        persist_headers(&db, &[gen.clone(), h1.clone(), h2.clone()]).unwrap();
+        let ts = Tipset::new(vec![h2.clone()]).unwrap();
+
+        let read_cids = |buf: Vec<u8>| {
+            let mut reader =
+                forest_car::CarReader::new(std::io::BufReader::new(buf.as_slice())).unwrap();
+            assert_eq!(reader.header.roots, ts.cids());
+            let mut cids = Vec::new();
+            while reader.has_next_block().unwrap() {
+                cids.push(reader.next_block().unwrap().cid().clone());
+            }
+            cids
+        };
+
+        // Whole chain, with the state of the head only
+        let cids = read_cids(export(&db, &ts, None, 0, Vec::new()).unwrap());
+        assert_eq!(
+            cids,
+            vec![
+                h2.cid().clone(),
+                leaf.clone(),
+                h2.state_root().clone(),
+                h1.cid().clone(),
+                gen.cid().clone()
+            ]
+        );
+
+        // Last epoch, with the states of both tipsets
+        let cids = read_cids(export(&db, &ts, Some(1), 1, Vec::new()).unwrap());
+        assert_eq!(
+            cids,
+            vec![
+                h2.cid().clone(),
+                leaf.clone(),
+                h2.state_root().clone(),
+                h1.cid().clone(),
+                h1.state_root().clone()
+            ]
+        );
+    }
 }
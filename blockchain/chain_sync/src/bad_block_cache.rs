@@ -0,0 +1,91 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use async_std::sync::Mutex;
+use cid::Cid;
+use lru::LruCache;
+
+/// Amount of blocks the cache holds before evicting the least recently used blocks
+const DEFAULT_CAPACITY: usize = 1 << 15;
+
+/// Thread safe cache of blocks marked as bad, along with the reason each block was marked.
+/// Blocks in the cache, and chains containing them, are refused by the syncer.
+pub struct BadBlockCache {
+    cache: Mutex<LruCache<Cid, String>>,
+}
+
+impl Default for BadBlockCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl BadBlockCache {
+    /// Creates a cache holding at most `cap` blocks
+    pub fn new(cap: usize) -> Self {
+        Self {
+            cache: Mutex::new(LruCache::new(cap)),
+        }
+    }
+
+    /// Marks the block as bad for the given reason
+    pub async fn put(&self, c: Cid, reason: String) {
+        self.cache.lock().await.put(c, reason);
+    }
+
+    /// Returns the reason the block was marked as bad, if it is in the cache, and marks it
+    /// as recently used
+    pub async fn get(&self, c: &Cid) -> Option<String> {
+        self.cache.lock().await.get(c).cloned()
+    }
+
+    /// Returns the reason the block was marked as bad, if it is in the cache
+    pub async fn peek(&self, c: &Cid) -> Option<String> {
+        self.cache.lock().await.peek(c).cloned()
+    }
+
+    /// Removes the block from the cache, returning the reason it was marked as bad
+    pub async fn remove(&self, c: &Cid) -> Option<String> {
+        self.cache.lock().await.pop(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use cid::multihash::Identity;
+
+    #[test]
+    fn mark_and_unmark() {
+        task::block_on(async {
+            let cache = BadBlockCache::new(2);
+            let c = Cid::new_from_cbor(&[1], Identity);
+            assert_eq!(cache.peek(&c).await, None);
+
+            cache.put(c.clone(), "invalid".to_owned()).await;
+            assert_eq!(cache.get(&c).await, Some("invalid".to_owned()));
+
+            assert_eq!(cache.remove(&c).await, Some("invalid".to_owned()));
+            assert_eq!(cache.peek(&c).await, None);
+        });
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        task::block_on(async {
+            let cache = BadBlockCache::new(2);
+            let cids: Vec<_> = (0..3u8)
+                .map(|i| Cid::new_from_cbor(&[i], Identity))
+                .collect();
+            cache.put(cids[0].clone(), "0".to_owned()).await;
+            cache.put(cids[1].clone(), "1".to_owned()).await;
+            cache.get(&cids[0]).await;
+            cache.put(cids[2].clone(), "2".to_owned()).await;
+
+            assert!(cache.peek(&cids[0]).await.is_some());
+            assert!(cache.peek(&cids[1]).await.is_none());
+            assert!(cache.peek(&cids[2]).await.is_some());
+        });
+    }
+}
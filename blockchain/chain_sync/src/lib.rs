@@ -1,13 +1,17 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod bad_block_cache;
 mod bucket;
 mod errors;
 mod network_context;
 mod network_handler;
 mod peer_manager;
 mod sync;
+mod sync_state;
 
+pub use self::bad_block_cache::BadBlockCache;
 pub use self::errors::Error;
 pub use self::network_context::SyncNetworkContext;
-pub use self::sync::ChainSyncer;
+pub use self::sync::{ChainSyncer, SyncConfig};
+pub use self::sync_state::{SyncState, SyncStatus};
@@ -1,100 +1,204 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use super::network_handler::RPCReceiver;
+use super::network_handler::PendingRequests;
+use super::peer_manager::PeerManager;
 use async_std::future;
 use async_std::prelude::*;
-use async_std::sync::{Receiver, Sender};
+use async_std::sync::{channel, Receiver, Sender};
 use blocks::{FullTipset, Tipset, TipsetKeys};
+use encoding::to_writer;
 use forest_libp2p::{
-    blocksync::{BlockSyncRequest, BlockSyncResponse, BLOCKS, MESSAGES},
+    blocksync::{BlockSyncRequest, BlockSyncResponse, BLOCKS, MESSAGES, OK},
     hello::HelloMessage,
-    rpc::{RPCEvent, RPCRequest, RPCResponse, RequestId},
+    rpc::{RPCEvent, RPCRequest, RPCResponse},
     NetworkEvent, NetworkMessage,
 };
 use libp2p::core::PeerId;
-use log::trace;
-use std::time::Duration;
+use log::{trace, warn};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Timeout for response from an RPC request
 const RPC_TIMEOUT: u64 = 5;
 
-/// Context used in chain sync to handle network requests
+/// Maximum amount of peers a blocksync request is attempted with before failing
+const MAX_BLOCKSYNC_ATTEMPTS: usize = 3;
+
+/// Context used in chain sync to handle network requests. The context can be cloned to send
+/// requests concurrently, responses are matched to their request by the request ID.
+#[derive(Clone)]
 pub struct SyncNetworkContext {
     /// Channel to send network messages through p2p service
     network_send: Sender<NetworkMessage>,
 
     /// Handles sequential request ID enumeration for requests
-    request_id: RequestId,
+    request_id: Arc<AtomicUsize>,
 
-    /// Receiver channel for BlockSync responses
-    rpc_receiver: RPCReceiver,
+    /// Requests awaiting a response from the network
+    pending_requests: PendingRequests,
 
     /// Receiver channel for network events
     pub receiver: Receiver<NetworkEvent>,
+
+    /// Peer manager used to select peers and record request statistics
+    peer_manager: Arc<PeerManager>,
 }
 
 impl SyncNetworkContext {
     pub fn new(
         network_send: Sender<NetworkMessage>,
-        rpc_receiver: RPCReceiver,
+        pending_requests: PendingRequests,
         receiver: Receiver<NetworkEvent>,
+        peer_manager: Arc<PeerManager>,
     ) -> Self {
         Self {
             network_send,
-            rpc_receiver,
+            pending_requests,
             receiver,
-            request_id: 1,
+            peer_manager,
+            request_id: Arc::new(AtomicUsize::new(1)),
         }
     }
 
-    /// Send a blocksync request for only block headers (ignore messages)
+    /// Send a blocksync request for only block headers (ignore messages).
+    /// If no peer is given or the request fails, other peers are selected from the peer manager.
     pub async fn blocksync_headers(
-        &mut self,
-        peer_id: PeerId,
+        &self,
+        peer_id: Option<PeerId>,
         tsk: &TipsetKeys,
         count: u64,
     ) -> Result<Vec<Tipset>, String> {
-        let bs_res = self
-            .blocksync_request(
+        let ts = self
+            .blocksync_with_retry(
                 peer_id,
                 BlockSyncRequest {
                     start: tsk.cids().to_vec(),
                     request_len: count,
                     options: BLOCKS,
                 },
+                BlockSyncResponse::into_result,
             )
             .await?;
-
-        let ts = bs_res.into_result()?;
         Ok(ts.iter().map(|fts| fts.to_tipset()).collect())
     }
-    /// Send a blocksync request for full tipsets (includes messages)
+    /// Send a blocksync request for full tipsets (includes messages).
+    /// If no peer is given or the request fails, other peers are selected from the peer manager.
     pub async fn blocksync_fts(
-        &mut self,
-        peer_id: PeerId,
+        &self,
+        peer_id: Option<PeerId>,
         tsk: &TipsetKeys,
     ) -> Result<FullTipset, String> {
-        let bs_res = self
-            .blocksync_request(
+        let fts = self
+            .blocksync_with_retry(
                 peer_id,
                 BlockSyncRequest {
                     start: tsk.cids().to_vec(),
                     request_len: 1,
                     options: BLOCKS | MESSAGES,
                 },
+                BlockSyncResponse::into_result,
             )
             .await?;
-
-        let fts = bs_res.into_result()?;
-        fts.get(0)
-            .cloned()
+        fts.into_iter()
+            .next()
             .ok_or(format!("No full tipset found for cid: {:?}", tsk))
     }
 
+    /// Send a blocksync request for only the messages of the given tipsets, which are ordered
+    /// from the newest tipset. The messages are combined with the headers into full tipsets.
+    /// If no peer is given or the request fails, other peers are selected from the peer manager.
+    pub async fn blocksync_messages(
+        &self,
+        peer_id: Option<PeerId>,
+        tipsets: &[Tipset],
+    ) -> Result<Vec<FullTipset>, String> {
+        let head = tipsets
+            .first()
+            .ok_or("No tipsets given to request messages for")?;
+        self.blocksync_with_retry(
+            peer_id,
+            BlockSyncRequest {
+                start: head.cids().to_vec(),
+                request_len: tipsets.len() as u64,
+                options: MESSAGES,
+            },
+            |mut res| {
+                if res.status != OK {
+                    return Err(format!("Status {}: {}", res.status, res.message));
+                }
+                if res.chain.len() != tipsets.len() {
+                    return Err(format!(
+                        "Expected messages for {} tipsets, received {}",
+                        tipsets.len(),
+                        res.chain.len()
+                    ));
+                }
+                for (bundle, ts) in res.chain.iter_mut().zip(tipsets) {
+                    bundle.blocks = ts.blocks().to_vec();
+                }
+                res.into_result()
+            },
+        )
+        .await
+    }
+
+    /// Sends a blocksync request and converts the response with the given function. Failed or
+    /// invalid responses are retried with a different peer, and the result of each attempt is
+    /// recorded in the peer manager.
+    async fn blocksync_with_retry<T, F>(
+        &self,
+        peer_id: Option<PeerId>,
+        request: BlockSyncRequest,
+        convert: F,
+    ) -> Result<T, String>
+    where
+        F: Fn(BlockSyncResponse) -> Result<T, String>,
+    {
+        let mut peer_id = peer_id;
+        let mut tried = Vec::new();
+        let mut last_err = "No peers available for blocksync request".to_owned();
+        for _ in 0..MAX_BLOCKSYNC_ATTEMPTS {
+            let peer = match peer_id.take() {
+                Some(peer) => peer,
+                None => match self.peer_manager.get_peer_except(&tried).await {
+                    Some(peer) => peer,
+                    None => break,
+                },
+            };
+
+            let start = Instant::now();
+            let res = self
+                .blocksync_request(peer.clone(), request.clone())
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|res| {
+                    let bytes = encoded_len(&res);
+                    Ok((convert(res)?, bytes))
+                });
+            match res {
+                Ok((res, bytes)) => {
+                    self.peer_manager
+                        .log_success(&peer, start.elapsed(), bytes)
+                        .await;
+                    return Ok(res);
+                }
+                Err(e) => {
+                    warn!("Failed blocksync request to peer {}: {}", peer, e);
+                    self.peer_manager.log_failure(&peer).await;
+                    tried.push(peer);
+                    last_err = e;
+                }
+            }
+        }
+        Err(last_err)
+    }
+
     /// Send a blocksync request to the network and await response
     pub async fn blocksync_request(
-        &mut self,
+        &self,
         peer_id: PeerId,
         request: BlockSyncRequest,
     ) -> Result<BlockSyncResponse, &'static str> {
@@ -110,35 +214,34 @@ impl SyncNetworkContext {
         }
     }
 
-    /// Send a hello request to the network (does not await response)
+    /// Send a hello request to the network (does not await response). The response is
+    /// handled by the network service, which emits the measured latency to the peer.
     pub async fn hello_request(&self, peer_id: PeerId, request: HelloMessage) {
         trace!("Sending Hello Message {:?}", request);
-        // TODO update to await response when we want to handle the latency
         self.send_rpc_event(peer_id, RPCEvent::Request(0, RPCRequest::Hello(request)))
             .await;
     }
 
     /// Send any RPC request to the network and await the response
     pub async fn send_rpc_request(
-        &mut self,
+        &self,
         peer_id: PeerId,
         rpc_request: RPCRequest,
     ) -> Result<RPCResponse, &'static str> {
-        let request_id = self.request_id;
-        self.request_id += 1;
+        let request_id = self.request_id.fetch_add(1, Ordering::Relaxed);
+        let (response_send, mut response_rx) = channel(1);
+        self.pending_requests
+            .write()
+            .await
+            .insert(request_id, response_send);
         self.send_rpc_event(peer_id, RPCEvent::Request(request_id, rpc_request))
             .await;
-        loop {
-            match future::timeout(Duration::from_secs(RPC_TIMEOUT), self.rpc_receiver.next()).await
-            {
-                Ok(Some((id, response))) => {
-                    if id == request_id {
-                        return Ok(response);
-                    }
-                    // Ignore any other RPC responses for now
-                }
-                Ok(None) => return Err("RPC Stream closed"),
-                Err(_) => return Err("Connection timeout"),
+        match future::timeout(Duration::from_secs(RPC_TIMEOUT), response_rx.next()).await {
+            Ok(Some(response)) => Ok(response),
+            Ok(None) => Err("RPC Stream closed"),
+            Err(_) => {
+                self.pending_requests.write().await.remove(&request_id);
+                Err("Connection timeout")
             }
         }
     }
@@ -150,3 +253,26 @@ impl SyncNetworkContext {
             .await
     }
 }
+
+/// Writer which only counts the bytes written to it
+struct ByteCounter(u64);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the size of the encoded blocksync response, without buffering the encoding
+fn encoded_len(res: &BlockSyncResponse) -> u64 {
+    let mut counter = ByteCounter(0);
+    match to_writer(&mut counter, res) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
+    }
+}
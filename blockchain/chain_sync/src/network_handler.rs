@@ -3,19 +3,20 @@
 
 use super::peer_manager::PeerManager;
 use async_std::prelude::*;
-use async_std::sync::{Receiver, Sender};
+use async_std::sync::{Receiver, RwLock, Sender};
 use async_std::task;
 use forest_libp2p::rpc::{RPCResponse, RequestId};
 use forest_libp2p::NetworkEvent;
 use log::trace;
+use std::collections::HashMap;
 use std::sync::Arc;
 
-pub(crate) type RPCReceiver = Receiver<(RequestId, RPCResponse)>;
-pub(crate) type RPCSender = Sender<(RequestId, RPCResponse)>;
+/// RPC requests awaiting a response, keyed by request id
+pub(crate) type PendingRequests = Arc<RwLock<HashMap<RequestId, Sender<RPCResponse>>>>;
 
 /// Handles network events from channel and splits based on request
 pub(crate) struct NetworkHandler {
-    rpc_send: RPCSender,
+    pending_requests: PendingRequests,
     event_send: Sender<NetworkEvent>,
     receiver: Receiver<NetworkEvent>,
 }
@@ -23,34 +24,47 @@ pub(crate) struct NetworkHandler {
 impl NetworkHandler {
     pub(crate) fn new(
         receiver: Receiver<NetworkEvent>,
-        rpc_send: RPCSender,
+        pending_requests: PendingRequests,
         event_send: Sender<NetworkEvent>,
     ) -> Self {
         Self {
             receiver,
-            rpc_send,
+            pending_requests,
             event_send,
         }
     }
 
     pub(crate) fn spawn(&self, peer_manager: Arc<PeerManager>) {
         let mut receiver = self.receiver.clone();
-        let rpc_send = self.rpc_send.clone();
+        let pending_requests = Arc::clone(&self.pending_requests);
         let event_send = self.event_send.clone();
 
         task::spawn(async move {
             loop {
                 match receiver.next().await {
-                    // Handle specifically RPC responses and send to that channel
+                    // Handle specifically RPC responses and send to the request awaiting it
                     Some(NetworkEvent::RPCResponse { req_id, response }) => {
-                        rpc_send.send((req_id, response)).await
+                        let pending = pending_requests.write().await.remove(&req_id);
+                        match pending {
+                            Some(response_send) => response_send.send(response).await,
+                            None => trace!("Ignoring RPC response to unknown request {}", req_id),
+                        }
                     }
                     // Pass any non RPC responses through event channel
                     Some(event) => {
                         // Update peer on this thread before sending hello
-                        if let NetworkEvent::Hello { source, .. } = &event {
-                            // TODO should probably add peer with their tipset/ not handled seperately
-                            peer_manager.add_peer(source.clone(), None).await;
+                        match &event {
+                            NetworkEvent::Hello { source, .. } => {
+                                // TODO should probably add peer with their tipset/ not handled seperately
+                                peer_manager.add_peer(source.clone(), None).await;
+                            }
+                            NetworkEvent::HelloResponse { source, latency } => {
+                                peer_manager.update_latency(source.clone(), *latency).await;
+                            }
+                            NetworkEvent::PeerDisconnected { peer_id } => {
+                                peer_manager.remove_peer(peer_id).await;
+                            }
+                            _ => (),
                         }
 
                         // TODO revisit, doing this to avoid blocking this thread but can handle better
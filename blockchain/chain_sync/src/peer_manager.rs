@@ -4,9 +4,57 @@
 use async_std::sync::RwLock;
 use blocks::Tipset;
 use libp2p::core::PeerId;
-use log::debug;
+use log::{debug, warn};
+use rand::distributions::{Distribution, WeightedIndex};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Amount of consecutive failed requests after which a peer is temporarily banned
+const BAN_THRESHOLD: u32 = 3;
+/// Duration peers are banned for after repeated failed requests
+const BAN_DURATION: Duration = Duration::from_secs(5 * 60);
+
+/// Request statistics of a peer, used to score peers for selection
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerStats {
+    /// Amount of successful requests made to the peer
+    pub successes: u64,
+    /// Amount of failed or invalid requests made to the peer
+    pub failures: u64,
+    /// Average response time of successful requests
+    pub average_time: Duration,
+    /// Total amount of bytes served by the peer
+    pub bytes: u64,
+    /// Latency of the peer measured from hello responses
+    pub latency: Option<Duration>,
+    /// Failed requests since the last successful request
+    consecutive_failures: u32,
+    /// Time until which the peer is not selected for requests
+    banned_until: Option<Instant>,
+}
+
+impl PeerStats {
+    /// Returns true if the peer is temporarily banned
+    pub fn is_banned(&self) -> bool {
+        self.banned_until
+            .map(|until| Instant::now() < until)
+            .unwrap_or(false)
+    }
+
+    /// Returns the score used as weight when selecting peers. Peers with a higher success
+    /// rate and faster responses have a higher score.
+    fn score(&self) -> f64 {
+        let success_rate =
+            (self.successes as f64 + 1.0) / ((self.successes + self.failures) as f64 + 2.0);
+        let response_time = if self.successes > 0 {
+            self.average_time
+        } else {
+            self.latency.unwrap_or_default()
+        };
+        success_rate / (1.0 + response_time.as_secs_f64())
+    }
+}
 
 /// Thread safe peer manager
 #[derive(Default)]
@@ -14,6 +62,8 @@ pub struct PeerManager {
     // TODO potentially separate or expand to handle blocksync peers/ peers that haven't sent hello
     /// Hash set of full peers available
     full_peers: RwLock<HashMap<PeerId, Option<Arc<Tipset>>>>,
+    /// Request statistics of peers
+    peer_stats: RwLock<HashMap<PeerId, PeerStats>>,
 }
 
 impl PeerManager {
@@ -28,15 +78,85 @@ impl PeerManager {
         self.full_peers.read().await.is_empty()
     }
 
-    /// Retrieves a cloned PeerId to be used to send network request
+    /// Retrieves a cloned PeerId to be used to send network request. Peers are selected
+    /// randomly, weighted by their score, and banned peers are never selected.
     pub async fn get_peer(&self) -> Option<PeerId> {
-        // TODO replace this with a shuffled or more random sample
-        self.full_peers
+        self.get_peer_except(&[]).await
+    }
+
+    /// Retrieves a peer to send a network request to, excluding the given peers
+    pub async fn get_peer_except(&self, exclude: &[PeerId]) -> Option<PeerId> {
+        let peer_stats = self.peer_stats.read().await;
+        let full_peers = self.full_peers.read().await;
+
+        let mut peers = Vec::new();
+        let mut weights = Vec::new();
+        for peer in full_peers.keys().filter(|p| !exclude.contains(p)) {
+            let stats = peer_stats.get(peer);
+            if stats.map(PeerStats::is_banned).unwrap_or(false) {
+                continue;
+            }
+            peers.push(peer);
+            weights.push(
+                stats
+                    .map(PeerStats::score)
+                    .unwrap_or_else(|| PeerStats::default().score()),
+            );
+        }
+
+        let dist = WeightedIndex::new(&weights).ok()?;
+        Some(peers[dist.sample(&mut rand::thread_rng())].clone())
+    }
+
+    /// Records the latency measured for a peer
+    pub async fn update_latency(&self, peer_id: PeerId, latency: Duration) {
+        debug!("Measured latency of {:?} for peer {}", latency, &peer_id);
+        self.peer_stats
+            .write()
+            .await
+            .entry(peer_id)
+            .or_default()
+            .latency = Some(latency);
+    }
+
+    /// Returns the latency measured for a peer, if any
+    pub async fn get_latency(&self, peer_id: &PeerId) -> Option<Duration> {
+        self.peer_stats
             .read()
             .await
-            .iter()
-            .next()
-            .map(|(k, _)| k.clone())
+            .get(peer_id)
+            .and_then(|s| s.latency)
+    }
+
+    /// Records a successful request to a peer, which served the given amount of bytes
+    pub async fn log_success(&self, peer_id: &PeerId, time: Duration, bytes: u64) {
+        let mut peer_stats = self.peer_stats.write().await;
+        let stats = peer_stats.entry(peer_id.clone()).or_default();
+        let total = stats.average_time.as_nanos() * u128::from(stats.successes) + time.as_nanos();
+        stats.successes += 1;
+        let average = total / u128::from(stats.successes);
+        stats.average_time = Duration::from_nanos(average.min(u128::from(u64::MAX)) as u64);
+        stats.bytes = stats.bytes.saturating_add(bytes);
+        stats.consecutive_failures = 0;
+    }
+
+    /// Records a failed or invalid request to a peer. Peers are temporarily banned after
+    /// repeated failures.
+    pub async fn log_failure(&self, peer_id: &PeerId) {
+        let mut peer_stats = self.peer_stats.write().await;
+        let stats = peer_stats.entry(peer_id.clone()).or_default();
+        stats.failures += 1;
+        stats.consecutive_failures += 1;
+        if stats.consecutive_failures >= BAN_THRESHOLD {
+            warn!("Temporarily banning peer {} after failed requests", peer_id);
+            stats.banned_until = Some(Instant::now() + BAN_DURATION);
+            stats.consecutive_failures = 0;
+        }
+    }
+
+    /// Returns the request statistics of a peer
+    pub async fn get_peer_stats(&self, peer_id: &PeerId) -> Option<PeerStats> {
+        self.peer_stats.read().await.get(peer_id).cloned()
     }
 
     /// Retrieves all tipsets from current peer set
@@ -49,8 +169,17 @@ impl PeerManager {
             .collect()
     }
 
-    /// Removes a peer from the set and returns true if the value was present previously
+    /// Removes a peer from the set and returns true if the value was present previously.
+    /// Statistics of banned peers are kept so the ban persists when the peer reconnects.
     pub async fn remove_peer(&self, peer_id: &PeerId) -> bool {
+        let mut peer_stats = self.peer_stats.write().await;
+        if !peer_stats
+            .get(peer_id)
+            .map(PeerStats::is_banned)
+            .unwrap_or(false)
+        {
+            peer_stats.remove(peer_id);
+        }
         self.full_peers.write().await.remove(peer_id).is_some()
     }
 
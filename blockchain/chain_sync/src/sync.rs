@@ -4,16 +4,19 @@
 #[cfg(test)]
 mod peer_test;
 
+use super::bad_block_cache::BadBlockCache;
 use super::bucket::{SyncBucket, SyncBucketSet};
-use super::network_handler::NetworkHandler;
+use super::network_handler::{NetworkHandler, PendingRequests};
 use super::peer_manager::PeerManager;
+use super::sync_state::{SyncState, SyncStatus};
 use super::{Error, SyncNetworkContext};
 use address::{Address, Protocol};
 use amt::Amt;
-use async_std::sync::{channel, Receiver, Sender};
+use async_std::sync::{channel, Receiver, RwLock, Sender};
 use async_std::task;
 use beacon::{Beacon, BeaconEntry};
-use blocks::{Block, BlockHeader, FullTipset, Tipset, TipsetKeys, TxMeta};
+use blockchain::message_pool::{MessagePool, MpoolProvider};
+use blocks::{Block, BlockHeader, FullTipset, GossipBlock, Tipset, TipsetKeys, TxMeta};
 use chain::ChainStore;
 use cid::{multihash::Blake2b256, Cid};
 use commcid::cid_to_replica_commitment_v1;
@@ -24,50 +27,63 @@ use encoding::{Cbor, Error as EncodingError};
 use fil_types::SectorInfo;
 use filecoin_proofs_api::{post::verify_winning_post, ProverId, PublicReplicaInfo, SectorId};
 use forest_libp2p::{
-    hello::HelloMessage, BlockSyncRequest, NetworkEvent, NetworkMessage, MESSAGES,
+    blocksync::MAX_REQUEST_LENGTH, hello::HelloMessage, NetworkEvent, NetworkMessage, PubsubMessage,
 };
 use futures::{
     executor::block_on,
-    stream::{FuturesUnordered, StreamExt},
+    stream::{self, FuturesUnordered, StreamExt},
 };
 use ipld_blockstore::BlockStore;
 use libp2p::core::PeerId;
 use log::error;
 use log::{debug, info, warn};
-use lru::LruCache;
 use message::{Message, SignedMessage, UnsignedMessage};
 use num_traits::Zero;
+use serde::Deserialize;
 use state_manager::{utils, StateManager};
 use state_tree::StateTree;
 use std::cmp::min;
 use std::collections::{BTreeMap, HashMap};
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryInto;
 use std::sync::Arc;
 use vm::TokenAmount;
 
-#[derive(PartialEq, Debug, Clone)]
-/// Current state of the ChainSyncer
-pub enum SyncState {
-    /// Initial state, validating data structures and local chain
-    Init,
-
-    /// Bootstrap to the network, and acquire a secure enough set of peers
-    Bootstrap,
-
-    /// Syncing to checkpoint (using BlockSync for now)
-    Checkpoint,
-
-    /// Receive new blocks from the network and sync toward heaviest tipset
-    Catchup,
+// TODO move to shared parameter (from actors crate most likely)
+/// Maximum amount of tipsets a fork can diverge from the local chain to be synced
+const FORK_LENGTH_THRESHOLD: u64 = 500;
+
+// see https://github.com/filecoin-project/lotus/blob/master/build/params_shared.go#L109 for block message limit
+/// Maximum amount of messages a block can include
+const BLOCK_MESSAGE_LIMIT: usize = 512;
+
+/// Holds all configuration options related to fetching the chain while syncing.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// the amount of tipsets requested in a single blocksync request
+    pub request_window: u64,
+    /// the maximum amount of message window requests in flight at the same time. Header
+    /// windows are requested one at a time, as a blocksync request can only start at known
+    /// tipset keys, which are the parents of the previous window.
+    pub max_concurrent_requests: usize,
+}
 
-    /// Once all blocks are validated to the heaviest chain, follow network
-    /// by receiving blocks over the network and validating them
-    Follow,
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            // see https://github.com/filecoin-project/lotus/blob/master/build/params_shared.go#L109 for request window size
+            request_window: 200,
+            max_concurrent_requests: 4,
+        }
+    }
 }
 
 pub struct ChainSyncer<DB, TBeacon> {
-    /// Syncing state of chain sync
-    state: SyncState,
+    /// Syncing state and progress of chain sync
+    state: Arc<RwLock<SyncStatus>>,
+
+    /// Request window size and concurrency used when fetching the chain
+    config: SyncConfig,
 
     /// Drand randomness beacon
     beacon: Arc<TBeacon>,
@@ -83,6 +99,9 @@ pub struct ChainSyncer<DB, TBeacon> {
     /// access and store tipsets / blocks / messages
     chain_store: ChainStore<DB>,
 
+    /// pool of pending messages, fed by messages gossiped over the network
+    mpool: Arc<MessagePool<MpoolProvider<DB>>>,
+
     /// Context to be able to send requests to p2p network
     network: SyncNetworkContext,
 
@@ -91,7 +110,7 @@ pub struct ChainSyncer<DB, TBeacon> {
 
     /// Bad blocks cache, updates based on invalid state transitions.
     /// Will mark any invalid blocks and all childen as bad in this bounded cache
-    bad_blocks: LruCache<Cid, String>,
+    bad_blocks: Arc<BadBlockCache>,
 
     ///  incoming network events to be handled by syncer
     net_handler: NetworkHandler,
@@ -113,31 +132,52 @@ where
 {
     pub fn new(
         chain_store: ChainStore<DB>,
+        mpool: Arc<MessagePool<MpoolProvider<DB>>>,
         beacon: Arc<TBeacon>,
         network_send: Sender<NetworkMessage>,
         network_rx: Receiver<NetworkEvent>,
         genesis: Tipset,
+        config: SyncConfig,
     ) -> Result<Self, Error> {
+        if config.request_window == 0 || config.request_window > MAX_REQUEST_LENGTH {
+            return Err(Error::Other(format!(
+                "Invalid request window {}, must be between 1 and {}",
+                config.request_window, MAX_REQUEST_LENGTH
+            )));
+        }
+        if config.max_concurrent_requests == 0 {
+            return Err(Error::Other(
+                "Amount of concurrent requests must be at least 1".to_owned(),
+            ));
+        }
+
         let state_manager = Arc::new(StateManager::new(chain_store.db.clone()));
 
         // Split incoming channel to handle blocksync requests
-        let (rpc_send, rpc_rx) = channel(20);
+        let pending_requests = PendingRequests::default();
         let (event_send, event_rx) = channel(30);
 
-        let network = SyncNetworkContext::new(network_send, rpc_rx, event_rx);
-
         let peer_manager = Arc::new(PeerManager::default());
 
-        let net_handler = NetworkHandler::new(network_rx, rpc_send, event_send);
+        let network = SyncNetworkContext::new(
+            network_send,
+            Arc::clone(&pending_requests),
+            event_rx,
+            Arc::clone(&peer_manager),
+        );
+
+        let net_handler = NetworkHandler::new(network_rx, pending_requests, event_send);
 
         Ok(Self {
-            state: SyncState::Init,
+            state: Arc::default(),
+            config,
             beacon,
             state_manager,
             chain_store,
+            mpool,
             network,
             genesis,
-            bad_blocks: LruCache::new(1 << 15),
+            bad_blocks: Arc::default(),
             net_handler,
             peer_manager,
             sync_queue: SyncBucketSet::default(),
@@ -186,6 +226,26 @@ where
                         )
                         .await
                 }
+                NetworkEvent::PubsubMessage { source, message } => match message {
+                    PubsubMessage::Block(b) => {
+                        match self.gossip_block_to_fts(source.clone(), b).await {
+                            Ok(fts) => {
+                                if let Err(e) = self.inform_new_head(source, &fts).await {
+                                    warn!("Failed to sync with gossiped block: {}", e);
+                                }
+                            }
+                            Err(e) => {
+                                warn!("Failed to fetch messages of gossiped block: {}", e);
+                            }
+                        }
+                    }
+                    PubsubMessage::Message(m) => {
+                        // Gossiped messages are validated against the current head of the pool
+                        if let Err(e) = self.mpool.add(m).await {
+                            debug!("Failed to add gossiped message from {}: {}", source, e);
+                        }
+                    }
+                },
                 _ => (),
             }
         }
@@ -209,139 +269,102 @@ where
 
         // Get heaviest tipset from storage to sync toward
         let heaviest = self.chain_store.heaviest_tipset().unwrap();
+        self.state
+            .write()
+            .await
+            .init(Arc::clone(&heaviest), Arc::new(head.clone()));
 
         info!("Starting block sync...");
+        let res = self.sync_to(head, &heaviest).await;
+        let mut state = self.state.write().await;
+        match &res {
+            Ok(()) => state.done(),
+            Err(e) => state.error(e.to_string()),
+        }
+        res
+    }
 
+    /// Syncs the headers and messages of the chain from the head down to the heaviest tipset
+    async fn sync_to(&mut self, head: &Tipset, heaviest: &Tipset) -> Result<(), Error> {
         // Sync headers from network from head to heaviest from storage
-        let tipsets = self.sync_headers_reverse(head.clone(), &heaviest).await?;
-        self.set_state(SyncState::Catchup);
+        let tipsets = self.sync_headers_reverse(head.clone(), heaviest).await?;
+        self.set_state(SyncState::Catchup).await;
         // Persist header chain pulled from network
-        self.persist_headers(&tipsets).await?;
+        self.persist_headers(&tipsets)?;
+
+        // Sync and validate messages from fetched tipsets. The heaviest tipset the chain was
+        // synced to has already been validated, or is a trusted checkpoint
+        let unvalidated = match tipsets.split_last() {
+            Some((last, rest)) if last == heaviest => rest,
+            _ => &tipsets,
+        };
+        self.sync_messages_check_state(unvalidated).await?;
 
-        // Sync and validate messages from fetched tipsets
-        self.sync_messages_check_state(&tipsets).await?;
-        self.set_state(SyncState::Follow);
+        // Switch to the synced chain if it is heavier, which reverts the local tipsets of a fork
+        self.chain_store.put_tipsets(head).await?;
+        self.set_state(SyncState::Follow).await;
 
         Ok(())
     }
 
-    /// Syncs messages by first checking state for message existence otherwise fetches messages from blocksync
+    /// Syncs messages by first checking state for message existence otherwise fetches messages from blocksync.
+    /// The tipsets are split into windows, windows with messages missing from storage are requested
+    /// concurrently from the peers and validated from the oldest tipset as they arrive.
     async fn sync_messages_check_state(&mut self, ts: &[Tipset]) -> Result<(), Error> {
-        // see https://github.com/filecoin-project/lotus/blob/master/build/params_shared.go#L109 for request window size
-        const REQUEST_WINDOW: i64 = 200;
-        // TODO refactor type handling
-        // set i to the length of provided tipsets
-        let mut i: i64 = i64::try_from(ts.len())? - 1;
-
-        while i >= 0 {
-            // check storage first to see if we have full tipset
-            let fts = match self.chain_store.fill_tipsets(ts[i as usize].clone()) {
-                Ok(fts) => fts,
-                Err(_) => {
-                    // no full tipset in storage; request messages via blocksync
-
-                    // retrieve peerId used for blocksync request
-                    if let Some(peer_id) = self.peer_manager.get_peer().await {
-                        let mut batch_size = REQUEST_WINDOW;
-                        if i < batch_size {
-                            batch_size = i;
-                        }
-
-                        // set params for blocksync request
-                        let idx = i - batch_size;
-                        let next = &ts[idx as usize];
-                        let req_len = batch_size + 1;
-
-                        // receive tipset bundle from block sync
-                        let ts_bundle = self
-                            .network
-                            .blocksync_request(
-                                peer_id,
-                                BlockSyncRequest {
-                                    start: next.cids().to_vec(),
-                                    request_len: req_len as u64,
-                                    options: MESSAGES,
-                                },
-                            )
-                            .await?;
-
-                        for b in ts_bundle.chain {
-                            let ts = ts[i as usize].clone();
-                            // construct full tipsets from fetched messages
-                            let fts = self.tipset_msgs(
-                                ts,
-                                &b.bls_msgs,
-                                &b.secp_msgs,
-                                b.bls_msg_includes,
-                                b.secp_msg_includes,
-                            )?;
-                            // validate tipset and messages
-                            self.validate_tipsets(fts).await?;
-                            // store messages
-                            self.chain_store.put_messages(&b.bls_msgs)?;
-                            self.chain_store.put_messages(&b.secp_msgs)?;
-                        }
+        // Tipsets are ordered from the newest, so windows are taken from the end of the slice
+        // to fetch and validate the oldest windows first
+        let requests: Vec<_> = ts
+            .rchunks(self.config.request_window as usize)
+            .map(|window| {
+                // check storage first to see if we have the full tipsets
+                let stored = window
+                    .iter()
+                    .map(|t| self.chain_store.fill_tipsets(t.clone()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok();
+                let network = self.network.clone();
+                let window = window.to_vec();
+                async move {
+                    match stored {
+                        Some(fts) => Ok(fts),
+                        // no full tipsets in storage; request messages via blocksync
+                        None => network.blocksync_messages(None, &window).await,
                     }
-                    i -= REQUEST_WINDOW;
-                    continue;
                 }
-            };
-            // full tipset found in storage; validate and continue
-            self.validate_tipsets(fts).await?;
-            i -= 1;
-            continue;
+            })
+            .collect();
+
+        // Requests are spawned to make progress while previous windows are validated
+        let mut windows = stream::iter(requests)
+            .map(task::spawn)
+            .buffered(self.config.max_concurrent_requests);
+
+        while let Some(window) = windows.next().await {
+            let window = window.map_err(Error::Other)?;
+            for fts in window.iter().rev() {
+                // ensure message count of the blocks is below the limit
+                for block in fts.blocks() {
+                    if let Err(e) = check_block_message_limit(block) {
+                        self.bad_blocks
+                            .put(block.cid().clone(), e.to_string())
+                            .await;
+                        return Err(e);
+                    }
+                }
+                // validate tipset and messages
+                self.validate_tipsets(fts).await?;
+                self.state.write().await.set_epoch(fts.epoch());
+                // store messages
+                for block in fts.blocks() {
+                    self.chain_store.put_messages(block.bls_msgs())?;
+                    self.chain_store.put_messages(block.secp_msgs())?;
+                }
+            }
         }
 
         Ok(())
     }
 
-    /// Returns FullTipset with unchecked messages
-    fn tipset_msgs(
-        &self,
-        ts: Tipset,
-        bls_msgs: &[UnsignedMessage],
-        secp_msgs: &[SignedMessage],
-        bmi: Vec<Vec<u64>>,
-        smi: Vec<Vec<u64>>,
-    ) -> Result<FullTipset, Error> {
-        // see https://github.com/filecoin-project/lotus/blob/master/build/params_shared.go#L109 for block message limit
-        const BLOCK_MESSAGE_LIMIT: usize = 512;
-        let mut blocks: Vec<Block> = Vec::with_capacity(ts.blocks().len());
-
-        for (i, header) in ts.blocks().iter().enumerate() {
-            let mut smgs = Vec::new();
-            for (x, _) in smi[i].iter().enumerate() {
-                smgs.push(secp_msgs[x].clone());
-            }
-
-            let mut bmgs = Vec::new();
-            for (y, _) in bmi[i].iter().enumerate() {
-                bmgs.push(bls_msgs[y].clone());
-            }
-
-            // ensure message count is below the limit
-            let msg_count = bls_msgs.len() + secp_msgs.len();
-            if msg_count > BLOCK_MESSAGE_LIMIT {
-                return Err(Error::Other("Block has too many messages".to_string()));
-            }
-
-            // validate message root from header matches message root
-            let sm_root = compute_msg_data(self.chain_store.blockstore(), &bls_msgs, &secp_msgs)?;
-            if header.messages() != &sm_root {
-                return Err(Error::InvalidRoots);
-            }
-            let bls_messages = bmgs.to_vec();
-            let secp_messages = smgs.to_vec();
-
-            blocks.push(Block {
-                header: header.clone(),
-                bls_messages,
-                secp_messages,
-            });
-        }
-        Ok(FullTipset::new(blocks)?)
-    }
-
     /// informs the syncer about a new potential tipset
     /// This should be called when connecting to new peers, and additionally
     /// when receiving new blocks from the network
@@ -352,7 +375,7 @@ where
         }
 
         for block in fts.blocks() {
-            if let Some(bad) = self.bad_blocks.peek(block.cid()) {
+            if let Some(bad) = self.bad_blocks.peek(block.cid()).await {
                 warn!("Bad block detected, cid: {:?}", bad);
                 return Err(Error::Other("Block marked as bad".to_string()));
             }
@@ -380,7 +403,7 @@ where
             .await;
 
         // Only update target on initial sync
-        if self.get_state() == &SyncState::Init {
+        if self.get_state().await == SyncState::Init {
             if let Some(best_target) = self.select_sync_target().await {
                 // TODO revisit this if using for full node, shouldn't start syncing on first update
                 self.sync(&best_target).await?;
@@ -415,7 +438,8 @@ where
         info!("Scheduling incoming tipset to sync: {:?}", tipset.cids());
 
         // check sync status if indicates tipsets are ready to be synced
-        if self.get_state() == &SyncState::Catchup {
+        let state = self.get_state().await;
+        if state == SyncState::Catchup || state == SyncState::Follow {
             // send tipsets to be synced
             self.sync(&tipset).await?;
             return Ok(());
@@ -446,6 +470,7 @@ where
     /// Validates message root from header matches message root generated from the
     /// bls and secp messages contained in the passed in block and stores them in a key-value store
     fn validate_msg_data(&self, block: &Block) -> Result<(), Error> {
+        check_block_message_limit(block)?;
         let sm_root = compute_msg_data(
             self.chain_store.blockstore(),
             block.bls_msgs(),
@@ -461,6 +486,33 @@ where
         Ok(())
     }
 
+    /// Builds a FullTipset from a block received over gossip. Messages are loaded from the store,
+    /// which contains the messages received by the message pool, otherwise the full block is
+    /// requested from the source peer through block sync
+    async fn gossip_block_to_fts(
+        &mut self,
+        source: PeerId,
+        block: GossipBlock,
+    ) -> Result<FullTipset, String> {
+        match chain::block_messages_from_cids(
+            self.chain_store.blockstore(),
+            &block.bls_messages,
+            &block.secpk_messages,
+        ) {
+            Ok((bls_messages, secp_messages)) => FullTipset::new(vec![Block {
+                header: block.header,
+                bls_messages,
+                secp_messages,
+            }])
+            .map_err(|e| e.to_string()),
+            Err(e) => {
+                debug!("Messages for gossiped block not found locally: {}", e);
+                let tsk = TipsetKeys::new(vec![block.header.cid().clone()]);
+                self.network.blocksync_fts(Some(source), &tsk).await
+            }
+        }
+    }
+
     /// Returns FullTipset from store if TipsetKeys exist in key-value store otherwise requests FullTipset
     /// from block sync
     async fn fetch_tipset(
@@ -470,7 +522,7 @@ where
     ) -> Result<FullTipset, String> {
         let fts = match self.load_fts(tsk) {
             Ok(fts) => fts,
-            _ => return self.network.blocksync_fts(peer_id, tsk).await,
+            _ => return self.network.blocksync_fts(Some(peer_id), tsk).await,
         };
 
         Ok(fts)
@@ -719,14 +771,14 @@ where
         Ok(())
     }
     /// validates tipsets and adds header data to tipset tracker
-    async fn validate_tipsets(&mut self, fts: FullTipset) -> Result<(), Error> {
+    async fn validate_tipsets(&mut self, fts: &FullTipset) -> Result<(), Error> {
         if fts.to_tipset() == self.genesis {
             return Ok(());
         }
 
         for b in fts.blocks() {
             if let Err(e) = self.validate(&b).await {
-                self.bad_blocks.put(b.cid().clone(), e.to_string());
+                self.bad_blocks.put(b.cid().clone(), e.to_string()).await;
                 return Err(Error::Other("Invalid blocks detected".to_string()));
             }
             self.chain_store.set_tipset_tracker(b.header())?;
@@ -827,7 +879,8 @@ where
         // Loop until most recent tipset height is less than to tipset height
         'sync: while let Some(cur_ts) = return_set.last() {
             // Check if parent cids exist in bad block caches
-            self.validate_tipset_against_cache(cur_ts.parents(), &accepted_blocks)?;
+            self.validate_tipset_against_cache(cur_ts.parents(), &accepted_blocks)
+                .await?;
 
             if cur_ts.epoch() <= to_epoch {
                 // Current tipset is less than epoch of tipset syncing toward
@@ -842,28 +895,27 @@ where
                 continue;
             }
 
-            const REQUEST_WINDOW: u64 = 100;
+            // Each window is requested from the parents of the last received tipset
             let epoch_diff = cur_ts.epoch() - to_epoch;
-            let window = min(epoch_diff, REQUEST_WINDOW);
+            let window = min(epoch_diff, self.config.request_window);
 
             // update sync state to Bootstrap indicating we are acquiring a 'secure enough' set of peers
-            self.set_state(SyncState::Bootstrap);
+            self.set_state(SyncState::Bootstrap).await;
 
             let peer_id = self.get_peer().await;
 
             // checkpoint established
-            self.set_state(SyncState::Checkpoint);
+            self.set_state(SyncState::Checkpoint).await;
 
             // Load blocks from network using blocksync
             let tipsets: Vec<Tipset> = match self
                 .network
-                .blocksync_headers(peer_id.clone(), cur_ts.parents(), window)
+                .blocksync_headers(Some(peer_id), cur_ts.parents(), window)
                 .await
             {
                 Ok(ts) => ts,
                 Err(e) => {
-                    warn!("Failed blocksync request to peer {:?}: {}", peer_id, e);
-                    self.peer_manager.remove_peer(&peer_id).await;
+                    warn!("Failed to retrieve headers with blocksync: {}", e);
                     continue;
                 }
             };
@@ -876,9 +928,11 @@ where
                     break 'sync;
                 }
                 // Check Cids of blocks against bad block cache
-                self.validate_tipset_against_cache(&ts.key(), &accepted_blocks)?;
+                self.validate_tipset_against_cache(&ts.key(), &accepted_blocks)
+                    .await?;
 
                 accepted_blocks.extend_from_slice(ts.cids());
+                self.state.write().await.set_epoch(ts.epoch());
                 // Add tipset to vector of tipsets to return
                 return_set.push(ts);
             }
@@ -897,22 +951,28 @@ where
             }
             // add fork into return set
             let fork = self.sync_fork(&last_ts, &to).await?;
+            for ts in fork.iter() {
+                self.validate_tipset_against_cache(&ts.key(), &accepted_blocks)
+                    .await?;
+                accepted_blocks.extend_from_slice(ts.cids());
+            }
             return_set.extend(fork);
         }
 
         Ok(return_set)
     }
     /// checks to see if tipset is included in bad clocks cache
-    fn validate_tipset_against_cache(
-        &mut self,
+    async fn validate_tipset_against_cache(
+        &self,
         ts: &TipsetKeys,
         accepted_blocks: &[Cid],
     ) -> Result<(), Error> {
         for cid in ts.cids() {
-            if let Some(reason) = self.bad_blocks.get(cid).cloned() {
+            if let Some(reason) = self.bad_blocks.get(cid).await {
                 for bh in accepted_blocks {
                     self.bad_blocks
-                        .put(bh.clone(), format!("chain contained {}", cid));
+                        .put(bh.clone(), format!("chain contained {}", cid))
+                        .await;
                 }
 
                 return Err(Error::Other(format!(
@@ -923,67 +983,82 @@ where
         }
         Ok(())
     }
-    /// fork detected, collect tipsets to be included in return_set sync_headers_reverse
+    /// Fork detected, searches for the common ancestor of the fork and the local chain of the `to`
+    /// tipset. Returns the tipsets of the fork below `head`, excluding the common ancestor, to be
+    /// included in the return set of sync_headers_reverse.
     async fn sync_fork(&mut self, head: &Tipset, to: &Tipset) -> Result<Vec<Tipset>, Error> {
-        let peer_id = self.get_peer().await;
-        // TODO move to shared parameter (from actors crate most likely)
-        const FORK_LENGTH_THRESHOLD: u64 = 500;
-
         // Load blocks from network using blocksync
         let tips: Vec<Tipset> = self
             .network
-            .blocksync_headers(peer_id, head.parents(), FORK_LENGTH_THRESHOLD)
+            .blocksync_headers(None, head.parents(), FORK_LENGTH_THRESHOLD)
             .await
-            .map_err(|_| Error::Other("Could not retrieve tipset".to_string()))?;
+            .map_err(|e| Error::Other(format!("Could not retrieve fork tipsets: {}", e)))?;
 
+        // Walk back the local chain and the fork until a common tipset is found
         let mut ts = self.chain_store.tipset_from_keys(to.parents())?;
-
-        for i in 0..tips.len() {
-            while ts.epoch() > tips[i].epoch() {
-                if ts.epoch() == 0 {
-                    return Err(Error::Other(
-                        "Synced chain forked at genesis, refusing to sync".to_string(),
-                    ));
-                }
+        for (i, tip) in tips.iter().enumerate() {
+            while ts.epoch() > tip.epoch() {
                 ts = self.chain_store.tipset_from_keys(ts.parents())?;
             }
-            if ts == tips[i] {
-                return Ok(tips[0..=i].to_vec());
+            if &ts == tip {
+                return Ok(tips[..i].to_vec());
+            }
+            if ts.epoch() == 0 && tip.epoch() == 0 {
+                return Err(Error::Other(
+                    "Synced chain forked at genesis, refusing to sync".to_string(),
+                ));
             }
         }
 
-        Err(Error::Other(
-            "Fork longer than threshold finality of 500".to_string(),
-        ))
+        Err(Error::Other(format!(
+            "Fork longer than threshold finality of {}",
+            FORK_LENGTH_THRESHOLD
+        )))
     }
 
-    /// Persists headers from tipset slice to chain store
-    async fn persist_headers(&mut self, tipsets: &[Tipset]) -> Result<(), Error> {
-        for tipset in tipsets.iter() {
-            self.chain_store.put_tipsets(tipset).await?
+    /// Persists headers from tipset slice to chain store, without updating the heaviest tipset
+    /// until the messages of the chain are validated. Tipsets are persisted from the oldest to
+    /// the newest so the ancestry of each tipset is available when the head changes.
+    fn persist_headers(&self, tipsets: &[Tipset]) -> Result<(), Error> {
+        for tipset in tipsets.iter().rev() {
+            self.chain_store.persist_headers(tipset)?
         }
         Ok(())
     }
     /// Returns the managed sync status
-    pub fn get_state(&self) -> &SyncState {
-        &self.state
+    pub async fn get_state(&self) -> SyncState {
+        self.state.read().await.stage.clone()
     }
     /// Sets the managed sync status
-    pub fn set_state(&mut self, new_state: SyncState) {
-        self.state = new_state
+    pub async fn set_state(&self, new_state: SyncState) {
+        self.state.write().await.set_stage(new_state)
+    }
+    /// Returns the shared sync status, including the progress towards the sync target
+    pub fn sync_status(&self) -> Arc<RwLock<SyncStatus>> {
+        Arc::clone(&self.state)
+    }
+    /// Returns the shared cache of blocks marked as bad
+    pub fn bad_blocks(&self) -> Arc<BadBlockCache> {
+        Arc::clone(&self.bad_blocks)
     }
 
     async fn get_peer(&self) -> PeerId {
-        while self.peer_manager.is_empty().await {
+        loop {
+            if let Some(peer_id) = self.peer_manager.get_peer().await {
+                return peer_id;
+            }
             warn!("No valid peers to sync, waiting for other nodes");
             task::sleep(Duration::from_secs(5)).await;
         }
+    }
+}
 
-        self.peer_manager
-            .get_peer()
-            .await
-            .expect("Peer set is not empty here")
+/// Returns an error if the block includes more messages than the block message limit
+fn check_block_message_limit(block: &Block) -> Result<(), Error> {
+    if block.bls_msgs().len() + block.secp_msgs().len() > BLOCK_MESSAGE_LIMIT {
+        return Err(Error::Other("Block has too many messages".to_string()));
     }
+    Ok(())
 }
 
 /// Returns message root CID from bls and secp message contained in the param Block
@@ -1023,18 +1098,29 @@ mod tests {
     use beacon::MockBeacon;
     use blocks::BlockHeader;
     use db::MemoryDB;
+    use forest_libp2p::blocksync::{BlockSyncRequest, BlockSyncResponse, TipsetBundle, BLOCKS};
+    use forest_libp2p::rpc::{RPCEvent, RPCRequest, RPCResponse};
     use forest_libp2p::NetworkEvent;
     use std::sync::Arc;
-    use test_utils::{construct_blocksync_response, construct_messages, construct_tipset};
+    use test_utils::{
+        construct_block, construct_blocksync_response, construct_messages, construct_tipset,
+        construct_tipset_bundle,
+    };
 
     fn chain_syncer_setup(
         db: Arc<MemoryDB>,
-    ) -> (ChainSyncer<MemoryDB, MockBeacon>, Sender<NetworkEvent>) {
-        let chain_store = ChainStore::new(db);
-
-        let (local_sender, _test_receiver) = channel(20);
+    ) -> (
+        ChainSyncer<MemoryDB, MockBeacon>,
+        Sender<NetworkEvent>,
+        Receiver<NetworkMessage>,
+    ) {
+        let chain_store = ChainStore::new(db.clone());
+
+        let (local_sender, test_receiver) = channel(20);
         let (event_sender, event_receiver) = channel(20);
 
+        let mpool = test_mpool(db, local_sender.clone());
+
         let gen = dummy_header();
         chain_store.set_genesis(gen.clone()).unwrap();
 
@@ -1044,26 +1130,60 @@ mod tests {
         (
             ChainSyncer::new(
                 chain_store,
+                mpool,
                 beacon,
                 local_sender,
                 event_receiver,
                 genesis_ts,
+                SyncConfig::default(),
             )
             .unwrap(),
             event_sender,
+            test_receiver,
+        )
+    }
+
+    pub(super) fn test_mpool(
+        db: Arc<MemoryDB>,
+        network_send: Sender<NetworkMessage>,
+    ) -> Arc<MessagePool<MpoolProvider<MemoryDB>>> {
+        let provider = MpoolProvider::new(Arc::new(StateManager::new(db)));
+        Arc::new(
+            MessagePool::new(
+                provider,
+                "test".to_owned(),
+                network_send,
+                Default::default(),
+            )
+            .unwrap(),
         )
     }
 
-    fn send_blocksync_response(event_sender: Sender<NetworkEvent>) {
-        let rpc_response = construct_blocksync_response();
+    /// Waits for the next blocksync request sent to the network and returns its request id
+    async fn next_blocksync_request(
+        network_rx: &mut Receiver<NetworkMessage>,
+    ) -> (usize, BlockSyncRequest) {
+        loop {
+            if let Some(NetworkMessage::RPC {
+                event: RPCEvent::Request(req_id, RPCRequest::BlockSync(request)),
+                ..
+            }) = network_rx.next().await
+            {
+                return (req_id, request);
+            }
+        }
+    }
 
-        task::block_on(async {
+    /// Responds to the next blocksync request sent to the network with the given response
+    fn send_blocksync_response(
+        event_sender: Sender<NetworkEvent>,
+        mut network_rx: Receiver<NetworkMessage>,
+        response: RPCResponse,
+    ) {
+        task::spawn(async move {
+            let (req_id, _) = next_blocksync_request(&mut network_rx).await;
             event_sender
-                .send(NetworkEvent::RPCResponse {
-                    // TODO update this, only matching first index of requestId
-                    req_id: 1,
-                    response: rpc_response,
-                })
+                .send(NetworkEvent::RPCResponse { req_id, response })
                 .await;
         });
     }
@@ -1089,11 +1209,11 @@ mod tests {
     #[test]
     fn sync_headers_reverse_given_tipsets_test() {
         let db = Arc::new(MemoryDB::default());
-        let (mut cs, event_sender) = chain_syncer_setup(db);
+        let (mut cs, event_sender, network_rx) = chain_syncer_setup(db);
 
         cs.net_handler.spawn(Arc::clone(&cs.peer_manager));
-        // send blocksync response to channel
-        send_blocksync_response(event_sender);
+        // respond to the blocksync request sent by the syncer
+        send_blocksync_response(event_sender, network_rx, construct_blocksync_response());
 
         // params for sync_headers_reverse
         let source = PeerId::random();
@@ -1112,7 +1232,7 @@ mod tests {
     #[test]
     fn compute_msg_data_given_msgs_test() {
         let db = Arc::new(MemoryDB::default());
-        let (cs, _) = chain_syncer_setup(db);
+        let (cs, _, _) = chain_syncer_setup(db);
 
         let (bls, secp) = construct_messages();
 
@@ -1123,4 +1243,122 @@ mod tests {
         let root = compute_msg_data(cs.chain_store.blockstore(), &[bls], &[secp]).unwrap();
         assert_eq!(root, expected_root);
     }
+
+    #[test]
+    fn block_message_limit() {
+        let db = Arc::new(MemoryDB::default());
+        let (cs, _, _) = chain_syncer_setup(db);
+
+        let (bls, secp) = construct_messages();
+        let mut block = construct_block();
+        block.bls_messages = vec![bls; BLOCK_MESSAGE_LIMIT / 2];
+        block.secp_messages = vec![secp; BLOCK_MESSAGE_LIMIT / 2];
+        assert!(check_block_message_limit(&block).is_ok());
+
+        // A block with 513 messages is rejected before its message root is checked
+        block.bls_messages.push(block.bls_messages[0].clone());
+        assert_eq!(block.bls_msgs().len() + block.secp_msgs().len(), 513);
+        assert_eq!(
+            cs.validate_msg_data(&block).unwrap_err().to_string(),
+            "Block has too many messages"
+        );
+    }
+
+    #[test]
+    fn concurrent_blocksync_responses_matched_to_requests() {
+        let db = Arc::new(MemoryDB::default());
+        let (cs, event_sender, mut network_rx) = chain_syncer_setup(db);
+        cs.net_handler.spawn(Arc::clone(&cs.peer_manager));
+
+        let source = PeerId::random();
+        task::block_on(async move {
+            cs.peer_manager.add_peer(source.clone(), None).await;
+
+            let first = construct_tipset(2, 10);
+            let second = construct_tipset(3, 10);
+            let network = cs.network.clone();
+            let (first_key, second_key) = (first.key().clone(), second.key().clone());
+            let requests = task::spawn(async move {
+                futures::join!(
+                    network.blocksync_headers(None, &first_key, 1),
+                    network.blocksync_headers(None, &second_key, 1),
+                )
+            });
+
+            // Respond to the requests in reverse order of arrival
+            let received = vec![
+                next_blocksync_request(&mut network_rx).await,
+                next_blocksync_request(&mut network_rx).await,
+            ];
+            for (req_id, request) in received.into_iter().rev() {
+                assert_eq!(request.options, BLOCKS);
+                let epoch = if request.start == first.cids() { 2 } else { 3 };
+                event_sender
+                    .send(NetworkEvent::RPCResponse {
+                        req_id,
+                        response: RPCResponse::BlockSync(BlockSyncResponse {
+                            chain: vec![construct_tipset_bundle(epoch, 10)],
+                            status: 0,
+                            message: String::new(),
+                        }),
+                    })
+                    .await;
+            }
+
+            let (first_res, second_res) = requests.await;
+            assert_eq!(first_res.unwrap(), vec![first]);
+            assert_eq!(second_res.unwrap(), vec![second]);
+        });
+    }
+
+    #[test]
+    fn sync_fork_finds_common_ancestor() {
+        let db = Arc::new(MemoryDB::default());
+        let (mut cs, event_sender, network_rx) = chain_syncer_setup(db);
+        cs.net_handler.spawn(Arc::clone(&cs.peer_manager));
+
+        let header = |epoch: u64, miner: u64, parent: &BlockHeader| {
+            BlockHeader::builder()
+                .epoch(epoch)
+                .parents(TipsetKeys::new(vec![parent.cid().clone()]))
+                .miner_address(Address::new_id(miner))
+                .build_and_validate()
+                .unwrap()
+        };
+        let ts = |h: &BlockHeader| Tipset::new(vec![h.clone()]).unwrap();
+        let gen = dummy_header();
+        let a1 = header(1, 1, &gen);
+        let a2 = header(2, 1, &a1);
+        let a3 = header(3, 1, &a2);
+        let b2 = header(2, 2, &a1);
+        let b3 = header(3, 2, &b2);
+        for h in &[&a1, &a2, &a3] {
+            cs.chain_store.persist_headers(&ts(h)).unwrap();
+        }
+
+        // Peer serves the fork back from the parents of the fork head
+        let bundle = |h: &BlockHeader| TipsetBundle {
+            blocks: vec![h.clone()],
+            bls_msgs: Vec::new(),
+            bls_msg_includes: vec![Vec::new()],
+            secp_msgs: Vec::new(),
+            secp_msg_includes: vec![Vec::new()],
+        };
+        send_blocksync_response(
+            event_sender,
+            network_rx,
+            RPCResponse::BlockSync(BlockSyncResponse {
+                chain: vec![bundle(&b2), bundle(&a1), bundle(&gen)],
+                status: 0,
+                message: String::new(),
+            }),
+        );
+
+        let source = PeerId::random();
+        task::block_on(async move {
+            cs.peer_manager.add_peer(source, None).await;
+            let fork = cs.sync_fork(&ts(&b3), &ts(&a3)).await.unwrap();
+            assert_eq!(fork, vec![ts(&b2)]);
+        });
+    }
 }
@@ -15,10 +15,11 @@ use std::time::Duration;
 fn peer_manager_update() {
     let db = Arc::new(MemoryDB::default());
 
-    let chain_store = ChainStore::new(db);
+    let chain_store = ChainStore::new(db.clone());
 
     let (local_sender, _test_receiver) = channel(20);
     let (event_sender, event_receiver) = channel(20);
+    let mpool = super::tests::test_mpool(db, local_sender.clone());
 
     let dummy_header = BlockHeader::builder()
         .miner_address(Address::new_id(1000))
@@ -33,10 +34,12 @@ fn peer_manager_update() {
     let beacon = Arc::new(MockBeacon::new(Duration::from_secs(1)));
     let cs = ChainSyncer::new(
         chain_store,
+        mpool,
         beacon,
         local_sender,
         event_receiver,
         genesis_ts,
+        SyncConfig::default(),
     )
     .unwrap();
 
@@ -61,6 +64,82 @@ fn peer_manager_update() {
         task::sleep(Duration::from_millis(50)).await;
 
         assert_eq!(peer_manager.len().await, 1);
-        assert_eq!(peer_manager.get_peer().await, Some(source_clone));
+        assert_eq!(peer_manager.get_peer().await, Some(source_clone.clone()));
+
+        let latency = Duration::from_millis(20);
+        event_sender
+            .send(NetworkEvent::HelloResponse {
+                source: source_clone.clone(),
+                latency,
+            })
+            .await;
+        event_sender
+            .send(NetworkEvent::PeerDisconnected {
+                peer_id: source_clone.clone(),
+            })
+            .await;
+        task::sleep(Duration::from_millis(50)).await;
+
+        // Disconnected peers are removed along with their latency
+        assert_eq!(peer_manager.len().await, 0);
+        assert_eq!(peer_manager.get_latency(&source_clone).await, None);
+    });
+}
+
+#[test]
+fn peer_manager_selection_and_bans() {
+    let peer_manager = PeerManager::default();
+    let good = PeerId::random();
+    let bad = PeerId::random();
+
+    task::block_on(async {
+        peer_manager.add_peer(good.clone(), None).await;
+        peer_manager.add_peer(bad.clone(), None).await;
+        peer_manager
+            .update_latency(good.clone(), Duration::from_millis(30))
+            .await;
+        assert_eq!(
+            peer_manager.get_latency(&good).await,
+            Some(Duration::from_millis(30))
+        );
+
+        peer_manager
+            .log_success(&good, Duration::from_millis(100), 10)
+            .await;
+        peer_manager
+            .log_success(&good, Duration::from_millis(300), 20)
+            .await;
+        let stats = peer_manager.get_peer_stats(&good).await.unwrap();
+        assert_eq!(stats.successes, 2);
+        assert_eq!(stats.average_time, Duration::from_millis(200));
+        assert_eq!(stats.bytes, 30);
+
+        // Averaging very long response times does not overflow
+        let slow = PeerId::random();
+        for _ in 0..2 {
+            peer_manager
+                .log_success(&slow, Duration::from_secs(u64::MAX), 0)
+                .await;
+        }
+        let stats = peer_manager.get_peer_stats(&slow).await.unwrap();
+        assert_eq!(stats.average_time, Duration::from_nanos(u64::MAX));
+
+        // Excluded peers are never selected
+        assert_eq!(
+            peer_manager.get_peer_except(&[good.clone()]).await,
+            Some(bad.clone())
+        );
+
+        // Repeated failures ban the peer, also across reconnects
+        for _ in 0..3 {
+            peer_manager.log_failure(&bad).await;
+        }
+        assert!(peer_manager.get_peer_stats(&bad).await.unwrap().is_banned());
+        for _ in 0..10 {
+            assert_eq!(peer_manager.get_peer().await, Some(good.clone()));
+        }
+        peer_manager.remove_peer(&bad).await;
+        peer_manager.add_peer(bad.clone(), None).await;
+        assert_eq!(peer_manager.get_peer_except(&[good.clone()]).await, None);
     });
 }
@@ -0,0 +1,93 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use blocks::Tipset;
+use clock::ChainEpoch;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+#[derive(PartialEq, Debug, Clone)]
+/// Current state of the ChainSyncer
+pub enum SyncState {
+    /// Initial state, validating data structures and local chain
+    Init,
+
+    /// Bootstrap to the network, and acquire a secure enough set of peers
+    Bootstrap,
+
+    /// Syncing to checkpoint (using BlockSync for now)
+    Checkpoint,
+
+    /// Receive new blocks from the network and sync toward heaviest tipset
+    Catchup,
+
+    /// Once all blocks are validated to the heaviest chain, follow network
+    /// by receiving blocks over the network and validating them
+    Follow,
+}
+
+/// Progress of the ChainSyncer towards its current sync target
+#[derive(Clone, Debug)]
+pub struct SyncStatus {
+    /// Current stage of the syncer
+    pub stage: SyncState,
+    /// Heaviest local tipset when syncing to the target started
+    pub base: Option<Arc<Tipset>>,
+    /// Tipset being synced to
+    pub target: Option<Arc<Tipset>>,
+    /// Epoch of the last tipset fetched or validated in the current stage
+    pub epoch: ChainEpoch,
+    /// Time syncing to the target started
+    pub start: Option<SystemTime>,
+    /// Time syncing to the target ended, successfully or not
+    pub end: Option<SystemTime>,
+    /// Error syncing to the target failed with
+    pub message: String,
+}
+
+impl Default for SyncStatus {
+    fn default() -> Self {
+        Self {
+            stage: SyncState::Init,
+            base: None,
+            target: None,
+            epoch: 0,
+            start: None,
+            end: None,
+            message: String::new(),
+        }
+    }
+}
+
+impl SyncStatus {
+    /// Starts syncing from the base to a new target
+    pub(crate) fn init(&mut self, base: Arc<Tipset>, target: Arc<Tipset>) {
+        self.epoch = base.epoch();
+        self.base = Some(base);
+        self.target = Some(target);
+        self.start = Some(SystemTime::now());
+        self.end = None;
+        self.message.clear();
+    }
+
+    /// Sets the stage of the syncer
+    pub(crate) fn set_stage(&mut self, stage: SyncState) {
+        self.stage = stage;
+    }
+
+    /// Sets the epoch the current stage progressed to
+    pub(crate) fn set_epoch(&mut self, epoch: ChainEpoch) {
+        self.epoch = epoch;
+    }
+
+    /// Marks the target as synced
+    pub(crate) fn done(&mut self) {
+        self.end = Some(SystemTime::now());
+    }
+
+    /// Marks syncing to the target as failed with the given error
+    pub(crate) fn error(&mut self, err: String) {
+        self.message = err;
+        self.end = Some(SystemTime::now());
+    }
+}
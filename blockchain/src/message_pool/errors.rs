@@ -0,0 +1,64 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use chain::Error as ChainErr;
+use encoding::Error as EncErr;
+use state_manager::Error as StErr;
+use thiserror::Error;
+
+/// Message pool error
+#[derive(Debug, PartialEq, Error)]
+pub enum Error {
+    /// Message exceeds the maximum serialized message size
+    #[error("Message is too big")]
+    MessageTooBig,
+    /// Pool has reached the configured maximum amount of pending messages
+    #[error("Message pool is full")]
+    MPoolFull,
+    /// Message sequence is lower than the sender's sequence in the head state
+    #[error("Message nonce too low")]
+    NonceTooLow,
+    /// Message sequence is too far ahead of the sender's sequence in the head state
+    #[error("Message nonce gap exceeds maximum allowed")]
+    NonceGap,
+    /// Sender cannot pay for all of its pending messages
+    #[error("Not enough funds to execute transaction")]
+    NotEnoughFunds,
+    /// Message with the same sequence is pending and the new gas price is not high enough
+    /// to replace it
+    #[error("Gas price is too low to replace pending message")]
+    GasPriceTooLow,
+    /// The exact message is already pending in the pool
+    #[error("Message already in message pool")]
+    DuplicateMessage,
+    /// Signature of the message could not be verified
+    #[error("Invalid message signature: {0}")]
+    InvalidSignature(String),
+    /// Pool has no head tipset to validate messages against
+    #[error("Message pool has no head tipset")]
+    NoHead,
+    /// Error originating from the chain store
+    #[error(transparent)]
+    Chain(#[from] ChainErr),
+    /// Error originating from state
+    #[error(transparent)]
+    State(#[from] StErr),
+    /// Error originating from encoding arbitrary data
+    #[error("{0}")]
+    Encoding(String),
+    /// Other message pool error
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<EncErr> for Error {
+    fn from(e: EncErr) -> Error {
+        Error::Encoding(e.to_string())
+    }
+}
+
+impl From<String> for Error {
+    fn from(e: String) -> Self {
+        Error::Other(e)
+    }
+}
@@ -1,6 +1,10 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod errors;
 mod pool;
+mod provider;
 
-pub use pool::*;
+pub use self::errors::*;
+pub use self::pool::*;
+pub use self::provider::*;
@@ -1,71 +1,742 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use message::SignedMessage;
+use super::{Error, Provider};
+use address::Address;
+use async_std::sync::{RwLock, Sender};
+use blocks::Tipset;
+use chain::HeadChange;
+use cid::Cid;
+use crypto::{Signature, SignatureType};
+use encoding::Cbor;
+use flo_stream::Subscriber;
+use forest_libp2p::{NetworkMessage, PUBSUB_MSG_STR};
+use futures::StreamExt;
+use libp2p::gossipsub::Topic;
+use log::{error, warn};
+use lru::LruCache;
+use message::{Message, SignedMessage, UnsignedMessage};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use vm::TokenAmount;
 
-trait PoolValidator {
-    // fn validate(&self, msg: SignedMessage);
-}
+/// Maximum size of a serialized message accepted into the pool
+const MAX_MESSAGE_SIZE: usize = 32 << 10;
+
+/// Replacing a pending message requires the new gas price to be at least
+/// `REPLACE_BY_FEE_NUM / REPLACE_BY_FEE_DEN` higher than the pending message's gas price
+const REPLACE_BY_FEE_NUM: u64 = 1;
+const REPLACE_BY_FEE_DEN: u64 = 4;
+
+/// Number of BLS signatures of pending messages kept to re-add messages from reverted blocks
+const BLS_SIG_CACHE_SIZE: usize = 40000;
 
-#[allow(dead_code)]
 /// Holds all configuration options related to nodes message pool (mpool).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct MessagePoolConfig {
     /// the maximum number of pending messages we will allow in the message pool at any time
-    max_pool_size: u64,
+    pub max_pool_size: u64,
     /// the maximum nonce of a message past the last received on chain
-    max_nonce_gap: u64,
+    pub max_nonce_gap: u64,
+}
+
+impl Default for MessagePoolConfig {
+    fn default() -> Self {
+        Self {
+            max_pool_size: 10000,
+            max_nonce_gap: 100,
+        }
+    }
+}
+
+/// Nonce ordered set of pending messages from a single sender.
+#[derive(Default)]
+struct MsgSet {
+    msgs: BTreeMap<u64, SignedMessage>,
+}
+
+impl MsgSet {
+    /// Adds a message to the set. If a message with the same sequence is already pending,
+    /// it is only replaced if the new message pays a high enough gas price premium.
+    fn add(&mut self, msg: SignedMessage) -> Result<(), Error> {
+        if let Some(existing) = self.msgs.get(&msg.sequence()) {
+            if existing == &msg {
+                return Err(Error::DuplicateMessage);
+            }
+            let old_price = existing.gas_price();
+            let min_price = old_price + old_price * REPLACE_BY_FEE_NUM / REPLACE_BY_FEE_DEN + 1u64;
+            if msg.gas_price() < &min_price {
+                return Err(Error::GasPriceTooLow);
+            }
+        }
+        self.msgs.insert(msg.sequence(), msg);
+        Ok(())
+    }
+
+    /// Returns the sequence following the pending messages that can be applied in order
+    /// on top of the given state sequence
+    fn next_nonce(&self, state_nonce: u64) -> u64 {
+        let mut next = state_nonce;
+        while self.msgs.contains_key(&next) {
+            next += 1;
+        }
+        next
+    }
+
+    /// Returns the funds required for all pending messages, excluding the message with
+    /// the given sequence
+    fn required_funds(&self, exclude: u64) -> TokenAmount {
+        self.msgs
+            .values()
+            .filter(|m| m.sequence() != exclude)
+            .map(|m| m.required_funds())
+            .sum()
+    }
 }
 
-#[allow(dead_code)]
-/// Pool keeps an unordered, de-duplicated set of Messages and supports removal by CID.
+/// MessagePool keeps a de-duplicated set of pending messages, ordered by nonce per sender.
 ///
-/// By 'de-duplicated' we mean that insertion of a message by cid that already
-/// exists is a no-op. We use a Pool to store all messages received by this node
-/// via network or directly created via user command that have yet to be included
-/// in a block. Messages are removed as they are processed.
+/// We use the MessagePool to store all messages received by this node via network or
+/// directly created via user command that have yet to be included in a block. Messages
+/// are validated against the state of the current head and removed as they are included
+/// on chain. Messages pushed locally are published to the network.
 ///
-/// Pool is safe for concurrent access.
-pub struct Pool {
-    cfg: MessagePoolConfig,
-    validator: dyn PoolValidator,
-    // pending CID messages
-    // addressNonce
+/// MessagePool is safe for concurrent access.
+pub struct MessagePool<P> {
+    api: P,
+    config: MessagePoolConfig,
+    network_name: String,
+    /// Sender to publish locally pushed messages over gossipsub
+    network_sender: Sender<NetworkMessage>,
+    /// Pending messages keyed by sender address
+    pending: RwLock<HashMap<Address, MsgSet>>,
+    /// Head tipset that incoming messages are validated against
+    cur_tipset: RwLock<Option<Tipset>>,
+    /// Signatures of BLS messages, which are stored unsigned in blocks, keyed by message Cid
+    bls_sig_cache: RwLock<LruCache<Cid, Signature>>,
 }
 
-#[allow(dead_code)]
-struct TimedMessage {
-    // message
-    added_at: u64,
-}
+impl<P> MessagePool<P>
+where
+    P: Provider,
+{
+    /// Creates a new MessagePool using the heaviest tipset of the provider as the head
+    pub fn new(
+        api: P,
+        network_name: String,
+        network_sender: Sender<NetworkMessage>,
+        config: MessagePoolConfig,
+    ) -> Result<Self, Error> {
+        let cur_tipset = api.get_heaviest_tipset()?;
+        Ok(Self {
+            api,
+            config,
+            network_name,
+            network_sender,
+            pending: RwLock::new(HashMap::new()),
+            cur_tipset: RwLock::new(cur_tipset),
+            bls_sig_cache: RwLock::new(LruCache::new(BLS_SIG_CACHE_SIZE)),
+        })
+    }
+
+    /// Adds a locally submitted message to the pool and publishes it to the network.
+    /// Returns the Cid of the signed message.
+    pub async fn push(&self, msg: SignedMessage) -> Result<Cid, Error> {
+        let cid = self.add(msg.clone()).await?;
+        let topic = Topic::new(format!("{}/{}", PUBSUB_MSG_STR, self.network_name));
+        self.network_sender
+            .send(NetworkMessage::PubsubMessage {
+                topic,
+                message: msg.marshal_cbor()?,
+            })
+            .await;
+        Ok(cid)
+    }
+
+    /// Validates a message against the state of the current head, persists it in the
+    /// blockstore and adds it to the pending messages of its sender.
+    /// Returns the Cid of the signed message.
+    pub async fn add(&self, msg: SignedMessage) -> Result<Cid, Error> {
+        check_message(&msg)?;
+
+        let cur_ts = self.cur_tipset.read().await;
+        let ts = cur_ts.as_ref().ok_or(Error::NoHead)?;
+        let ts_msgs = self.messages_for_tipset(ts)?;
+
+        let actor = self.api.state_get_actor(msg.from(), ts)?;
+        let state_nonce = next_state_nonce(msg.from(), actor.sequence, &ts_msgs);
+        if msg.sequence() < state_nonce {
+            return Err(Error::NonceTooLow);
+        }
+        if msg.sequence() > state_nonce + self.config.max_nonce_gap {
+            return Err(Error::NonceGap);
+        }
+
+        let mut pending = self.pending.write().await;
+
+        let (pending_funds, replaces) = match pending.get(msg.from()) {
+            Some(mset) => (
+                mset.required_funds(msg.sequence()),
+                mset.msgs.contains_key(&msg.sequence()),
+            ),
+            None => (TokenAmount::default(), false),
+        };
+        if !replaces && pending_count(&pending) >= self.config.max_pool_size {
+            return Err(Error::MPoolFull);
+        }
+        if actor.balance < pending_funds + msg.required_funds() {
+            return Err(Error::NotEnoughFunds);
+        }
+
+        let cid = self.api.put_message(&msg)?;
+        if msg.signature().signature_type() == SignatureType::BLS {
+            // Bls messages are included in blocks unsigned, so they are stored by that Cid too
+            self.api.put_message(msg.message())?;
+            self.bls_sig_cache
+                .write()
+                .await
+                .put(msg.message().cid()?, msg.signature().clone());
+        }
+        pending.entry(*msg.from()).or_default().add(msg)?;
+        Ok(cid)
+    }
+
+    /// Returns the next sequence to use for the address, accounting for pending messages
+    pub async fn get_nonce(&self, addr: &Address) -> Result<u64, Error> {
+        let cur_ts = self.cur_tipset.read().await;
+        let ts = cur_ts.as_ref().ok_or(Error::NoHead)?;
+        let ts_msgs = self.messages_for_tipset(ts)?;
+
+        let actor = self.api.state_get_actor(addr, ts)?;
+        let state_nonce = next_state_nonce(addr, actor.sequence, &ts_msgs);
+
+        Ok(match self.pending.read().await.get(addr) {
+            Some(mset) => mset.next_nonce(state_nonce),
+            None => state_nonce,
+        })
+    }
+
+    /// Returns all pending messages, ordered by nonce for each sender
+    pub async fn pending(&self) -> Vec<SignedMessage> {
+        self.pending
+            .read()
+            .await
+            .values()
+            .flat_map(|mset| mset.msgs.values().cloned())
+            .collect()
+    }
+
+    /// Returns the pending messages of a single sender ordered by nonce
+    pub async fn pending_for(&self, addr: &Address) -> Vec<SignedMessage> {
+        match self.pending.read().await.get(addr) {
+            Some(mset) => mset.msgs.values().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Removes the pending message of the sender with the given sequence, if it exists
+    pub async fn remove(&self, from: &Address, sequence: u64) {
+        let mut pending = self.pending.write().await;
+        if let Some(mset) = pending.get_mut(from) {
+            mset.msgs.remove(&sequence);
+            if mset.msgs.is_empty() {
+                pending.remove(from);
+            }
+        }
+    }
+
+    /// Returns the number of pending messages in the pool
+    pub async fn len(&self) -> usize {
+        pending_count(&*self.pending.read().await) as usize
+    }
+
+    /// Returns true if there are no pending messages in the pool
+    pub async fn is_empty(&self) -> bool {
+        self.pending.read().await.is_empty()
+    }
+
+    /// Returns the current head of the message pool
+    pub async fn cur_tipset(&self) -> Option<Tipset> {
+        self.cur_tipset.read().await.clone()
+    }
+
+    /// Sets a new head for the pool and evicts all pending messages whose sequence has
+    /// already been used in the state of the new head
+    pub async fn set_head(&self, ts: Tipset) -> Result<(), Error> {
+        let mut cur_ts = self.cur_tipset.write().await;
+        let mut pending = self.pending.write().await;
+        *cur_ts = Some(ts.clone());
+        self.evict_stale(&mut pending, &ts)
+    }
+
+    /// Updates the pool for a change of the chain head. Messages included in reverted
+    /// tipsets are added back to the pool without validation, messages included in applied
+    /// tipsets are removed and the last applied tipset becomes the new head. If no tipsets
+    /// are applied, the parent of the last reverted tipset becomes the new head.
+    pub async fn head_change(&self, revert: &[Tipset], apply: &[Tipset]) -> Result<(), Error> {
+        let new_head = match (apply.last(), revert.last()) {
+            (Some(ts), _) => Some(ts.clone()),
+            (None, Some(ts)) => Some(self.api.load_tipset(ts.parents())?),
+            (None, None) => None,
+        };
+
+        let mut rmsgs: HashMap<Address, HashMap<u64, SignedMessage>> = HashMap::new();
+        for ts in revert {
+            for header in ts.blocks() {
+                let (bls_msgs, secp_msgs) = self.api.messages_for_block(header)?;
+                for msg in bls_msgs {
+                    let sig = self.bls_sig_cache.write().await.get(&msg.cid()?).cloned();
+                    match sig {
+                        Some(sig) => {
+                            let smsg = SignedMessage::new_from_parts(msg, sig)?;
+                            rmsgs
+                                .entry(*smsg.from())
+                                .or_default()
+                                .insert(smsg.sequence(), smsg);
+                        }
+                        None => warn!(
+                            "Could not find signature for reverted bls message {}",
+                            msg.cid()?
+                        ),
+                    }
+                }
+                for smsg in secp_msgs {
+                    rmsgs
+                        .entry(*smsg.from())
+                        .or_default()
+                        .insert(smsg.sequence(), smsg);
+                }
+            }
+        }
+
+        let mut cur_ts = self.cur_tipset.write().await;
+        let mut pending = self.pending.write().await;
+        // The head is moved even if updating the pending messages fails, so that later
+        // head changes are applied on top of the current chain
+        if let Some(ts) = &new_head {
+            *cur_ts = Some(ts.clone());
+        }
+
+        for ts in apply {
+            for msg in self.messages_for_tipset(ts)? {
+                if let Some(mset) = pending.get_mut(msg.from()) {
+                    mset.msgs.remove(&msg.sequence());
+                }
+                if let Some(msgs) = rmsgs.get_mut(msg.from()) {
+                    msgs.remove(&msg.sequence());
+                }
+            }
+        }
+
+        for (addr, msgs) in rmsgs {
+            pending.entry(addr).or_default().msgs.extend(msgs);
+        }
+
+        match &new_head {
+            Some(ts) => self.evict_stale(&mut pending, ts)?,
+            None => pending.retain(|_, mset| !mset.msgs.is_empty()),
+        }
+        Ok(())
+    }
+
+    /// Updates the pool with head changes published by the chain store until the
+    /// subscription is closed
+    pub async fn process_head_changes(&self, mut subscriber: Subscriber<HeadChange>) {
+        while let Some(change) = subscriber.next().await {
+            let res = match change {
+                HeadChange::Current(ts) => self.set_head(ts.as_ref().clone()).await,
+                HeadChange::Apply(ts) => self.head_change(&[], &[ts.as_ref().clone()]).await,
+                HeadChange::Revert(ts) => self.head_change(&[ts.as_ref().clone()], &[]).await,
+            };
+            if let Err(e) = res {
+                error!("Failed to update message pool on head change: {}", e);
+            }
+        }
+    }
+
+    /// Removes all pending messages whose sequence has already been used in the state of
+    /// the given tipset. Pending messages of senders whose actor cannot be loaded in that
+    /// state are dropped, as they could not be included on top of it.
+    fn evict_stale(
+        &self,
+        pending: &mut HashMap<Address, MsgSet>,
+        ts: &Tipset,
+    ) -> Result<(), Error> {
+        let ts_msgs = self.messages_for_tipset(ts)?;
+        pending.retain(|addr, mset| match self.api.state_get_actor(addr, ts) {
+            Ok(actor) => {
+                let state_nonce = next_state_nonce(addr, actor.sequence, &ts_msgs);
+                mset.msgs = mset.msgs.split_off(&state_nonce);
+                !mset.msgs.is_empty()
+            }
+            Err(e) => {
+                warn!("Dropping pending messages of {}: {}", addr, e);
+                false
+            }
+        });
+        Ok(())
+    }
 
-#[allow(dead_code)]
-/// Creates a pool using a default config
-fn new_default_message_pool_config() -> MessagePoolConfig {
-    MessagePoolConfig {
-        max_pool_size: 10000,
-        max_nonce_gap: 100,
+    /// Returns the unique messages included in all blocks of a tipset
+    fn messages_for_tipset(&self, ts: &Tipset) -> Result<Vec<UnsignedMessage>, Error> {
+        let mut seen = HashSet::new();
+        let mut msgs = Vec::new();
+        for header in ts.blocks() {
+            let (bls_msgs, secp_msgs) = self.api.messages_for_block(header)?;
+            let secp_msgs = secp_msgs.into_iter().map(|m| m.message().clone());
+            for msg in bls_msgs.into_iter().chain(secp_msgs) {
+                if seen.insert(msg.cid()?) {
+                    msgs.push(msg);
+                }
+            }
+        }
+        Ok(msgs)
     }
 }
 
-#[allow(dead_code)]
-/// Constructs a new, empty Pool.
-fn new_pool() -> &'static str {
-    "Pool{ cfg: cfg, validator: validator}"
+/// Returns the sequence of an address after applying the messages of the head tipset,
+/// which are not yet included in the tipset's parent state
+fn next_state_nonce(addr: &Address, actor_nonce: u64, ts_msgs: &[UnsignedMessage]) -> u64 {
+    let mut next_nonce = actor_nonce;
+    for msg in ts_msgs {
+        if msg.from() == addr && msg.sequence() == next_nonce {
+            next_nonce += 1;
+        }
+    }
+    next_nonce
 }
 
-#[allow(dead_code)]
-struct AddressNonce {
-    //addr  address
-    nonce: u64,
+/// Returns the total amount of pending messages
+fn pending_count(pending: &HashMap<Address, MsgSet>) -> u64 {
+    pending.values().map(|mset| mset.msgs.len() as u64).sum()
 }
-#[allow(dead_code)]
-fn new_address_nonce(_msg: SignedMessage) -> &'static str {
-    "return addressNonce"
+
+/// Checks the size and signature of a message before it is validated against state
+fn check_message(msg: &SignedMessage) -> Result<(), Error> {
+    let bz = msg.message().marshal_cbor()?;
+    if bz.len() > MAX_MESSAGE_SIZE {
+        return Err(Error::MessageTooBig);
+    }
+    msg.signature()
+        .verify(&bz, msg.from())
+        .map_err(Error::InvalidSignature)
 }
 
-#[allow(dead_code)]
-/// Adds a message to the pool, tagged with the block height at which it was received.
-/// Does nothing if the message is already in the pool.
-fn add(_msg: SignedMessage, _height: u64) -> &'static str {
-    "add message to pool"
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::sync::{channel, Receiver};
+    use blocks::{BlockHeader, TipsetKeys};
+    use cid::multihash::Identity;
+    use crypto::{Signature, SignatureType, Signer};
+    use key_management::{generate, new_address, sign, to_public};
+    use std::error::Error as StdError;
+    use vm::ActorState;
+
+    struct TestSigner {
+        private_key: Vec<u8>,
+    }
+
+    impl Signer for TestSigner {
+        fn sign_bytes(&self, data: Vec<u8>, _: &Address) -> Result<Signature, Box<dyn StdError>> {
+            Ok(sign(SignatureType::Secp256k1, &self.private_key, &data)?)
+        }
+    }
+
+    #[derive(Default)]
+    struct TestApi {
+        actors: HashMap<Address, ActorState>,
+        /// Actors which don't exist in the state of a tipset
+        missing_actors: HashSet<(TipsetKeys, Address)>,
+        block_msgs: HashMap<Cid, Vec<SignedMessage>>,
+        tipsets: HashMap<TipsetKeys, Tipset>,
+    }
+
+    impl Provider for TestApi {
+        fn get_heaviest_tipset(&self) -> Result<Option<Tipset>, Error> {
+            Ok(Some(mock_tipset(1)))
+        }
+
+        fn load_tipset(&self, tsk: &TipsetKeys) -> Result<Tipset, Error> {
+            self.tipsets
+                .get(tsk)
+                .cloned()
+                .ok_or_else(|| Error::Other("tipset not found".to_owned()))
+        }
+
+        fn state_get_actor(&self, addr: &Address, ts: &Tipset) -> Result<ActorState, Error> {
+            if self.missing_actors.contains(&(ts.key().clone(), *addr)) {
+                return Err(Error::Other("actor not found".to_owned()));
+            }
+            self.actors
+                .get(addr)
+                .cloned()
+                .ok_or_else(|| Error::Other("actor not found".to_owned()))
+        }
+
+        fn put_message<M: Cbor>(&self, msg: &M) -> Result<Cid, Error> {
+            Ok(msg.cid()?)
+        }
+
+        fn messages_for_block(
+            &self,
+            h: &BlockHeader,
+        ) -> Result<(Vec<UnsignedMessage>, Vec<SignedMessage>), Error> {
+            let msgs = self.block_msgs.get(h.cid()).cloned().unwrap_or_default();
+            Ok((Vec::new(), msgs))
+        }
+    }
+
+    fn mock_tipset(epoch: u64) -> Tipset {
+        let header = BlockHeader::builder()
+            .epoch(epoch)
+            .miner_address(Address::new_id(0))
+            .build_and_validate()
+            .unwrap();
+        Tipset::new(vec![header]).unwrap()
+    }
+
+    fn new_sender() -> (Address, TestSigner) {
+        let private_key = generate(SignatureType::Secp256k1).unwrap();
+        let public_key = to_public(SignatureType::Secp256k1, &private_key).unwrap();
+        let addr = new_address(SignatureType::Secp256k1, &public_key).unwrap();
+        (addr, TestSigner { private_key })
+    }
+
+    fn new_actor(balance: u64) -> ActorState {
+        let code = Cid::new_from_cbor(&[], Identity);
+        ActorState::new(code.clone(), code, TokenAmount::from(balance), 0)
+    }
+
+    fn signed_msg(
+        from: Address,
+        signer: &TestSigner,
+        sequence: u64,
+        gas_price: u64,
+    ) -> SignedMessage {
+        let umsg = UnsignedMessage::builder()
+            .to(Address::new_id(1))
+            .from(from)
+            .sequence(sequence)
+            .value(TokenAmount::from(1u8))
+            .gas_price(TokenAmount::from(gas_price))
+            .gas_limit(1000)
+            .build()
+            .unwrap();
+        SignedMessage::new(umsg, signer).unwrap()
+    }
+
+    fn new_mpool(
+        api: TestApi,
+        config: MessagePoolConfig,
+    ) -> (MessagePool<TestApi>, Receiver<NetworkMessage>) {
+        let (network_sender, network_receiver) = channel(20);
+        let mpool = MessagePool::new(api, "test".to_owned(), network_sender, config).unwrap();
+        (mpool, network_receiver)
+    }
+
+    fn setup(
+        balance: u64,
+        config: MessagePoolConfig,
+    ) -> (
+        MessagePool<TestApi>,
+        Receiver<NetworkMessage>,
+        Address,
+        TestSigner,
+    ) {
+        let (addr, signer) = new_sender();
+        let mut api = TestApi::default();
+        api.actors.insert(addr, new_actor(balance));
+        let (mpool, network_receiver) = new_mpool(api, config);
+        (mpool, network_receiver, addr, signer)
+    }
+
+    #[test]
+    fn push_and_get_nonce() {
+        let (mpool, mut network_rx, sender, signer) =
+            setup(1_000_000, MessagePoolConfig::default());
+        async_std::task::block_on(async {
+            assert_eq!(mpool.get_nonce(&sender).await.unwrap(), 0);
+            for i in 0..3 {
+                mpool.push(signed_msg(sender, &signer, i, 1)).await.unwrap();
+            }
+
+            // Pushed messages are published on the messages topic
+            match network_rx.next().await {
+                Some(NetworkMessage::PubsubMessage { topic, message }) => {
+                    assert_eq!(topic, Topic::new("/fil/msgs/test".to_owned()));
+                    assert_eq!(
+                        message,
+                        signed_msg(sender, &signer, 0, 1).marshal_cbor().unwrap()
+                    );
+                }
+                other => panic!("unexpected network message: {:?}", other),
+            }
+            assert_eq!(mpool.get_nonce(&sender).await.unwrap(), 3);
+            assert_eq!(mpool.len().await, 3);
+
+            let pending = mpool.pending_for(&sender).await;
+            let nonces: Vec<u64> = pending.iter().map(|m| m.sequence()).collect();
+            assert_eq!(nonces, vec![0, 1, 2]);
+
+            // Pushing the exact same message again is rejected
+            assert_eq!(
+                mpool.add(signed_msg(sender, &signer, 0, 1)).await,
+                Err(Error::DuplicateMessage)
+            );
+        });
+    }
+
+    #[test]
+    fn nonce_gap_and_signature_checks() {
+        let config = MessagePoolConfig {
+            max_nonce_gap: 5,
+            ..Default::default()
+        };
+        let (mpool, _network_rx, sender, signer) = setup(1_000_000, config);
+        async_std::task::block_on(async {
+            assert_eq!(
+                mpool.add(signed_msg(sender, &signer, 6, 1)).await,
+                Err(Error::NonceGap)
+            );
+            mpool.add(signed_msg(sender, &signer, 5, 1)).await.unwrap();
+            // Gap in pending nonces means next nonce is still the state nonce
+            assert_eq!(mpool.get_nonce(&sender).await.unwrap(), 0);
+
+            // Message signed by a different key is rejected
+            let (_, other_signer) = new_sender();
+            assert!(matches!(
+                mpool.add(signed_msg(sender, &other_signer, 0, 1)).await,
+                Err(Error::InvalidSignature(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn replace_by_gas_price() {
+        let (mpool, _network_rx, sender, signer) = setup(1_000_000, MessagePoolConfig::default());
+        async_std::task::block_on(async {
+            mpool
+                .add(signed_msg(sender, &signer, 0, 100))
+                .await
+                .unwrap();
+            assert_eq!(
+                mpool.add(signed_msg(sender, &signer, 0, 110)).await,
+                Err(Error::GasPriceTooLow)
+            );
+            let replacement = signed_msg(sender, &signer, 0, 126);
+            mpool.add(replacement.clone()).await.unwrap();
+            assert_eq!(mpool.pending().await, vec![replacement]);
+        });
+    }
+
+    #[test]
+    fn funds_and_pool_size_limits() {
+        let config = MessagePoolConfig {
+            max_pool_size: 2,
+            ..Default::default()
+        };
+        // Each message requires 1 * 1000 + 1 attoFIL
+        let (mpool, _network_rx, sender, signer) = setup(2002, config);
+        async_std::task::block_on(async {
+            mpool.add(signed_msg(sender, &signer, 0, 1)).await.unwrap();
+            mpool.add(signed_msg(sender, &signer, 1, 1)).await.unwrap();
+            assert_eq!(
+                mpool.add(signed_msg(sender, &signer, 2, 1)).await,
+                Err(Error::MPoolFull)
+            );
+            // Replacing a pending message does not count against pool size,
+            // but must still be affordable
+            assert_eq!(
+                mpool.add(signed_msg(sender, &signer, 1, 2)).await,
+                Err(Error::NotEnoughFunds)
+            );
+        });
+    }
+
+    #[test]
+    fn head_change_evicts_included() {
+        let (addr, signer) = new_sender();
+        let msgs: Vec<SignedMessage> = (0..3).map(|i| signed_msg(addr, &signer, i, 1)).collect();
+
+        let new_head = mock_tipset(2);
+        let mut api = TestApi::default();
+        api.actors.insert(addr, new_actor(1_000_000));
+        api.block_msgs
+            .insert(new_head.cids()[0].clone(), msgs[..2].to_vec());
+
+        let (mpool, _network_rx) = new_mpool(api, MessagePoolConfig::default());
+        async_std::task::block_on(async {
+            for m in msgs.iter() {
+                mpool.add(m.clone()).await.unwrap();
+            }
+            mpool.set_head(new_head).await.unwrap();
+            assert_eq!(mpool.pending().await, vec![msgs[2].clone()]);
+            assert_eq!(mpool.get_nonce(&addr).await.unwrap(), 3);
+        });
+    }
+
+    #[test]
+    fn head_change_reverts_and_applies() {
+        let (addr, signer) = new_sender();
+        let msgs: Vec<SignedMessage> = (0..3).map(|i| signed_msg(addr, &signer, i, 1)).collect();
+
+        let parent = mock_tipset(1);
+        let reverted = mock_tipset(2);
+        let mut api = TestApi::default();
+        api.actors.insert(addr, new_actor(1_000_000));
+        api.block_msgs
+            .insert(reverted.cids()[0].clone(), msgs[..2].to_vec());
+        api.tipsets
+            .insert(reverted.parents().clone(), parent.clone());
+
+        let (mpool, _network_rx) = new_mpool(api, MessagePoolConfig::default());
+        async_std::task::block_on(async {
+            mpool.add(msgs[2].clone()).await.unwrap();
+
+            // Messages of the reverted tipset are pending again and the head moves
+            // back to the parent of the reverted tipset
+            mpool.head_change(&[reverted.clone()], &[]).await.unwrap();
+            assert_eq!(mpool.pending_for(&addr).await, msgs);
+            assert_eq!(mpool.cur_tipset().await, Some(parent));
+
+            // Applying the tipset again removes its messages and moves the head
+            mpool.head_change(&[], &[reverted.clone()]).await.unwrap();
+            assert_eq!(mpool.pending().await, vec![msgs[2].clone()]);
+            assert_eq!(mpool.cur_tipset().await, Some(reverted));
+        });
+    }
+
+    #[test]
+    fn head_change_drops_senders_without_actor() {
+        let (addr, signer) = new_sender();
+        let (other, other_signer) = new_sender();
+
+        let new_head = mock_tipset(2);
+        let next_head = mock_tipset(3);
+        let mut api = TestApi::default();
+        api.actors.insert(addr, new_actor(1_000_000));
+        api.actors.insert(other, new_actor(1_000_000));
+        api.missing_actors.insert((new_head.key().clone(), other));
+
+        let (mpool, _network_rx) = new_mpool(api, MessagePoolConfig::default());
+        async_std::task::block_on(async {
+            mpool.add(signed_msg(addr, &signer, 0, 1)).await.unwrap();
+            mpool
+                .add(signed_msg(other, &other_signer, 0, 1))
+                .await
+                .unwrap();
+
+            // Pending messages of the sender without an actor are dropped and the
+            // head is still updated
+            mpool.head_change(&[], &[new_head.clone()]).await.unwrap();
+            assert_eq!(mpool.pending().await, vec![signed_msg(addr, &signer, 0, 1)]);
+            assert_eq!(mpool.cur_tipset().await, Some(new_head));
+
+            // Later head changes are applied on top of the new head
+            mpool.head_change(&[], &[next_head.clone()]).await.unwrap();
+            assert_eq!(mpool.cur_tipset().await, Some(next_head));
+        });
+    }
 }
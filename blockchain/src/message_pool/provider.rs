@@ -0,0 +1,86 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::Error;
+use address::Address;
+use blocks::{BlockHeader, Tipset, TipsetKeys};
+use cid::{multihash::Blake2b256, Cid};
+use encoding::Cbor;
+use ipld_blockstore::BlockStore;
+use message::{SignedMessage, UnsignedMessage};
+use state_manager::{Error as StErr, StateManager};
+use std::sync::Arc;
+use vm::ActorState;
+
+/// Provider is the interface the message pool uses to query chain and state data.
+/// Abstracted so the pool can be tested without a full chain.
+pub trait Provider {
+    /// Returns the heaviest tipset from the store, if one has been set
+    fn get_heaviest_tipset(&self) -> Result<Option<Tipset>, Error>;
+    /// Loads the tipset with the given keys from the store
+    fn load_tipset(&self, tsk: &TipsetKeys) -> Result<Tipset, Error>;
+    /// Returns the actor state of an address in the parent state of the given tipset
+    fn state_get_actor(&self, addr: &Address, ts: &Tipset) -> Result<ActorState, Error>;
+    /// Persists a message to the blockstore and returns its Cid
+    fn put_message<M: Cbor>(&self, msg: &M) -> Result<Cid, Error>;
+    /// Returns the bls and secp messages included in a block
+    fn messages_for_block(
+        &self,
+        h: &BlockHeader,
+    ) -> Result<(Vec<UnsignedMessage>, Vec<SignedMessage>), Error>;
+}
+
+/// Message pool provider backed by the node's StateManager
+pub struct MpoolProvider<DB> {
+    sm: Arc<StateManager<DB>>,
+}
+
+impl<DB> MpoolProvider<DB>
+where
+    DB: BlockStore,
+{
+    pub fn new(sm: Arc<StateManager<DB>>) -> Self {
+        Self { sm }
+    }
+}
+
+impl<DB> Provider for MpoolProvider<DB>
+where
+    DB: BlockStore,
+{
+    fn get_heaviest_tipset(&self) -> Result<Option<Tipset>, Error> {
+        Ok(chain::get_heaviest_tipset(
+            self.sm.get_block_store().as_ref(),
+        )?)
+    }
+
+    fn load_tipset(&self, tsk: &TipsetKeys) -> Result<Tipset, Error> {
+        Ok(chain::tipset_from_keys(
+            self.sm.get_block_store().as_ref(),
+            tsk,
+        )?)
+    }
+
+    fn state_get_actor(&self, addr: &Address, ts: &Tipset) -> Result<ActorState, Error> {
+        self.sm
+            .get_actor(addr, ts.parent_state())?
+            .ok_or_else(|| Error::State(StErr::ActorNotFound(addr.to_string())))
+    }
+
+    fn put_message<M: Cbor>(&self, msg: &M) -> Result<Cid, Error> {
+        self.sm
+            .get_block_store()
+            .put(msg, Blake2b256)
+            .map_err(|e| Error::Other(e.to_string()))
+    }
+
+    fn messages_for_block(
+        &self,
+        h: &BlockHeader,
+    ) -> Result<(Vec<UnsignedMessage>, Vec<SignedMessage>), Error> {
+        Ok(chain::block_messages(
+            self.sm.get_block_store().as_ref(),
+            h,
+        )?)
+    }
+}
@@ -2,33 +2,80 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 mod errors;
+mod tipset_state_cache;
 pub mod utils;
 pub use self::errors::*;
-use actor::{init, miner, power, ActorState, INIT_ACTOR_ADDR, STORAGE_POWER_ACTOR_ADDR};
+use self::tipset_state_cache::TipsetStateCache;
+use actor::{
+    init, market, miner, power, ActorState, DealID, OptionalEpoch, INIT_ACTOR_ADDR,
+    STORAGE_MARKET_ACTOR_ADDR, STORAGE_POWER_ACTOR_ADDR,
+};
 use address::{Address, BLSPublicKey, Payload, BLS_PUB_LEN};
 use async_log::span;
-use async_std::sync::RwLock;
 use blockstore::BlockStore;
 use blockstore::BufferedBlockStore;
-use chain::{block_messages, ChainStore};
+use chain::{block_messages, ChainStore, HeadChange};
 use cid::Cid;
-use encoding::de::DeserializeOwned;
+use clock::ChainEpoch;
+use encoding::{de::DeserializeOwned, Cbor};
+use flo_stream::Subscriber;
 use forest_blocks::{Block, BlockHeader, FullTipset, Tipset, TipsetKeys};
-use interpreter::{resolve_to_key_addr, ChainRand, DefaultSyscalls, VM};
+use futures::StreamExt;
+use interpreter::{
+    resolve_to_key_addr, ApplyCallback, ApplyRet, ChainRand, DefaultSyscalls, ExecutionTrace, VM,
+};
 use ipld_amt::Amt;
 use log::trace;
+use message::{Message, MessageReceipt, SignedMessage, UnsignedMessage};
 use num_bigint::BigUint;
 use state_tree::StateTree;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error as StdError;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Gas limit of messages executed with `StateManager::call`
+const CALL_GAS_LIMIT: u64 = 1 << 30;
+
+/// Number of tipsets with state and receipt roots kept in memory
+const TIPSET_STATE_CACHE_SIZE: usize = 1 << 12;
+
+/// Error returned by the replay callback to stop executing a tipset once the message is found
+const REPLAY_HALT: &str = "replay halted";
 
 /// Intermediary for retrieving state objects and updating actor states
 pub type CidPair = (Cid, Cid);
 
+/// Storage deal proposal along with its current state in the market actor
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarketDeal {
+    pub proposal: market::DealProposal,
+    pub state: market::DealState,
+}
+
+/// Result of executing a message outside of a block
+#[derive(Clone, PartialEq)]
+pub struct InvocResult {
+    pub msg: UnsignedMessage,
+    pub msg_rct: MessageReceipt,
+    pub error: Option<String>,
+    pub exec_trace: Option<ExecutionTrace>,
+}
+
+impl InvocResult {
+    fn new(msg: UnsignedMessage, ret: &ApplyRet) -> Self {
+        Self {
+            msg,
+            msg_rct: ret.msg_receipt.clone(),
+            error: ret.act_error.as_ref().map(|e| e.to_string()),
+            exec_trace: ret.exec_trace.clone(),
+        }
+    }
+}
+
 pub struct StateManager<DB> {
     bs: Arc<DB>,
-    cache: RwLock<HashMap<TipsetKeys, CidPair>>,
+    cache: TipsetStateCache,
 }
 
 impl<DB> StateManager<DB>
@@ -39,11 +86,11 @@ where
     pub fn new(bs: Arc<DB>) -> Self {
         Self {
             bs,
-            cache: RwLock::new(HashMap::new()),
+            cache: TipsetStateCache::new(TIPSET_STATE_CACHE_SIZE),
         }
     }
     /// Loads actor state from IPLD Store
-    fn load_actor_state<D>(&self, addr: &Address, state_cid: &Cid) -> Result<D, Error>
+    pub fn load_actor_state<D>(&self, addr: &Address, state_cid: &Cid) -> Result<D, Error>
     where
         D: DeserializeOwned,
     {
@@ -57,7 +104,8 @@ where
             .ok_or_else(|| Error::ActorStateNotFound(actor.state.to_string()))?;
         Ok(act)
     }
-    fn get_actor(&self, addr: &Address, state_cid: &Cid) -> Result<Option<ActorState>, Error> {
+    /// Returns the actor state of an address from the state tree at the given state root
+    pub fn get_actor(&self, addr: &Address, state_cid: &Cid) -> Result<Option<ActorState>, Error> {
         let state = StateTree::new_from_root(self.bs.as_ref(), state_cid).map_err(Error::State)?;
         state.get_actor(addr).map_err(Error::State)
     }
@@ -105,12 +153,88 @@ where
         }
     }
 
+    /// Returns the total raw byte and quality adjusted power of the network
+    pub fn get_network_power(&self, state_cid: &Cid) -> Result<(BigUint, BigUint), Error> {
+        let ps: power::State = self.load_actor_state(&*STORAGE_POWER_ACTOR_ADDR, state_cid)?;
+        Ok((ps.total_raw_byte_power, ps.total_quality_adj_power))
+    }
+    /// Returns the ID address of an address from the init actor
+    pub fn lookup_id(&self, addr: &Address, state_cid: &Cid) -> Result<Address, Error> {
+        let state = StateTree::new_from_root(self.bs.as_ref(), state_cid).map_err(Error::State)?;
+        state.lookup_id(addr).map_err(Error::State)
+    }
+    /// Returns the public key address of an account actor address
+    pub fn resolve_to_key_addr(&self, addr: &Address, state_cid: &Cid) -> Result<Address, Error> {
+        let state = StateTree::new_from_root(self.bs.as_ref(), state_cid).map_err(Error::State)?;
+        resolve_to_key_addr(&state, self.bs.as_ref(), addr)
+            .map_err(|e| Error::Other(format!("Failed to resolve key address; error: {}", e)))
+    }
+    /// Returns the static information of a miner
+    pub fn get_miner_info(
+        &self,
+        state_cid: &Cid,
+        addr: &Address,
+    ) -> Result<miner::MinerInfo, Error> {
+        let ms: miner::State = self.load_actor_state(addr, state_cid)?;
+        Ok(ms.info)
+    }
+    /// Returns all sectors committed by a miner
+    pub fn get_miner_sectors(
+        &self,
+        state_cid: &Cid,
+        addr: &Address,
+    ) -> Result<Vec<miner::SectorOnChainInfo>, Error> {
+        let ms: miner::State = self.load_actor_state(addr, state_cid)?;
+        let mut sectors = Vec::new();
+        ms.for_each_sector(self.bs.as_ref(), |sector| {
+            sectors.push(sector.clone());
+            Ok(())
+        })
+        .map_err(Error::State)?;
+        Ok(sectors)
+    }
+    /// Returns all deals tracked by the storage market actor, keyed by deal ID
+    pub fn get_market_deals(&self, state_cid: &Cid) -> Result<HashMap<DealID, MarketDeal>, Error> {
+        let ms: market::State = self.load_actor_state(&*STORAGE_MARKET_ACTOR_ADDR, state_cid)?;
+        let proposals = Amt::<market::DealProposal, _>::load(&ms.proposals, self.bs.as_ref())
+            .map_err(|e| Error::State(e.to_string()))?;
+        let states = Amt::<market::DealState, _>::load(&ms.states, self.bs.as_ref())
+            .map_err(|e| Error::State(e.to_string()))?;
+
+        let mut deals = HashMap::new();
+        proposals
+            .for_each(|deal_id, proposal| {
+                // Deals which have not been activated yet have no state
+                let state =
+                    states
+                        .get(deal_id)
+                        .map_err(|e| e.to_string())?
+                        .unwrap_or(market::DealState {
+                            sector_start_epoch: OptionalEpoch(None),
+                            last_updated_epoch: OptionalEpoch(None),
+                            slash_epoch: OptionalEpoch(None),
+                        });
+                deals.insert(
+                    deal_id,
+                    MarketDeal {
+                        proposal: proposal.clone(),
+                        state,
+                    },
+                );
+                Ok(())
+            })
+            .map_err(Error::State)?;
+        Ok(deals)
+    }
+
     /// Performs the state transition for the tipset and applies all unique messages in all blocks.
     /// This function returns the state root and receipt root of the transition.
+    /// Execution traces of the messages are recorded for the callback, if one is given.
     pub fn apply_blocks(
         &self,
         ts: &FullTipset,
         rand: &ChainRand,
+        callback: Option<ApplyCallback>,
     ) -> Result<(Cid, Cid), Box<dyn StdError>> {
         let mut buf_store = BufferedBlockStore::new(self.bs.as_ref());
         // TODO possibly switch out syscalls to be saved at state manager level
@@ -121,9 +245,12 @@ where
             DefaultSyscalls::new(&buf_store),
             rand,
         )?;
+        if callback.is_some() {
+            vm.enable_trace();
+        }
 
         // Apply tipset messages
-        let receipts = vm.apply_tip_set_messages(ts)?;
+        let receipts = vm.apply_tip_set_messages(ts, callback)?;
 
         // Construct receipt root from receipts
         let rect_root = Amt::new_from_slice(self.bs.as_ref(), &receipts)?;
@@ -136,48 +263,40 @@ where
         Ok((state_root, rect_root))
     }
 
+    /// Returns the state and receipt roots of the tipset, executing its messages if the
+    /// roots are not indexed yet
     pub async fn tipset_state(&self, tipset: &Tipset) -> Result<(Cid, Cid), Box<dyn StdError>> {
         span!("tipset_state", {
             trace!("tipset {:?}", tipset.cids());
-            // if exists in cache return
-            if let Some(cid_pair) = self.cache.read().await.get(&tipset.key()) {
-                return Ok(cid_pair.clone());
-            }
-
-            if tipset.epoch() == 0 {
-                // NB: This is here because the process that executes blocks requires that the
-                // block miner reference a valid miner in the state tree. Unless we create some
-                // magical genesis miner, this won't work properly, so we short circuit here
-                // This avoids the question of 'who gets paid the genesis block reward'
-                let message_receipts = tipset
-                    .blocks()
-                    .first()
-                    .ok_or_else(|| Error::Other("Could not get message receipts".to_string()))?;
-                let cid_pair = (
-                    tipset.parent_state().clone(),
-                    message_receipts.message_receipts().clone(),
-                );
-                self.cache
-                    .write()
-                    .await
-                    .insert(tipset.key().clone(), cid_pair.clone());
-                return Ok(cid_pair);
-            }
-
-            let block_headers = tipset.blocks();
-            // generic constants are not implemented yet this is a lowcost method for now
-            let cid_pair = self.compute_tipset_state(&block_headers)?;
             self.cache
-                .write()
+                .get_or_compute(self.bs.as_ref(), tipset.key(), || {
+                    if tipset.epoch() == 0 {
+                        // NB: This is here because the process that executes blocks requires
+                        // that the block miner reference a valid miner in the state tree. Unless
+                        // we create some magical genesis miner, this won't work properly, so we
+                        // short circuit here. This avoids the question of 'who gets paid the
+                        // genesis block reward'
+                        let message_receipts = tipset.blocks().first().ok_or_else(|| {
+                            Error::Other("Could not get message receipts".to_string())
+                        })?;
+                        return Ok((
+                            tipset.parent_state().clone(),
+                            message_receipts.message_receipts().clone(),
+                        ));
+                    }
+
+                    // generic constants are not implemented yet this is a lowcost method for
+                    // now
+                    self.compute_tipset_state(&tipset.blocks(), None)
+                })
                 .await
-                .insert(tipset.key().clone(), cid_pair.clone());
-            Ok(cid_pair)
         })
     }
 
     pub fn compute_tipset_state<'a>(
         &'a self,
         blocks_headers: &[BlockHeader],
+        callback: Option<ApplyCallback>,
     ) -> Result<(Cid, Cid), Box<dyn StdError>> {
         span!("compute_tipset_state", {
             let check_for_duplicates = |s: &BlockHeader| {
@@ -213,10 +332,257 @@ where
                 .collect::<Result<Vec<Block>, _>>()?;
             // convert tipset to fulltipset
             let full_tipset = FullTipset::new(blocks)?;
-            self.apply_blocks(&full_tipset, &chain_rand)
+            self.apply_blocks(&full_tipset, &chain_rand, callback)
         })
     }
 
+    /// Executes the message on top of the parent state of the tipset, or the heaviest tipset
+    /// if none is given, without persisting any state changes. The sequence of the message is
+    /// set to the sender's sequence and gas is not charged.
+    pub fn call(
+        &self,
+        msg: &mut UnsignedMessage,
+        tipset: Option<Tipset>,
+    ) -> Result<InvocResult, Error> {
+        let ts = match tipset {
+            Some(ts) => ts,
+            None => chain::get_heaviest_tipset(self.bs.as_ref())
+                .map_err(|e| Error::Other(e.to_string()))?
+                .ok_or_else(|| Error::Other("No heaviest tipset".to_owned()))?,
+        };
+        let actor = self
+            .get_actor(msg.from(), ts.parent_state())?
+            .ok_or_else(|| Error::ActorNotFound(msg.from().to_string()))?;
+        msg.set_sequence(actor.sequence);
+        msg.set_gas_limit(CALL_GAS_LIMIT);
+        msg.set_gas_price(BigUint::default());
+
+        // State changes are only written to the buffer, which is never flushed
+        let buf_store = BufferedBlockStore::new(self.bs.as_ref());
+        let chain_rand = ChainRand::new(ts.key().clone());
+        let mut vm = VM::new(
+            ts.parent_state(),
+            &buf_store,
+            ts.epoch(),
+            DefaultSyscalls::new(&buf_store),
+            &chain_rand,
+        )
+        .map_err(Error::VM)?;
+        vm.enable_trace();
+        let ret = vm.apply_message(msg).map_err(Error::VM)?;
+        Ok(InvocResult::new(msg.clone(), &ret))
+    }
+
+    /// Applies the messages on top of the state computed for the tipset at the given epoch,
+    /// without rewards or cron. Returns the resulting state root, which is persisted, along
+    /// with the result and execution trace of each message.
+    pub async fn compute_state(
+        &self,
+        epoch: ChainEpoch,
+        msgs: &[UnsignedMessage],
+        ts: &Tipset,
+    ) -> Result<(Cid, Vec<InvocResult>), Error> {
+        let (state_root, _) = self
+            .tipset_state(ts)
+            .await
+            .map_err(|e| Error::Other(format!("Failed to compute tipset state: {}", e)))?;
+
+        let mut buf_store = BufferedBlockStore::new(self.bs.as_ref());
+        let chain_rand = ChainRand::new(ts.key().clone());
+        let mut vm = VM::new(
+            &state_root,
+            &buf_store,
+            epoch,
+            DefaultSyscalls::new(&buf_store),
+            &chain_rand,
+        )
+        .map_err(Error::VM)?;
+        vm.enable_trace();
+        let mut results = Vec::with_capacity(msgs.len());
+        for msg in msgs {
+            let ret = vm.apply_message(msg).map_err(Error::VM)?;
+            results.push(InvocResult::new(msg.clone(), &ret));
+        }
+
+        let root = vm.flush().map_err(Error::VM)?;
+        buf_store
+            .flush(&root)
+            .map_err(|e| Error::Other(e.to_string()))?;
+        Ok((root, results))
+    }
+
+    /// Re-executes the messages of the tipset which included the message and returns the
+    /// result of applying the message. Execution stops once the message has been applied.
+    pub fn replay(&self, ts: &Tipset, mcid: &Cid) -> Result<InvocResult, Error> {
+        let mut result = None;
+        let mut callback =
+            |cid: &Cid, msg: &UnsignedMessage, ret: &ApplyRet| -> Result<(), String> {
+                if cid == mcid {
+                    result = Some(InvocResult::new(msg.clone(), ret));
+                    return Err(REPLAY_HALT.to_owned());
+                }
+                Ok(())
+            };
+        let res = self.compute_tipset_state(ts.blocks(), Some(&mut callback as ApplyCallback));
+        if let Some(result) = result {
+            return Ok(result);
+        }
+        res.map_err(|e| Error::Other(format!("Failed to compute tipset state: {}", e)))?;
+        Err(Error::Other(format!(
+            "Message {} not found in tipset",
+            mcid
+        )))
+    }
+
+    /// Searches back from the heaviest tipset for the execution of the message. Returns the
+    /// tipset in which the message was executed, whose parent included it, and its receipt.
+    pub fn search_for_message(
+        &self,
+        mcid: &Cid,
+    ) -> Result<Option<(Tipset, MessageReceipt)>, Error> {
+        let (from, sequence) = self.load_message_sender(mcid)?;
+        let mut ts = chain::get_heaviest_tipset(self.bs.as_ref())
+            .map_err(|e| Error::Other(e.to_string()))?
+            .ok_or_else(|| Error::Other("No heaviest tipset".to_owned()))?;
+
+        while ts.epoch() > 0 {
+            // Once the sender's sequence in the state is not past the message's sequence,
+            // the message cannot have been executed any earlier
+            match self.get_actor(&from, ts.parent_state())? {
+                Some(actor) if actor.sequence > sequence => {}
+                _ => return Ok(None),
+            }
+
+            let parent = chain::tipset_from_keys(self.bs.as_ref(), ts.parents())
+                .map_err(|e| Error::Other(e.to_string()))?;
+            if let Some(index) = self.message_index(&parent, mcid)? {
+                let receipts = Amt::<MessageReceipt, _>::load(
+                    ts.blocks()[0].message_receipts(),
+                    self.bs.as_ref(),
+                )
+                .map_err(|e| Error::State(e.to_string()))?;
+                let receipt = receipts
+                    .get(index)
+                    .map_err(|e| Error::State(e.to_string()))?
+                    .ok_or_else(|| {
+                        Error::State(format!("Receipt of message {} not found", mcid))
+                    })?;
+                return Ok(Some((ts, receipt)));
+            }
+            ts = parent;
+        }
+        Ok(None)
+    }
+
+    /// Waits until the message has been executed in a tipset with the given amount of
+    /// tipsets on top of it, checking the chain again on every head change received from the
+    /// subscriber. Returns the tipset in which the message was executed and its receipt, or an
+    /// error if it isn't found before the timeout.
+    pub async fn wait_for_message(
+        &self,
+        subscriber: Subscriber<HeadChange>,
+        mcid: &Cid,
+        confidence: u64,
+        timeout: Duration,
+    ) -> Result<(Tipset, MessageReceipt), Error> {
+        async_std::future::timeout(
+            timeout,
+            self.wait_on_head_changes(subscriber, mcid, confidence),
+        )
+        .await
+        .map_err(|_| Error::Other(format!("Timed out waiting for message {}", mcid)))?
+    }
+
+    async fn wait_on_head_changes(
+        &self,
+        mut subscriber: Subscriber<HeadChange>,
+        mcid: &Cid,
+        confidence: u64,
+    ) -> Result<(Tipset, MessageReceipt), Error> {
+        // The message may have been executed before the subscription
+        if let Some(found) = self.search_with_confidence(mcid, confidence)? {
+            return Ok(found);
+        }
+        while let Some(change) = subscriber.next().await {
+            if let HeadChange::Revert(_) = change {
+                continue;
+            }
+            if let Some(found) = self.search_with_confidence(mcid, confidence)? {
+                return Ok(found);
+            }
+        }
+        Err(Error::Other("Head change subscription closed".to_owned()))
+    }
+
+    /// Searches for the execution of the message, returning it only once the heaviest tipset
+    /// is the given amount of epochs past the tipset it was executed in
+    fn search_with_confidence(
+        &self,
+        mcid: &Cid,
+        confidence: u64,
+    ) -> Result<Option<(Tipset, MessageReceipt)>, Error> {
+        let (ts, receipt) = match self.search_for_message(mcid)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let head = chain::get_heaviest_tipset(self.bs.as_ref())
+            .map_err(|e| Error::Other(e.to_string()))?
+            .ok_or_else(|| Error::Other("No heaviest tipset".to_owned()))?;
+        if head.epoch() >= ts.epoch() + confidence {
+            Ok(Some((ts, receipt)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the sender and sequence of a signed or unsigned message in the store
+    fn load_message_sender(&self, mcid: &Cid) -> Result<(Address, u64), Error> {
+        if let Ok(Some(smsg)) = self.bs.get::<SignedMessage>(mcid) {
+            return Ok((*smsg.from(), smsg.sequence()));
+        }
+        let msg: UnsignedMessage = self
+            .bs
+            .get(mcid)
+            .map_err(|e| Error::Other(e.to_string()))?
+            .ok_or_else(|| Error::Other(format!("Message {} not found", mcid)))?;
+        Ok((*msg.from(), msg.sequence()))
+    }
+
+    /// Returns the index of the message in the order messages of the tipset are applied,
+    /// which is the index of its receipt
+    fn message_index(&self, ts: &Tipset, mcid: &Cid) -> Result<Option<u64>, Error> {
+        let mut applied = HashSet::new();
+        let mut index = 0;
+        for header in ts.blocks() {
+            let (bls_msgs, secp_msgs) = block_messages(self.bs.as_ref(), header)
+                .map_err(|e| Error::Other(e.to_string()))?;
+            let mut cids = Vec::with_capacity(bls_msgs.len() + secp_msgs.len());
+            for msg in &bls_msgs {
+                let cid = msg.cid().map_err(|e| Error::Other(e.to_string()))?;
+                cids.push((cid.clone(), cid));
+            }
+            for msg in &secp_msgs {
+                cids.push((
+                    msg.message()
+                        .cid()
+                        .map_err(|e| Error::Other(e.to_string()))?,
+                    msg.cid().map_err(|e| Error::Other(e.to_string()))?,
+                ));
+            }
+            for (unsigned_cid, cid) in cids {
+                // Messages included in multiple blocks are only applied once
+                if !applied.insert(unsigned_cid) {
+                    continue;
+                }
+                if &cid == mcid {
+                    return Ok(Some(index));
+                }
+                index += 1;
+            }
+        }
+        Ok(None)
+    }
+
     /// Returns a bls public key from provided address
     pub fn get_bls_public_key(
         db: &Arc<DB>,
@@ -235,3 +601,211 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actor::{Serialized, ACCOUNT_ACTOR_CODE_ID, REWARD_ACTOR_ADDR};
+    use async_std::task;
+    use cid::multihash::{Blake2b256, Identity};
+    use db::MemoryDB;
+    use forest_blocks::TxMeta;
+
+    /// Chain of a genesis tipset, a tipset including a message and a tipset executing it
+    struct TestChain {
+        db: Arc<MemoryDB>,
+        included: Tipset,
+        executed: Tipset,
+        msg: UnsignedMessage,
+        receipt: MessageReceipt,
+    }
+
+    fn sender() -> Address {
+        Address::new_id(100)
+    }
+
+    fn account(db: &MemoryDB, addr: Address, balance: u64) -> ActorState {
+        let head = db
+            .put(&actor::account::State { address: addr }, Identity)
+            .unwrap();
+        ActorState::new(ACCOUNT_ACTOR_CODE_ID.clone(), head, balance.into(), 0)
+    }
+
+    fn message(sequence: u64) -> UnsignedMessage {
+        UnsignedMessage::builder()
+            .to(sender())
+            .from(sender())
+            .sequence(sequence)
+            .method_num(0)
+            .value(1u8.into())
+            .gas_price(1u8.into())
+            .gas_limit(1_000_000)
+            .params(Serialized::default())
+            .build()
+            .unwrap()
+    }
+
+    fn header(
+        db: &MemoryDB,
+        epoch: ChainEpoch,
+        parents: &Tipset,
+        state_root: &Cid,
+        msgs: &[UnsignedMessage],
+        receipts: &[MessageReceipt],
+    ) -> BlockHeader {
+        chain::put_messages(db, msgs).unwrap();
+        let bls_cids = msgs
+            .iter()
+            .map(|msg| msg.cid().unwrap())
+            .collect::<Vec<_>>();
+        let meta = TxMeta {
+            bls_message_root: Amt::new_from_slice(db, &bls_cids).unwrap(),
+            secp_message_root: Amt::<Cid, _>::new_from_slice(db, &[]).unwrap(),
+        };
+        BlockHeader::builder()
+            .epoch(epoch)
+            .parents(parents.key().clone())
+            .messages(db.put(&meta, Blake2b256).unwrap())
+            .message_receipts(Amt::new_from_slice(db, receipts).unwrap())
+            .state_root(state_root.clone())
+            .miner_address(Address::new_id(0))
+            .build_and_validate()
+            .unwrap()
+    }
+
+    fn setup_chain() -> TestChain {
+        let db = Arc::new(MemoryDB::default());
+        let mut state = StateTree::new(db.as_ref());
+        state
+            .set_actor(&REWARD_ACTOR_ADDR, account(&db, *REWARD_ACTOR_ADDR, 0))
+            .unwrap();
+        state
+            .set_actor(&sender(), account(&db, sender(), 10_000_000))
+            .unwrap();
+        let genesis_state = state.flush().unwrap();
+
+        // The state claimed by the executing tipset is the result of applying the message
+        let msg = message(0);
+        let rand = ChainRand::new(TipsetKeys::new(vec![]));
+        let mut vm = VM::new(
+            &genesis_state,
+            db.as_ref(),
+            1,
+            DefaultSyscalls::new(db.as_ref()),
+            &rand,
+        )
+        .unwrap();
+        let receipt = vm.apply_message(&msg).unwrap().msg_receipt;
+        let executed_state = vm.flush().unwrap();
+
+        let genesis = BlockHeader::builder()
+            .messages(db.put(&"genesis", Blake2b256).unwrap())
+            .message_receipts(Amt::<MessageReceipt, _>::new_from_slice(db.as_ref(), &[]).unwrap())
+            .state_root(genesis_state.clone())
+            .miner_address(Address::new_id(0))
+            .build_and_validate()
+            .unwrap();
+        let genesis = Tipset::new(vec![genesis]).unwrap();
+        let included = Tipset::new(vec![header(
+            &db,
+            1,
+            &genesis,
+            &genesis_state,
+            &[msg.clone()],
+            &[],
+        )])
+        .unwrap();
+        let executed = Tipset::new(vec![header(
+            &db,
+            2,
+            &included,
+            &executed_state,
+            &[],
+            &[receipt.clone()],
+        )])
+        .unwrap();
+
+        let cs = ChainStore::new(Arc::clone(&db));
+        for ts in &[&genesis, &included, &executed] {
+            cs.persist_headers(ts).unwrap();
+        }
+        TestChain {
+            db,
+            included,
+            executed,
+            msg,
+            receipt,
+        }
+    }
+
+    #[test]
+    fn search_for_message_test() {
+        let chain = setup_chain();
+        let sm = StateManager::new(Arc::clone(&chain.db));
+        let mut cs = ChainStore::new(Arc::clone(&chain.db));
+        let mcid = chain.msg.cid().unwrap();
+
+        // Not executed yet while the tipset including it is the head
+        task::block_on(cs.set_heaviest_tipset(Arc::new(chain.included.clone()))).unwrap();
+        assert!(sm.search_for_message(&mcid).unwrap().is_none());
+
+        task::block_on(cs.set_heaviest_tipset(Arc::new(chain.executed.clone()))).unwrap();
+        let (ts, receipt) = sm.search_for_message(&mcid).unwrap().unwrap();
+        assert_eq!(ts, chain.executed);
+        assert!(receipt == chain.receipt);
+
+        // The sender's sequence shows the next message was never executed
+        let next = message(1);
+        chain::put_messages(chain.db.as_ref(), &[next.clone()]).unwrap();
+        assert!(sm
+            .search_for_message(&next.cid().unwrap())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn replay_test() {
+        let chain = setup_chain();
+        let sm = StateManager::new(Arc::clone(&chain.db));
+        let mcid = chain.msg.cid().unwrap();
+
+        let res = sm.replay(&chain.included, &mcid).unwrap();
+        assert_eq!(res.msg.cid().unwrap(), mcid);
+        assert!(res.msg_rct == chain.receipt);
+        assert!(res.error.is_none());
+
+        let other = message(1).cid().unwrap();
+        assert!(sm.replay(&chain.included, &other).is_err());
+    }
+
+    #[test]
+    fn wait_for_message_test() {
+        let chain = setup_chain();
+        let sm = StateManager::new(Arc::clone(&chain.db));
+        let mut cs = ChainStore::new(Arc::clone(&chain.db));
+        let mcid = chain.msg.cid().unwrap();
+
+        task::block_on(async {
+            cs.set_heaviest_tipset(Arc::new(chain.included.clone()))
+                .await
+                .unwrap();
+
+            // Found once the head changes to the tipset executing the message
+            let subscriber = cs.subscribe();
+            let wait = sm.wait_for_message(subscriber, &mcid, 0, Duration::from_secs(10));
+            let apply = cs.set_heaviest_tipset(Arc::new(chain.executed.clone()));
+            let (res, applied) = futures::join!(wait, apply);
+            applied.unwrap();
+            let (ts, receipt) = res.unwrap();
+            assert_eq!(ts, chain.executed);
+            assert!(receipt == chain.receipt);
+
+            // Waiting for more confidence than the chain has times out
+            let subscriber = cs.subscribe();
+            let res = sm
+                .wait_for_message(subscriber, &mcid, 1, Duration::from_millis(10))
+                .await;
+            assert!(res.is_err());
+        });
+    }
+}
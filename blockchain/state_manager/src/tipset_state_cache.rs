@@ -0,0 +1,195 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::CidPair;
+use async_std::sync::Mutex;
+use db::Store;
+use encoding::{from_slice, to_vec, Cbor};
+use forest_blocks::TipsetKeys;
+use lru::LruCache;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::sync::{Arc, Mutex as SyncMutex};
+
+/// Prefix of the datastore keys the roots of tipsets are persisted under
+const TIPSET_STATE_PREFIX: &[u8] = b"tipset_state/";
+
+/// Index of the state and receipt roots computed for tipsets. Roots are persisted in the
+/// datastore so they outlive the node, with the most recently used ones kept in memory.
+pub(crate) struct TipsetStateCache {
+    cache: Mutex<LruCache<TipsetKeys, CidPair>>,
+    /// Locks of the tipsets being computed, which concurrent callers wait on
+    pending: SyncMutex<HashMap<TipsetKeys, Arc<Mutex<()>>>>,
+}
+
+impl TipsetStateCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            cache: Mutex::new(LruCache::new(capacity)),
+            pending: SyncMutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the roots of the tipset, computing them if they are not indexed yet.
+    /// Concurrent calls for the same tipset wait on a single computation.
+    pub async fn get_or_compute<DB, F>(
+        &self,
+        db: &DB,
+        key: &TipsetKeys,
+        compute: F,
+    ) -> Result<CidPair, Box<dyn StdError>>
+    where
+        DB: Store,
+        F: FnOnce() -> Result<CidPair, Box<dyn StdError>>,
+    {
+        if let Some(roots) = self.get(db, key).await? {
+            return Ok(roots);
+        }
+
+        let lock = Arc::clone(
+            self.pending
+                .lock()
+                .unwrap()
+                .entry(key.clone())
+                .or_insert_with(|| Arc::new(Mutex::new(()))),
+        );
+        let _guard = lock.lock().await;
+        let res = self.compute_once(db, key, compute).await;
+        self.pending.lock().unwrap().remove(key);
+        res
+    }
+
+    /// Computes and indexes the roots of the tipset, unless they were computed by another
+    /// caller while waiting on the lock of the tipset
+    async fn compute_once<DB, F>(
+        &self,
+        db: &DB,
+        key: &TipsetKeys,
+        compute: F,
+    ) -> Result<CidPair, Box<dyn StdError>>
+    where
+        DB: Store,
+        F: FnOnce() -> Result<CidPair, Box<dyn StdError>>,
+    {
+        if let Some(roots) = self.get(db, key).await? {
+            return Ok(roots);
+        }
+        let roots = compute()?;
+        db.write(db_key(key)?, to_vec(&roots)?)?;
+        self.cache.lock().await.put(key.clone(), roots.clone());
+        Ok(roots)
+    }
+
+    /// Returns the indexed roots of the tipset, loading them from the datastore if they are
+    /// not in memory
+    async fn get<DB: Store>(
+        &self,
+        db: &DB,
+        key: &TipsetKeys,
+    ) -> Result<Option<CidPair>, Box<dyn StdError>> {
+        if let Some(roots) = self.cache.lock().await.get(key) {
+            return Ok(Some(roots.clone()));
+        }
+        match db.read(db_key(key)?)? {
+            Some(bz) => {
+                let roots: CidPair = from_slice(&bz)?;
+                self.cache.lock().await.put(key.clone(), roots.clone());
+                Ok(Some(roots))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Datastore key the roots of the tipset are persisted under
+fn db_key(key: &TipsetKeys) -> Result<Vec<u8>, Box<dyn StdError>> {
+    Ok([TIPSET_STATE_PREFIX, &key.marshal_cbor()?].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+    use cid::{multihash::Identity, Cid};
+    use db::MemoryDB;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::Duration;
+
+    fn roots() -> CidPair {
+        (
+            Cid::new_from_cbor(&[1], Identity),
+            Cid::new_from_cbor(&[2], Identity),
+        )
+    }
+
+    #[test]
+    fn persisted_roots() {
+        let db = MemoryDB::default();
+        let key = TipsetKeys::new(vec![Cid::new_from_cbor(&[0], Identity)]);
+        let computed = AtomicUsize::new(0);
+        let compute = || -> Result<CidPair, Box<dyn StdError>> {
+            computed.fetch_add(1, Ordering::SeqCst);
+            Ok(roots())
+        };
+
+        task::block_on(async {
+            let cache = TipsetStateCache::new(1);
+            assert_eq!(
+                cache.get_or_compute(&db, &key, compute).await.unwrap(),
+                roots()
+            );
+            assert_eq!(
+                cache.get_or_compute(&db, &key, compute).await.unwrap(),
+                roots()
+            );
+
+            // A new cache, as after a restart, loads the roots from the datastore
+            let cache = TipsetStateCache::new(1);
+            assert_eq!(
+                cache.get_or_compute(&db, &key, compute).await.unwrap(),
+                roots()
+            );
+        });
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn concurrent_computations_deduplicated() {
+        const CALLERS: usize = 8;
+        let db = Arc::new(MemoryDB::default());
+        let key = TipsetKeys::new(vec![Cid::new_from_cbor(&[0], Identity)]);
+        let cache = Arc::new(TipsetStateCache::new(1));
+        let computed = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(CALLERS));
+
+        let callers: Vec<_> = (0..CALLERS)
+            .map(|_| {
+                let (db, key, cache, computed, barrier) = (
+                    db.clone(),
+                    key.clone(),
+                    cache.clone(),
+                    computed.clone(),
+                    barrier.clone(),
+                );
+                thread::spawn(move || {
+                    // Start every call at once, with the computation slow enough for the
+                    // other calls to arrive while it is running
+                    barrier.wait();
+                    task::block_on(cache.get_or_compute(db.as_ref(), &key, || {
+                        computed.fetch_add(1, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(50));
+                        Ok(roots())
+                    }))
+                    .map_err(|e| e.to_string())
+                })
+            })
+            .collect();
+
+        for caller in callers {
+            assert_eq!(caller.join().unwrap(), Ok(roots()));
+        }
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{cli_error_and_die, Config};
+use rpc::auth::{create_token, generate_secret, Access};
+use std::io;
+use structopt::StructOpt;
+use utils::{read_file_to_string, read_file_to_vec, write_to_file};
+
+/// File in the RPC directory of the data directory holding the secret API tokens are signed with
+const JWT_SECRET_FILE: &str = "jwt_secret";
+/// File in the RPC directory of the data directory holding the admin token of the node
+const TOKEN_FILE: &str = "token";
+
+#[derive(Debug, StructOpt)]
+pub enum AuthCommands {
+    /// Create a new API token with the given permission
+    #[structopt(name = "create-token")]
+    CreateToken {
+        #[structopt(
+            short,
+            long,
+            help = "Permission to assign to the token, one of: read, write, sign, admin"
+        )]
+        perm: String,
+    },
+}
+
+impl AuthCommands {
+    pub fn run(&self, cfg: &Config) {
+        match self {
+            Self::CreateToken { perm } => {
+                let access: Access = match perm.parse() {
+                    Ok(access) => access,
+                    Err(e) => cli_error_and_die(&e.to_string()),
+                };
+                let secret = match read_jwt_secret(&cfg.data_dir) {
+                    Ok(secret) => secret,
+                    Err(e) => cli_error_and_die(&e),
+                };
+                match create_token(access, &secret) {
+                    Ok(token) => println!("{}", token),
+                    Err(e) => cli_error_and_die(&e.to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Returns the directory of the RPC files in the data directory
+fn rpc_dir(data_dir: &str) -> String {
+    format!("{}{}", data_dir, "/rpc/")
+}
+
+/// Loads the secret API tokens are signed with from the data directory. A new secret is
+/// generated and stored if none exists yet, which is only done by the node on startup.
+pub(crate) fn load_jwt_secret(data_dir: &str) -> Result<Vec<u8>, io::Error> {
+    let dir = rpc_dir(data_dir);
+    match read_file_to_vec(&format!("{}{}", dir, JWT_SECRET_FILE)) {
+        Ok(secret) => Ok(secret),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let secret = generate_secret();
+            write_to_file(&secret, &dir, JWT_SECRET_FILE)?;
+            Ok(secret)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads the secret API tokens are signed with from the data directory of a node, failing if
+/// the node has not created one
+fn read_jwt_secret(data_dir: &str) -> Result<Vec<u8>, String> {
+    let path = format!("{}{}", rpc_dir(data_dir), JWT_SECRET_FILE);
+    read_file_to_vec(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => format!(
+            "No API secret found at {}, start the node with this data directory first",
+            path
+        ),
+        _ => format!("Failed to read API secret from {}: {}", path, e),
+    })
+}
+
+/// Writes an admin token signed with the secret to the data directory, for the CLI to call
+/// the RPC server of the node with
+pub(crate) fn write_admin_token(data_dir: &str, secret: &[u8]) -> Result<(), String> {
+    let token = create_token(Access::Admin, secret).map_err(|e| e.to_string())?;
+    write_to_file(token.as_bytes(), &rpc_dir(data_dir), TOKEN_FILE).map_err(|e| e.to_string())
+}
+
+/// Reads the admin token written by the node to the data directory, failing if the node has
+/// not written one
+pub(crate) fn read_admin_token(data_dir: &str) -> Result<String, String> {
+    let path = format!("{}{}", rpc_dir(data_dir), TOKEN_FILE);
+    match read_file_to_string(&path) {
+        Ok(token) => Ok(token.trim().to_owned()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(format!(
+            "No API token found at {}, start the node with this data directory first",
+            path
+        )),
+        Err(e) => Err(format!("Failed to read API token from {}: {}", path, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpc::auth::check_access;
+
+    #[test]
+    fn admin_token_file() {
+        let dir = std::env::temp_dir().join(format!("forest_token_test_{}", std::process::id()));
+        let data_dir = dir.to_str().unwrap();
+
+        // Nothing is created when the node never started with the data directory
+        assert!(read_admin_token(data_dir).is_err());
+        assert!(read_jwt_secret(data_dir).is_err());
+        assert!(!dir.exists());
+
+        let secret = load_jwt_secret(data_dir).unwrap();
+        write_admin_token(data_dir, &secret).unwrap();
+        assert_eq!(read_jwt_secret(data_dir).unwrap(), secret);
+        let token = read_admin_token(data_dir).unwrap();
+        assert!(check_access("Filecoin.WalletNew", Some(&token), &secret).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
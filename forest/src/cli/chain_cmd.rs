@@ -0,0 +1,41 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{parse_cid, print_json, rpc_client, run_rpc_command, Config};
+use serde_json::Value;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub enum ChainCommands {
+    /// Print the block cids of the heaviest tipset
+    #[structopt(name = "head")]
+    Head,
+    /// Print a block header
+    #[structopt(name = "get-block")]
+    GetBlock {
+        #[structopt(help = "Cid of the block")]
+        cid: String,
+    },
+}
+
+impl ChainCommands {
+    pub fn run(&self, cfg: &Config) {
+        run_rpc_command(async {
+            match self {
+                Self::Head => {
+                    let head: Value = rpc_client::call(cfg, "Filecoin.ChainHead", ()).await?;
+                    let cids = head["Cids"].as_array().cloned().unwrap_or_default();
+                    for cid in cids {
+                        println!("{}", cid["/"].as_str().unwrap_or_default());
+                    }
+                }
+                Self::GetBlock { cid } => {
+                    let block: Value =
+                        rpc_client::call(cfg, "Filecoin.ChainGetBlock", (parse_cid(cid),)).await?;
+                    print_json(&block);
+                }
+            }
+            Ok::<(), String>(())
+        });
+    }
+}
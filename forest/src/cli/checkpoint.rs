@@ -0,0 +1,120 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use blocks::TipsetKeys;
+use chain::ChainStore;
+use cid::Cid;
+use forest_car::load_car;
+use ipld_blockstore::BlockStore;
+use log::info;
+use serde::Deserialize;
+use std::error::Error as StdError;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+/// Trusted checkpoint to start syncing the chain forward from. The history before the
+/// checkpoint is not validated. The checkpoint is loaded from a snapshot, as its headers and
+/// state are not fetched from the network.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CheckpointConfig {
+    /// CAR snapshot containing the checkpoint tipset as roots, its headers, messages and state
+    pub snapshot: Option<String>,
+    /// Cids of the blocks of the checkpoint tipset, which must match the roots of the snapshot
+    pub tipset: Option<Vec<String>>,
+    /// Parent state root of the checkpoint tipset
+    pub state_root: Option<String>,
+}
+
+/// Imports the snapshot of the checkpoint into the chain store if given, and sets the checkpoint
+/// tipset as the heaviest tipset. The chain store is left unchanged if it is already synced past
+/// the checkpoint. A checkpoint tipset or state root can't be given without a snapshot.
+pub fn initialize_checkpoint<BS>(
+    config: &CheckpointConfig,
+    chain_store: &mut ChainStore<BS>,
+) -> Result<(), Box<dyn StdError>>
+where
+    BS: BlockStore,
+{
+    let path = match &config.snapshot {
+        Some(path) => path,
+        None if config.tipset.is_some() || config.state_root.is_some() => {
+            return Err(
+                "Checkpoint requires a snapshot containing the checkpoint tipset and its state"
+                    .into(),
+            );
+        }
+        None => return Ok(()),
+    };
+
+    let reader = BufReader::new(File::open(path)?);
+    let cids = load_car(chain_store.blockstore(), reader)?;
+    info!("Imported snapshot from {}", path);
+    if let Some(tipset) = &config.tipset {
+        let tipset = tipset
+            .iter()
+            .map(|c| Cid::from_raw_cid(c.as_str()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if tipset != cids {
+            return Err("Checkpoint tipset does not match the roots of the snapshot".into());
+        }
+    }
+
+    let checkpoint = chain_store.tipset_from_keys(&TipsetKeys::new(cids))?;
+    if let Some(state_root) = &config.state_root {
+        if &Cid::from_raw_cid(state_root.as_str())? != checkpoint.parent_state() {
+            return Err("Checkpoint state root does not match the checkpoint tipset".into());
+        }
+    }
+    if !chain_store
+        .blockstore()
+        .exists(checkpoint.parent_state().to_bytes())?
+    {
+        return Err("State of the checkpoint tipset not found in store".into());
+    }
+
+    if let Some(heaviest) = chain_store.heaviest_tipset() {
+        if heaviest.epoch() >= checkpoint.epoch() {
+            info!(
+                "Chain already synced to epoch {}, past checkpoint at epoch {}",
+                heaviest.epoch(),
+                checkpoint.epoch()
+            );
+            return Ok(());
+        }
+    }
+
+    info!(
+        "Syncing from checkpoint at epoch {}: {:?}",
+        checkpoint.epoch(),
+        checkpoint.cids()
+    );
+    async_std::task::block_on(chain_store.set_checkpoint(Arc::new(checkpoint)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::MemoryDB;
+
+    #[test]
+    fn checkpoint_requires_snapshot() {
+        let mut chain_store = ChainStore::new(Arc::new(MemoryDB::default()));
+        assert!(initialize_checkpoint(&CheckpointConfig::default(), &mut chain_store).is_ok());
+
+        let config = CheckpointConfig {
+            tipset: Some(vec![
+                "bafy2bzacecujyfvb74s7xxnlajidxpgcpk6abyatk62dlhgq6gcob3iixhgom".to_owned(),
+            ]),
+            ..Default::default()
+        };
+        let err = initialize_checkpoint(&config, &mut chain_store).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Checkpoint requires a snapshot containing the checkpoint tipset and its state"
+        );
+        assert!(chain_store.heaviest_tipset().is_none());
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{cli_error_and_die, Config};
+use blocks::{BlockHeader, Tipset, TipsetKeys};
+use chain::{ChainStore, Error as ChainError};
+use cid::Cid;
+use db::{RocksDb, Store};
+use forest_car::CarReader;
+use ipld_blockstore::BlockStore;
+use log::info;
+use state_manager::StateManager;
+use std::error::Error as StdError;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::sync::Arc;
+use structopt::StructOpt;
+
+/// Number of blocks written to the blockstore at once when importing a snapshot
+const IMPORT_BATCH_SIZE: usize = 4096;
+
+#[derive(Debug, StructOpt)]
+pub struct ImportCommand {
+    #[structopt(help = "CAR snapshot of the chain, rooted at the tipset to set as head")]
+    snapshot: String,
+    #[structopt(
+        long,
+        help = "Validate the header chain of the snapshot before setting its head"
+    )]
+    validate: bool,
+    #[structopt(
+        long,
+        default_value = "0",
+        help = "Number of tipsets below the head to re-execute the state of when validating"
+    )]
+    recompute: u64,
+}
+
+impl ImportCommand {
+    pub fn run(&self, cfg: &Config) {
+        let mut db = RocksDb::new(format!("{}{}", cfg.data_dir, "/db"));
+        if let Err(e) = db.open() {
+            cli_error_and_die(&format!("Failed to open database: {}", e));
+        }
+        let mut chain_store = ChainStore::new(Arc::new(db));
+
+        let file = match File::open(&self.snapshot) {
+            Ok(file) => file,
+            Err(e) => cli_error_and_die(&format!("Failed to open {}: {}", self.snapshot, e)),
+        };
+        if let Err(e) = import_chain(
+            BufReader::new(file),
+            &mut chain_store,
+            self.validate,
+            self.recompute,
+        ) {
+            cli_error_and_die(&format!("Failed to import snapshot: {}", e));
+        }
+    }
+}
+
+/// Imports a CAR snapshot into the chain store and sets the tipset it is rooted at as the
+/// heaviest tipset. The genesis block is set if the snapshot reaches it. When validating,
+/// the blocks must match their Cids, the header chain and the messages of its blocks are
+/// checked, the state tree of the head must be loadable, and the state of the given number
+/// of tipsets below the head is re-executed, and must match the state roots claimed by
+/// their children.
+fn import_chain<R, BS>(
+    reader: BufReader<R>,
+    chain_store: &mut ChainStore<BS>,
+    validate: bool,
+    recompute: u64,
+) -> Result<Tipset, Box<dyn StdError>>
+where
+    R: Read,
+    BS: BlockStore,
+{
+    let roots = load_blocks(reader, chain_store.blockstore(), validate)?;
+    let head = chain_store.tipset_from_keys(&TipsetKeys::new(roots))?;
+    info!("Imported snapshot of tipset at epoch {}", head.epoch());
+
+    // The recomputed tipsets are checked against the state roots claimed by their children
+    let recent = if validate { recompute as usize + 1 } else { 0 };
+    let (tipsets, genesis) = walk_headers(chain_store, &head, validate, recent)?;
+    if validate {
+        let sm = StateManager::new(Arc::clone(&chain_store.db));
+        sm.get_network_name(head.parent_state())
+            .map_err(|e| format!("Failed to load the state of the head: {}", e))?;
+        for (child, parent) in tipsets.iter().zip(tipsets.iter().skip(1)) {
+            recompute_state(&sm, child, parent)?;
+        }
+        info!("Validated snapshot");
+    }
+
+    if let Some(genesis) = genesis {
+        match chain_store.genesis()? {
+            Some(store_genesis) if store_genesis != genesis => {
+                return Err("Genesis of the snapshot does not match the genesis in store".into());
+            }
+            Some(_) => (),
+            None => chain_store.set_genesis(genesis)?,
+        }
+    }
+
+    async_std::task::block_on(chain_store.set_checkpoint(Arc::new(head.clone())))?;
+    info!(
+        "Set head to tipset at epoch {}: {:?}",
+        head.epoch(),
+        head.cids()
+    );
+    Ok(head)
+}
+
+/// Streams the blocks of the CAR snapshot into the blockstore in batches, and returns its roots.
+/// When validating, the data of each block must hash to its Cid.
+fn load_blocks<R, BS>(
+    reader: BufReader<R>,
+    bs: &BS,
+    validate: bool,
+) -> Result<Vec<Cid>, Box<dyn StdError>>
+where
+    R: Read,
+    BS: BlockStore,
+{
+    let mut car_reader = CarReader::new(reader)?;
+    let mut keys = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    let mut values = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    while car_reader.has_next_block()? {
+        let block = car_reader.next_block()?;
+        if validate && &Cid::new_from_prefix(&block.cid().prefix(), block.data())? != block.cid() {
+            return Err(format!("Data of block {} does not match its cid", block.cid()).into());
+        }
+        keys.push(block.cid().to_bytes());
+        values.push(block.data().to_vec());
+        if keys.len() == IMPORT_BATCH_SIZE {
+            bs.bulk_write(&keys, &values)?;
+            keys.clear();
+            values.clear();
+        }
+    }
+    bs.bulk_write(&keys, &values)?;
+    Ok(car_reader.header.roots)
+}
+
+/// Walks the header chain from the head back to genesis, or to the oldest tipset of the
+/// snapshot, returning the given number of most recent tipsets and the genesis block if it
+/// is reached. When validating, epochs must decrease and weights must not increase along
+/// the chain, and the messages of every block must be in the store.
+fn walk_headers<BS>(
+    chain_store: &ChainStore<BS>,
+    head: &Tipset,
+    validate: bool,
+    recent: usize,
+) -> Result<(Vec<Tipset>, Option<BlockHeader>), Box<dyn StdError>>
+where
+    BS: BlockStore,
+{
+    let mut tipsets = Vec::with_capacity(recent);
+    let mut current = head.clone();
+    loop {
+        if tipsets.len() < recent {
+            tipsets.push(current.clone());
+        }
+        if validate {
+            for header in current.blocks() {
+                chain::block_messages(chain_store.blockstore(), header).map_err(|e| {
+                    format!("Failed to load messages of block {}: {}", header.cid(), e)
+                })?;
+            }
+        }
+        if current.parents().cids().is_empty() {
+            return Ok((tipsets, Some(current.blocks()[0].clone())));
+        }
+        let parent = match chain_store.tipset_from_keys(current.parents()) {
+            Ok(parent) => parent,
+            // The history of the snapshot ends before genesis
+            Err(ChainError::NotFound(_)) => return Ok((tipsets, None)),
+            Err(e) => return Err(e.into()),
+        };
+        if validate {
+            if parent.epoch() >= current.epoch() {
+                return Err(format!(
+                    "Tipset at epoch {} has a parent at epoch {}",
+                    current.epoch(),
+                    parent.epoch()
+                )
+                .into());
+            }
+            if parent.weight() > current.weight() {
+                return Err(format!(
+                    "Tipset at epoch {} is lighter than its parent",
+                    current.epoch()
+                )
+                .into());
+            }
+        }
+        current = parent;
+    }
+}
+
+/// Re-executes the messages of the parent tipset, checking the resulting state and receipt
+/// roots against the ones claimed by the child tipset
+fn recompute_state<BS>(
+    sm: &StateManager<BS>,
+    child: &Tipset,
+    parent: &Tipset,
+) -> Result<(), Box<dyn StdError>>
+where
+    BS: BlockStore,
+{
+    let (state_root, receipts_root) = sm.compute_tipset_state(parent.blocks(), None)?;
+    if &state_root != child.parent_state() {
+        return Err(format!(
+            "State root {} computed at epoch {} does not match {} claimed by its child",
+            state_root,
+            parent.epoch(),
+            child.parent_state()
+        )
+        .into());
+    }
+    if &receipts_root != child.blocks()[0].message_receipts() {
+        return Err(format!(
+            "Receipts root {} computed at epoch {} does not match the one claimed by its child",
+            receipts_root,
+            parent.epoch()
+        )
+        .into());
+    }
+    info!("Recomputed state of tipset at epoch {}", parent.epoch());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actor::{init, ActorState, INIT_ACTOR_ADDR, INIT_ACTOR_CODE_ID};
+    use address::Address;
+    use blocks::TxMeta;
+    use cid::multihash::Blake2b256;
+    use db::MemoryDB;
+    use ipld_amt::Amt;
+    use ipld_hamt::Hamt;
+    use state_tree::StateTree;
+
+    /// Exports the chain of a genesis and the given number of tipsets on top of it, returning
+    /// the snapshot and its head
+    fn export_chain(len: u64, lookback: Option<u64>) -> (Vec<u8>, Tipset) {
+        let db = MemoryDB::default();
+        let address_map = Hamt::<String, _>::new_with_bit_width(&db, 5)
+            .flush()
+            .unwrap();
+        let init_state = db
+            .put(
+                &init::State::new(address_map, "test".to_owned()),
+                Blake2b256,
+            )
+            .unwrap();
+        let mut state = StateTree::new(&db);
+        state
+            .set_actor(
+                &INIT_ACTOR_ADDR,
+                ActorState::new(
+                    INIT_ACTOR_CODE_ID.clone(),
+                    init_state,
+                    Default::default(),
+                    0,
+                ),
+            )
+            .unwrap();
+        let state_root = state.flush().unwrap();
+
+        let empty = Amt::<Cid, _>::new_from_slice(&db, &[]).unwrap();
+        let meta = TxMeta {
+            bls_message_root: empty.clone(),
+            secp_message_root: empty.clone(),
+        };
+        let messages = db.put(&meta, Blake2b256).unwrap();
+        let header = |epoch: u64, parents: Vec<Cid>| {
+            BlockHeader::builder()
+                .epoch(epoch)
+                .parents(TipsetKeys::new(parents))
+                .messages(messages.clone())
+                .message_receipts(empty.clone())
+                .state_root(state_root.clone())
+                .miner_address(Address::new_id(0))
+                .build_and_validate()
+                .unwrap()
+        };
+        let chain_store = ChainStore::new(Arc::new(db));
+        let mut head = Tipset::new(vec![header(0, vec![])]).unwrap();
+        chain_store.persist_headers(&head).unwrap();
+        for epoch in 1..=len {
+            head = Tipset::new(vec![header(epoch, head.cids().to_vec())]).unwrap();
+            chain_store.persist_headers(&head).unwrap();
+        }
+        let snapshot = chain_store.export(&head, lookback, 0, Vec::new()).unwrap();
+        (snapshot, head)
+    }
+
+    fn import(
+        snapshot: &[u8],
+        validate: bool,
+    ) -> Result<(Tipset, ChainStore<MemoryDB>), Box<dyn StdError>> {
+        import_buffered(BufReader::new(snapshot), validate)
+    }
+
+    fn import_buffered(
+        reader: BufReader<&[u8]>,
+        validate: bool,
+    ) -> Result<(Tipset, ChainStore<MemoryDB>), Box<dyn StdError>> {
+        let mut chain_store = ChainStore::new(Arc::new(MemoryDB::default()));
+        let head = import_chain(reader, &mut chain_store, validate, 0)?;
+        Ok((head, chain_store))
+    }
+
+    #[test]
+    fn import_exported_chain() {
+        let (snapshot, head) = export_chain(2, None);
+        let (imported, chain_store) = import(&snapshot, true).unwrap();
+        assert_eq!(imported, head);
+        assert_eq!(chain_store.heaviest_tipset().as_deref(), Some(&head));
+        assert_eq!(chain_store.genesis().unwrap().map(|g| g.epoch()), Some(0));
+    }
+
+    #[test]
+    fn import_snapshot_larger_than_buffer() {
+        let (snapshot, head) = export_chain(128, None);
+        assert!(snapshot.len() > 8 * 1024);
+        let (imported, chain_store) = import(&snapshot, false).unwrap();
+        assert_eq!(imported, head);
+        assert_eq!(chain_store.genesis().unwrap().map(|g| g.epoch()), Some(0));
+
+        // Blocks ending at the end of the buffer don't end the import early
+        for capacity in 1..=64 {
+            let reader = BufReader::with_capacity(capacity, snapshot.as_slice());
+            let (_, chain_store) = import_buffered(reader, false).unwrap();
+            assert_eq!(chain_store.genesis().unwrap().map(|g| g.epoch()), Some(0));
+        }
+    }
+
+    #[test]
+    fn import_chain_without_genesis() {
+        // The history of the snapshot ends at the parent of the head
+        let (snapshot, head) = export_chain(2, Some(1));
+        let (imported, chain_store) = import(&snapshot, true).unwrap();
+        assert_eq!(imported, head);
+        assert!(chain_store.genesis().unwrap().is_none());
+    }
+
+    #[test]
+    fn import_corrupted_snapshot() {
+        // The snapshot ends with the data of the genesis header
+        let (mut snapshot, _) = export_chain(2, None);
+        let last = snapshot.len() - 1;
+        snapshot[last] ^= 0xff;
+        assert!(import(&snapshot, true).is_err());
+    }
+}
@@ -1,15 +1,42 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod auth_cmd;
+mod chain_cmd;
+mod checkpoint;
 mod config;
 mod genesis;
+mod import_cmd;
+mod mpool_cmd;
+mod net_cmd;
+mod rpc_client;
+mod send_cmd;
+mod sync_cmd;
+mod wallet_cmd;
 
+pub use self::auth_cmd::AuthCommands;
+pub(super) use self::auth_cmd::{load_jwt_secret, write_admin_token};
+pub use self::chain_cmd::ChainCommands;
+pub(super) use self::checkpoint::initialize_checkpoint;
+pub use self::checkpoint::CheckpointConfig;
 pub use self::config::Config;
 pub(super) use self::genesis::initialize_genesis;
+pub use self::import_cmd::ImportCommand;
+pub use self::mpool_cmd::MpoolCommands;
+pub use self::net_cmd::NetCommands;
+pub use self::send_cmd::SendCommand;
+pub use self::sync_cmd::SyncCommands;
+pub use self::wallet_cmd::WalletCommands;
 
+use address::{json::AddressJson, Address};
 use async_std::task;
+use cid::{json::CidJson, Cid};
+use futures::Future;
+use libp2p::core::multiaddr::{Multiaddr, Protocol};
+use serde_json::Value;
 use std::cell::RefCell;
 use std::io;
+use std::net::{IpAddr, SocketAddr};
 use std::process;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -28,6 +55,63 @@ pub struct CLI {
     pub config: Option<String>,
     #[structopt(short, long, help = "The genesis CAR file")]
     pub genesis: Option<String>,
+    #[structopt(long, help = "A CAR snapshot of a trusted checkpoint to sync from")]
+    pub snapshot: Option<String>,
+    #[structopt(
+        long,
+        use_delimiter = true,
+        help = "Comma separated block cids of the checkpoint tipset, checked against the snapshot"
+    )]
+    pub checkpoint: Option<Vec<String>>,
+    #[structopt(
+        long,
+        help = "The parent state root of the checkpoint tipset, checked against the snapshot"
+    )]
+    pub checkpoint_state_root: Option<String>,
+    #[structopt(subcommand)]
+    pub cmd: Option<Subcommand>,
+}
+
+/// Commands run instead of starting the node
+#[derive(Debug, StructOpt)]
+pub enum Subcommand {
+    #[structopt(name = "auth", about = "Manage RPC API tokens")]
+    Auth(AuthCommands),
+    #[structopt(
+        name = "import",
+        about = "Import a CAR snapshot of the chain into the database of the node"
+    )]
+    Import(ImportCommand),
+    #[structopt(name = "chain", about = "Inspect the chain of a running node")]
+    Chain(ChainCommands),
+    #[structopt(name = "wallet", about = "Manage the wallet of a running node")]
+    Wallet(WalletCommands),
+    #[structopt(name = "send", about = "Send funds from the wallet of a running node")]
+    Send(SendCommand),
+    #[structopt(name = "mpool", about = "Inspect the message pool of a running node")]
+    Mpool(MpoolCommands),
+    #[structopt(name = "net", about = "Inspect the peers of a running node")]
+    Net(NetCommands),
+    #[structopt(
+        name = "sync",
+        about = "Inspect and manage the chain syncer of a running node"
+    )]
+    Sync(SyncCommands),
+}
+
+impl Subcommand {
+    pub fn run(&self, cfg: &Config) {
+        match self {
+            Self::Auth(cmd) => cmd.run(cfg),
+            Self::Import(cmd) => cmd.run(cfg),
+            Self::Chain(cmd) => cmd.run(cfg),
+            Self::Wallet(cmd) => cmd.run(cfg),
+            Self::Send(cmd) => cmd.run(cfg),
+            Self::Mpool(cmd) => cmd.run(cfg),
+            Self::Net(cmd) => cmd.run(cfg),
+            Self::Sync(cmd) => cmd.run(cfg),
+        }
+    }
 }
 
 impl CLI {
@@ -44,12 +128,72 @@ impl CLI {
         if let Some(genesis_file) = &self.genesis {
             cfg.genesis_file = Some(genesis_file.to_owned());
         }
+        if let Some(snapshot) = &self.snapshot {
+            cfg.checkpoint.snapshot = Some(snapshot.to_owned());
+        }
+        if let Some(tipset) = &self.checkpoint {
+            cfg.checkpoint.tipset = Some(tipset.clone());
+        }
+        if let Some(state_root) = &self.checkpoint_state_root {
+            cfg.checkpoint.state_root = Some(state_root.to_owned());
+        }
         // (where to find these flags, should be easy to do with structops)
 
         Ok(cfg)
     }
 }
 
+/// Converts a TCP multiaddr into the socket address it represents
+pub(super) fn multiaddr_to_socket_addr(addr: &Multiaddr) -> Result<SocketAddr, String> {
+    let mut protocols = addr.iter();
+    let ip = match protocols.next() {
+        Some(Protocol::Ip4(ip)) => IpAddr::V4(ip),
+        Some(Protocol::Ip6(ip)) => IpAddr::V6(ip),
+        _ => return Err(format!("{} must start with an IP address", addr)),
+    };
+    match protocols.next() {
+        Some(Protocol::Tcp(port)) => Ok(SocketAddr::new(ip, port)),
+        _ => Err(format!("{} must contain a TCP port", addr)),
+    }
+}
+
+/// Prints an error message and exits the process
+fn cli_error_and_die(msg: &str) -> ! {
+    eprintln!("Error: {}", msg);
+    process::exit(1);
+}
+
+/// Runs a command calling the RPC server of the node, exiting with its error if it fails
+fn run_rpc_command(cmd: impl Future<Output = Result<(), String>>) {
+    if let Err(e) = task::block_on(cmd) {
+        cli_error_and_die(&e);
+    }
+}
+
+/// Parses the cid argument of a command, exiting if it is invalid
+fn parse_cid(cid: &str) -> CidJson {
+    match cid.parse::<Cid>() {
+        Ok(cid) => CidJson(cid),
+        Err(e) => cli_error_and_die(&format!("Invalid cid {}: {}", cid, e)),
+    }
+}
+
+/// Parses the address argument of a command, exiting if it is invalid
+fn parse_address(addr: &str) -> AddressJson {
+    match addr.parse::<Address>() {
+        Ok(addr) => AddressJson(addr),
+        Err(e) => cli_error_and_die(&format!("Invalid address {}: {}", addr, e)),
+    }
+}
+
+/// Prints the JSON value returned by the node in a readable format
+fn print_json(value: &Value) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => cli_error_and_die(&e.to_string()),
+    }
+}
+
 /// Blocks current thread until ctrl-c is received
 pub(super) fn block_until_sigint() {
     let (ctrlc_send, ctrlc_oneshot) = futures::channel::oneshot::channel();
@@ -0,0 +1,34 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{parse_address, print_json, rpc_client, run_rpc_command, Config};
+use serde_json::Value;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub enum MpoolCommands {
+    /// Print the pending messages of the message pool
+    #[structopt(name = "pending")]
+    Pending {
+        #[structopt(long, help = "Only print the messages sent from this address")]
+        from: Option<String>,
+    },
+}
+
+impl MpoolCommands {
+    pub fn run(&self, cfg: &Config) {
+        run_rpc_command(async {
+            match self {
+                Self::Pending { from } => {
+                    let from = from.as_ref().map(|from| parse_address(from));
+                    let pending: Vec<Value> =
+                        rpc_client::call(cfg, "Filecoin.MpoolPending", (from,)).await?;
+                    for msg in pending {
+                        print_json(&msg);
+                    }
+                }
+            }
+            Ok::<(), String>(())
+        });
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{rpc_client, run_rpc_command, Config};
+use serde_json::Value;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub enum NetCommands {
+    /// Print the peers the node is connected to, with their addresses
+    #[structopt(name = "peers")]
+    Peers,
+}
+
+impl NetCommands {
+    pub fn run(&self, cfg: &Config) {
+        run_rpc_command(async {
+            match self {
+                Self::Peers => {
+                    let peers: Vec<Value> = rpc_client::call(cfg, "Filecoin.NetPeers", ()).await?;
+                    for peer in peers {
+                        let addrs: Vec<&str> = peer["Addrs"]
+                            .as_array()
+                            .map(|addrs| addrs.iter().filter_map(Value::as_str).collect())
+                            .unwrap_or_default();
+                        println!(
+                            "{}, [{}]",
+                            peer["ID"].as_str().unwrap_or_default(),
+                            addrs.join(", ")
+                        );
+                    }
+                }
+            }
+            Ok::<(), String>(())
+        });
+    }
+}
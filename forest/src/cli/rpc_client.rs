@@ -0,0 +1,48 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::auth_cmd::read_admin_token;
+use super::{multiaddr_to_socket_addr, Config};
+use async_std::net::TcpStream;
+use http_types::{Body, Method, Request, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+/// Calls the method on the RPC server of the local node. Calls are authenticated with the
+/// admin token the node writes to its data directory.
+pub(super) async fn call<P, R>(cfg: &Config, method: &str, params: P) -> Result<R, String>
+where
+    P: Serialize,
+    R: DeserializeOwned,
+{
+    let addr = multiaddr_to_socket_addr(&cfg.rpc_listen_address)?;
+    let token = read_admin_token(&cfg.data_dir)?;
+
+    let url = Url::parse(&format!("http://{}/api", addr)).map_err(|e| e.to_string())?;
+    let mut req = Request::new(Method::Post, url);
+    req.insert_header("Authorization", format!("Bearer {}", token));
+    // Methods without parameters are called with an empty parameter list
+    let params = match serde_json::to_value(params).map_err(|e| e.to_string())? {
+        Value::Null => json!([]),
+        params => params,
+    };
+    let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    req.set_body(Body::from_json(&body).map_err(|e| e.to_string())?);
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .map_err(|e| format!("Failed to connect to node at {}: {}", addr, e))?;
+    let mut res = async_h1::connect(stream, req)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        let body = res.body_string().await.unwrap_or_default();
+        return Err(format!("Request failed with {}: {}", res.status(), body));
+    }
+
+    let mut res: Value = res.body_json().await.map_err(|e| e.to_string())?;
+    if let Some(err) = res.get("error") {
+        return Err(err["message"].as_str().unwrap_or_default().to_owned());
+    }
+    serde_json::from_value(res["result"].take()).map_err(|e| e.to_string())
+}
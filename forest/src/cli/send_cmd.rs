@@ -0,0 +1,55 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{cli_error_and_die, parse_address, rpc_client, run_rpc_command, Config};
+use address::json::AddressJson;
+use encoding::Cbor;
+use message::{
+    signed_message::json::SignedMessageJson, unsigned_message::json::UnsignedMessageJson,
+    UnsignedMessage,
+};
+use structopt::StructOpt;
+use vm::TokenAmount;
+
+#[derive(Debug, StructOpt)]
+pub struct SendCommand {
+    #[structopt(
+        long,
+        help = "Address to send from, the default address of the wallet if not given"
+    )]
+    from: Option<String>,
+    #[structopt(help = "Address to send to")]
+    target: String,
+    #[structopt(help = "Amount to send in attoFIL")]
+    amount: String,
+}
+
+impl SendCommand {
+    pub fn run(&self, cfg: &Config) {
+        let value: TokenAmount = match self.amount.parse() {
+            Ok(value) => value,
+            Err(e) => cli_error_and_die(&format!("Invalid amount {}: {}", self.amount, e)),
+        };
+        run_rpc_command(async {
+            let AddressJson(from) = match &self.from {
+                Some(from) => parse_address(from),
+                None => rpc_client::call(cfg, "Filecoin.WalletDefaultAddress", ()).await?,
+            };
+            let msg = UnsignedMessage::builder()
+                .from(from)
+                .to(parse_address(&self.target).0)
+                .value(value)
+                .build()?;
+
+            // The node assigns the nonce and gas values, and signs the message with its wallet
+            let SignedMessageJson(smsg) = rpc_client::call(
+                cfg,
+                "Filecoin.MpoolPushMessage",
+                (UnsignedMessageJson(msg),),
+            )
+            .await?;
+            println!("{}", smsg.cid().map_err(|e| e.to_string())?);
+            Ok::<(), String>(())
+        });
+    }
+}
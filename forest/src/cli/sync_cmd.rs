@@ -0,0 +1,91 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{parse_cid, rpc_client, run_rpc_command, Config};
+use serde_json::Value;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub enum SyncCommands {
+    /// Show the sync stage of the node and its progress towards the sync target
+    #[structopt(name = "status")]
+    Status,
+    /// Check if a block is marked as bad, and the reason it was marked
+    #[structopt(name = "check-bad")]
+    CheckBad {
+        #[structopt(help = "Cid of the block")]
+        cid: String,
+    },
+    /// Mark a block as bad, so chains containing it are not synced
+    #[structopt(name = "mark-bad")]
+    MarkBad {
+        #[structopt(help = "Cid of the block")]
+        cid: String,
+    },
+    /// Remove a block from the bad blocks
+    #[structopt(name = "unmark-bad")]
+    UnmarkBad {
+        #[structopt(help = "Cid of the block")]
+        cid: String,
+    },
+}
+
+impl SyncCommands {
+    pub fn run(&self, cfg: &Config) {
+        run_rpc_command(async {
+            match self {
+                Self::Status => {
+                    let state: Value = rpc_client::call(cfg, "Filecoin.SyncState", ()).await?;
+                    print_sync_state(&state);
+                }
+                Self::CheckBad { cid } => {
+                    let reason: String =
+                        rpc_client::call(cfg, "Filecoin.SyncCheckBad", (parse_cid(cid),)).await?;
+                    if reason.is_empty() {
+                        println!("Block {} is not marked as bad", cid);
+                    } else {
+                        println!("Block {} is bad: {}", cid, reason);
+                    }
+                }
+                Self::MarkBad { cid } => {
+                    rpc_client::call::<_, ()>(cfg, "Filecoin.SyncMarkBad", (parse_cid(cid),))
+                        .await?;
+                    println!("Marked block {} as bad", cid);
+                }
+                Self::UnmarkBad { cid } => {
+                    rpc_client::call::<_, ()>(cfg, "Filecoin.SyncUnmarkBad", (parse_cid(cid),))
+                        .await?;
+                    println!("Unmarked block {} as bad", cid);
+                }
+            }
+            Ok::<(), String>(())
+        });
+    }
+}
+
+/// Prints the stage and progress of the active syncs
+fn print_sync_state(state: &Value) {
+    let syncs = state["ActiveSyncs"].as_array().cloned().unwrap_or_default();
+    for (i, sync) in syncs.iter().enumerate() {
+        println!("Worker {}:", i);
+        println!("\tBase:\t{}", format_tipset(&sync["Base"]));
+        println!("\tTarget:\t{}", format_tipset(&sync["Target"]));
+        println!("\tStage:\t{}", sync["Stage"].as_str().unwrap_or_default());
+        println!("\tHeight:\t{}", sync["Height"]);
+        if let Some(msg) = sync["Message"].as_str().filter(|m| !m.is_empty()) {
+            println!("\tError:\t{}", msg);
+        }
+    }
+}
+
+/// Formats the cids and height of a tipset
+fn format_tipset(ts: &Value) -> String {
+    if ts.is_null() {
+        return "-".to_owned();
+    }
+    let cids: Vec<&str> = ts["Cids"]
+        .as_array()
+        .map(|cids| cids.iter().filter_map(|c| c["/"].as_str()).collect())
+        .unwrap_or_default();
+    format!("[{}] @ {}", cids.join(", "), ts["Height"])
+}
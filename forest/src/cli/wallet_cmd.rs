@@ -0,0 +1,56 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{parse_address, rpc_client, run_rpc_command, Config};
+use address::json::AddressJson;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub enum WalletCommands {
+    /// Generate a new key in the wallet of the node and print its address
+    #[structopt(name = "new")]
+    New {
+        #[structopt(
+            default_value = "secp256k1",
+            help = "Type of the key, one of: bls, secp256k1"
+        )]
+        key_type: String,
+    },
+    /// List the addresses of the wallet of the node
+    #[structopt(name = "list")]
+    List,
+    /// Print the balance of an address in attoFIL
+    #[structopt(name = "balance")]
+    Balance {
+        #[structopt(help = "Address to get the balance of")]
+        address: String,
+    },
+}
+
+impl WalletCommands {
+    pub fn run(&self, cfg: &Config) {
+        run_rpc_command(async {
+            match self {
+                Self::New { key_type } => {
+                    let AddressJson(addr) =
+                        rpc_client::call(cfg, "Filecoin.WalletNew", (key_type,)).await?;
+                    println!("{}", addr);
+                }
+                Self::List => {
+                    let addrs: Vec<AddressJson> =
+                        rpc_client::call(cfg, "Filecoin.WalletList", ()).await?;
+                    for AddressJson(addr) in addrs {
+                        println!("{}", addr);
+                    }
+                }
+                Self::Balance { address } => {
+                    let balance: String =
+                        rpc_client::call(cfg, "Filecoin.WalletBalance", (parse_address(address),))
+                            .await?;
+                    println!("{}", balance);
+                }
+            }
+            Ok::<(), String>(())
+        });
+    }
+}
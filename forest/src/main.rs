@@ -4,28 +4,41 @@
 mod cli;
 mod logger;
 
-use self::cli::{block_until_sigint, initialize_genesis};
+use self::cli::{
+    block_until_sigint, initialize_checkpoint, initialize_genesis, load_jwt_secret,
+    multiaddr_to_socket_addr, write_admin_token,
+};
+use async_std::sync::RwLock;
 use async_std::task;
 use beacon::DrandBeacon;
+use blockchain::message_pool::{MessagePool, MpoolProvider};
 use chain::ChainStore;
 use chain_sync::ChainSyncer;
 use db::RocksDb;
 use forest_libp2p::{get_keypair, Libp2pService};
+use key_management::{PersistentKeyStore, Wallet};
 use libp2p::identity::{ed25519, Keypair};
 use log::{info, trace};
-use rpc::start_rpc;
+use rpc::{start_rpc, HeadChanges, State};
+use state_manager::StateManager;
 use std::sync::Arc;
 use structopt::StructOpt;
 use utils::write_to_file;
 
 fn main() {
     logger::setup_logger();
-    info!("Starting Forest");
 
     // Capture CLI inputs
     let cli = cli::CLI::from_args();
     let config = cli.get_config().expect("CLI error");
 
+    // Run the given command instead of starting the node
+    if let Some(cmd) = &cli.cmd {
+        cmd.run(&config);
+        return;
+    }
+    info!("Starting Forest");
+
     let net_keypair = match get_keypair(&format!("{}{}", &config.data_dir, "/libp2p/keypair")) {
         Some(kp) => kp,
         None => {
@@ -45,6 +58,20 @@ fn main() {
         }
     };
 
+    // Load the secret API tokens are signed with, and the addresses to serve RPC requests on
+    let jwt_secret = load_jwt_secret(&config.data_dir).expect("Failed to load JWT secret");
+    // The CLI calls the RPC server with the admin token written to the data directory
+    write_admin_token(&config.data_dir, &jwt_secret).expect("Failed to write API token");
+    let rpc_endpoint =
+        multiaddr_to_socket_addr(&config.rpc_listen_address).expect("Invalid RPC listen address");
+    let ws_endpoint = multiaddr_to_socket_addr(&config.rpc_ws_listen_address)
+        .expect("Invalid RPC WebSocket listen address");
+
+    // Initialize wallet
+    let keystore = PersistentKeyStore::new(&format!("{}{}", config.data_dir, "/keystore"))
+        .expect("Failed to load keystore");
+    let wallet = Arc::new(RwLock::new(Wallet::new(keystore)));
+
     // Initialize database
     let mut db = RocksDb::new(config.data_dir + "/db");
     db.open().unwrap();
@@ -55,39 +82,81 @@ fn main() {
     let (genesis, network_name) =
         initialize_genesis(&config.genesis_file, &mut chain_store).unwrap();
 
+    // Start syncing from a trusted checkpoint if given
+    initialize_checkpoint(&config.checkpoint, &mut chain_store).unwrap();
+
     // Libp2p service setup
-    let p2p_service = Libp2pService::new(config.network, net_keypair, &network_name);
+    let p2p_service = Libp2pService::new(
+        config.network,
+        Arc::clone(&db),
+        net_keypair,
+        &network_name,
+        genesis.blocks()[0].cid().clone(),
+    );
     let network_rx = p2p_service.network_receiver();
     let network_send = p2p_service.network_sender();
+    let network_rpc = network_send.clone();
+
+    let state_manager = Arc::new(StateManager::new(Arc::clone(&db)));
+
+    // Initialize message pool and keep it updated with head changes of the chain store
+    let mpool = Arc::new(
+        MessagePool::new(
+            MpoolProvider::new(Arc::clone(&state_manager)),
+            network_name,
+            network_send.clone(),
+            config.mpool,
+        )
+        .unwrap(),
+    );
+    let head_changes = chain_store.subscribe();
+    let rpc_head_changes = chain_store.subscribe();
+    let mpool_head = Arc::clone(&mpool);
+    let mpool_rpc = Arc::clone(&mpool);
+    let mpool_thread = task::spawn(async move {
+        mpool_head.process_head_changes(head_changes).await;
+    });
 
     // Get Drand Coefficients
     let coeff = config.drand_dist_public;
+    // TODO: Interval is supposed to be consistent with fils epoch interval length, but not yet defined
+    let beacon = task::block_on(DrandBeacon::new(coeff, genesis.blocks()[0].timestamp(), 1))
+        .expect("Failed to initialize drand beacon");
+
+    // Initialize ChainSyncer, sharing its sync state and bad blocks with the RPC server
+    let chain_syncer = ChainSyncer::new(
+        chain_store,
+        mpool,
+        Arc::new(beacon),
+        network_send,
+        network_rx,
+        genesis,
+        config.sync,
+    )
+    .unwrap();
+    let sync_state = chain_syncer.sync_status();
+    let bad_blocks = chain_syncer.bad_blocks();
 
     // Start services
     let p2p_thread = task::spawn(async {
         p2p_service.run().await;
     });
     let sync_thread = task::spawn(async {
-        // TODO: Interval is supposed to be consistent with fils epoch interval length, but not yet defined
-        let beacon = DrandBeacon::new(coeff, genesis.blocks()[0].timestamp(), 1)
-            .await
-            .unwrap();
-
-        // Initialize ChainSyncer
-        let chain_syncer = ChainSyncer::new(
-            chain_store,
-            Arc::new(beacon),
-            network_send,
-            network_rx,
-            genesis,
-        )
-        .unwrap();
         chain_syncer.start().await.unwrap();
     });
 
-    let db_rpc = Arc::clone(&db);
+    let rpc_state = State {
+        store: Arc::clone(&db),
+        state_manager,
+        mpool: mpool_rpc,
+        wallet,
+        network_send: network_rpc,
+        sync_state,
+        bad_blocks,
+        head_changes: HeadChanges::new(rpc_head_changes),
+    };
     let rpc_thread = task::spawn(async {
-        start_rpc(db_rpc).await;
+        start_rpc(rpc_state, jwt_secret, rpc_endpoint, ws_endpoint).await;
     });
 
     // Block until ctrl-c is hit
@@ -97,6 +166,7 @@ fn main() {
     drop(rpc_thread);
     drop(p2p_thread);
     drop(sync_thread);
+    drop(mpool_thread);
 
     info!("Forest finish shutdown");
 }
@@ -7,10 +7,10 @@ mod util;
 use blockstore::BlockStore;
 use cid::Cid;
 use error::*;
-use forest_encoding::from_slice;
+use forest_encoding::{from_slice, to_vec};
 use serde::{Deserialize, Serialize};
-use std::io::{BufReader, Read};
-use util::{ld_read, read_node};
+use std::io::{BufRead, BufReader, Read, Write};
+use util::{ld_read, ld_write, read_node};
 
 /// CAR file header
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -49,6 +49,16 @@ where
         Ok(CarReader { buf_reader, header })
     }
 
+    /// Returns true if there are blocks left to read. The buffer is refilled, so a block ending
+    /// at the end of the buffer is not mistaken for the end of the file.
+    pub fn has_next_block(&mut self) -> Result<bool, Error> {
+        let buf = self
+            .buf_reader
+            .fill_buf()
+            .map_err(|e| Error::Other(e.to_string()))?;
+        Ok(!buf.is_empty())
+    }
+
     /// Returns the next IPLD Block in the buffer
     pub fn next_block(&mut self) -> Result<Block, Error> {
         // Read node -> cid, bytes
@@ -57,6 +67,42 @@ where
     }
 }
 
+/// Writes CAR files, with the header written on creation followed by the blocks
+pub struct CarWriter<W> {
+    writer: W,
+}
+
+impl<W> CarWriter<W>
+where
+    W: Write,
+{
+    /// Creates a new CarWriter and writes the CarHeader
+    pub fn new(mut writer: W, header: &CarHeader) -> Result<Self, Error> {
+        if header.roots.is_empty() {
+            return Err(Error::InvalidFile("CAR file must have roots".to_owned()));
+        }
+        let buf = to_vec(header).map_err(|e| Error::ParsingError(e.to_string()))?;
+        ld_write(&mut writer, &buf)?;
+        Ok(CarWriter { writer })
+    }
+
+    /// Writes an IPLD block, as its Cid followed by its data
+    pub fn write_block(&mut self, cid: &Cid, data: &[u8]) -> Result<(), Error> {
+        ld_write(
+            &mut self.writer,
+            &[cid.to_bytes().as_slice(), data].concat(),
+        )
+    }
+
+    /// Flushes the written blocks and returns the underlying writer
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.writer
+            .flush()
+            .map_err(|e| Error::Other(e.to_string()))?;
+        Ok(self.writer)
+    }
+}
+
 /// IPLD Block
 #[derive(Clone, Debug)]
 pub struct Block {
@@ -64,6 +110,18 @@ pub struct Block {
     data: Vec<u8>,
 }
 
+impl Block {
+    /// Returns the Cid of the block
+    pub fn cid(&self) -> &Cid {
+        &self.cid
+    }
+
+    /// Returns the data of the block
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
 /// Loads a CAR buffer into a BlockStore
 pub fn load_car<R: Read, B: BlockStore>(
     s: &B,
@@ -71,7 +129,7 @@ pub fn load_car<R: Read, B: BlockStore>(
 ) -> Result<Vec<Cid>, Error> {
     let mut car_reader = CarReader::new(buf_reader)?;
 
-    while !car_reader.buf_reader.buffer().is_empty() {
+    while car_reader.has_next_block()? {
         let block = car_reader.next_block()?;
         s.write(block.cid.to_bytes(), block.data)
             .map_err(|e| Error::Other(e.to_string()))?;
@@ -3,7 +3,7 @@
 
 use super::error::Error;
 use cid::Cid;
-use std::io::Read;
+use std::io::{Read, Write};
 
 pub(crate) fn ld_read<R: Read>(mut buf_reader: &mut R) -> Result<Vec<u8>, Error> {
     let l =
@@ -16,6 +16,15 @@ pub(crate) fn ld_read<R: Read>(mut buf_reader: &mut R) -> Result<Vec<u8>, Error>
     Ok(buf)
 }
 
+pub(crate) fn ld_write<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), Error> {
+    let mut buf = unsigned_varint::encode::u64_buffer();
+    let len = unsigned_varint::encode::u64(bytes.len() as u64, &mut buf);
+    writer
+        .write_all(len)
+        .and_then(|_| writer.write_all(bytes))
+        .map_err(|e| Error::Other(e.to_string()))
+}
+
 pub(crate) fn read_node<R: Read>(buf_reader: &mut R) -> Result<(Cid, Vec<u8>), Error> {
     let buf = ld_read(buf_reader)?;
     let (c, n) = read_cid(&buf)?;
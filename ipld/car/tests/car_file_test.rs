@@ -1,10 +1,10 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use db::MemoryDB;
+use db::{MemoryDB, Store};
 use forest_car::*;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
 
 #[test]
 fn load_into_blockstore() {
@@ -14,3 +14,53 @@ fn load_into_blockstore() {
 
     let _ = load_car(&mut bs, buf_reader).unwrap();
 }
+
+#[test]
+fn write_and_read_back() {
+    let file = File::open("tests/test.car").unwrap();
+    let mut reader = CarReader::new(BufReader::new(file)).unwrap();
+    let header = CarHeader::new(reader.header.roots.clone(), 1);
+
+    let mut writer = CarWriter::new(Vec::new(), &header).unwrap();
+    let mut blocks = Vec::new();
+    while reader.has_next_block().unwrap() {
+        let block = reader.next_block().unwrap();
+        writer.write_block(block.cid(), block.data()).unwrap();
+        blocks.push(block);
+    }
+    let buf = writer.finish().unwrap();
+
+    let mut reader = CarReader::new(BufReader::new(Cursor::new(buf))).unwrap();
+    assert_eq!(reader.header.roots, header.roots);
+    for block in blocks {
+        let read = reader.next_block().unwrap();
+        assert_eq!(read.cid(), block.cid());
+        assert_eq!(read.data(), block.data());
+    }
+    assert!(!reader.has_next_block().unwrap());
+}
+
+#[test]
+fn read_blocks_across_buffer_boundaries() {
+    let file = File::open("tests/test.car").unwrap();
+    let mut bs = MemoryDB::default();
+    let roots = load_car(&mut bs, BufReader::new(file)).unwrap();
+
+    // Blocks ending at the end of the buffer don't end the file early
+    let bytes = std::fs::read("tests/test.car").unwrap();
+    for capacity in 1..=64 {
+        let mut reader =
+            CarReader::new(BufReader::with_capacity(capacity, bytes.as_slice())).unwrap();
+        let mut count = 0;
+        while reader.has_next_block().unwrap() {
+            let block = reader.next_block().unwrap();
+            assert_eq!(
+                bs.read(block.cid().to_bytes()).unwrap(),
+                Some(block.data().to_vec())
+            );
+            count += 1;
+        }
+        assert!(count > 1);
+        assert_eq!(reader.header.roots, roots);
+    }
+}
@@ -1,9 +1,17 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use self::json::{KeyInfoJson, KeyInfoJsonRef};
 use super::errors::Error;
 use crypto::SignatureType;
+use log::warn;
 use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind};
+use std::path::PathBuf;
+
+/// Name of the file keys of a PersistentKeyStore are stored in
+const KEYSTORE_NAME: &str = "keystore.json";
 
 /// KeyInfo struct, this contains the type of key (stored as a string) and the private key.
 /// note how the private key is stored as a byte vector
@@ -81,3 +89,188 @@ impl KeyStore for MemKeyStore {
         self.key_info.remove(&key)
     }
 }
+
+/// KeyStore persisted to a JSON file in the given directory. Every change to the keys is
+/// written to the file.
+#[derive(Clone, PartialEq, Debug, Eq)]
+pub struct PersistentKeyStore {
+    pub key_info: HashMap<String, KeyInfo>,
+    file_path: PathBuf,
+}
+
+impl PersistentKeyStore {
+    /// Loads the KeyStore from the directory, creating an empty one if no keys were stored yet
+    pub fn new(location: &str) -> Result<Self, Error> {
+        let dir = PathBuf::from(location);
+        fs::create_dir_all(&dir).map_err(|e| Error::Other(e.to_string()))?;
+        let file_path = dir.join(KEYSTORE_NAME);
+
+        let key_info = match File::open(&file_path) {
+            Ok(file) => {
+                let reader = BufReader::new(file);
+                let data: HashMap<String, KeyInfoJson> = serde_json::from_reader(reader)
+                    .map_err(|e| Error::Other(format!("failed to read keystore: {}", e)))?;
+                data.into_iter().map(|(k, v)| (k, v.0)).collect()
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(Error::Other(e.to_string())),
+        };
+
+        Ok(Self {
+            key_info,
+            file_path,
+        })
+    }
+
+    /// Writes all keys to the keystore file, which is only readable by the owner
+    pub fn flush(&self) -> Result<(), Error> {
+        let data: HashMap<&String, KeyInfoJsonRef> = self
+            .key_info
+            .iter()
+            .map(|(k, v)| (k, KeyInfoJsonRef(v)))
+            .collect();
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options
+            .open(&self.file_path)
+            .map_err(|e| Error::Other(e.to_string()))?;
+        serde_json::to_writer(BufWriter::new(file), &data)
+            .map_err(|e| Error::Other(format!("failed to write keystore: {}", e)))
+    }
+}
+
+impl KeyStore for PersistentKeyStore {
+    fn list(&self) -> Vec<String> {
+        self.key_info.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    fn get(&self, k: &str) -> Result<KeyInfo, Error> {
+        self.key_info.get(k).cloned().ok_or(Error::KeyInfo)
+    }
+
+    fn put(&mut self, key: String, key_info: KeyInfo) -> Result<(), Error> {
+        if self.key_info.contains_key(&key) {
+            return Err(Error::KeyExists);
+        }
+        self.key_info.insert(key.clone(), key_info);
+        if let Err(e) = self.flush() {
+            self.key_info.remove(&key);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, key: String) -> Option<KeyInfo> {
+        let key_info = self.key_info.remove(&key)?;
+        // Removal is persisted with the next successful write if flushing fails
+        if let Err(e) = self.flush() {
+            warn!("Failed to persist removal of key {}: {}", key, e);
+        }
+        Some(key_info)
+    }
+}
+
+pub mod json {
+    use super::*;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Wrapper for serializing and deserializing a KeyInfo from JSON.
+    #[derive(Deserialize, Serialize)]
+    #[serde(transparent)]
+    pub struct KeyInfoJson(#[serde(with = "self")] pub KeyInfo);
+
+    /// Wrapper for serializing a KeyInfo reference to JSON.
+    #[derive(Serialize)]
+    #[serde(transparent)]
+    pub struct KeyInfoJsonRef<'a>(#[serde(with = "self")] pub &'a KeyInfo);
+
+    impl From<KeyInfoJson> for KeyInfo {
+        fn from(wrapper: KeyInfoJson) -> Self {
+            wrapper.0
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct JsonHelper {
+        #[serde(rename = "Type")]
+        sig_type: String,
+        private_key: String,
+    }
+
+    pub fn serialize<S>(k: &KeyInfo, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let sig_type = match k.key_type {
+            SignatureType::BLS => "bls",
+            SignatureType::Secp256k1 => "secp256k1",
+        };
+        JsonHelper {
+            sig_type: sig_type.to_owned(),
+            private_key: base64::encode(&k.private_key),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<KeyInfo, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let JsonHelper {
+            sig_type,
+            private_key,
+        } = Deserialize::deserialize(deserializer)?;
+        let key_type = match sig_type.as_str() {
+            "bls" => SignatureType::BLS,
+            "secp256k1" => SignatureType::Secp256k1,
+            other => {
+                return Err(de::Error::custom(format!("unknown key type: {}", other)));
+            }
+        };
+        Ok(KeyInfo {
+            key_type,
+            private_key: base64::decode(private_key).map_err(de::Error::custom)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate;
+
+    #[test]
+    fn persistent_keystore() {
+        let location = std::env::temp_dir()
+            .join("forest_persistent_keystore_test")
+            .to_string_lossy()
+            .into_owned();
+        let _ = fs::remove_dir_all(&location);
+
+        let key_info = KeyInfo::new(SignatureType::BLS, generate(SignatureType::BLS).unwrap());
+        let mut ks = PersistentKeyStore::new(&location).unwrap();
+        assert!(ks.list().is_empty());
+        ks.put("default".to_owned(), key_info.clone()).unwrap();
+        assert_eq!(
+            ks.put("default".to_owned(), key_info.clone()),
+            Err(Error::KeyExists)
+        );
+
+        // Keys are loaded again from the file
+        let mut ks = PersistentKeyStore::new(&location).unwrap();
+        assert_eq!(ks.get("default"), Ok(key_info.clone()));
+        assert_eq!(ks.remove("default".to_owned()), Some(key_info));
+
+        let ks = PersistentKeyStore::new(&location).unwrap();
+        assert!(ks.list().is_empty());
+
+        fs::remove_dir_all(&location).unwrap();
+    }
+}
@@ -8,9 +8,9 @@ use libp2p::core::PeerId;
 use libp2p::gossipsub::{Gossipsub, GossipsubConfig, GossipsubEvent, Topic, TopicHash};
 use libp2p::identify::{Identify, IdentifyEvent};
 use libp2p::kad::record::store::MemoryStore;
-use libp2p::kad::{Kademlia, KademliaConfig, KademliaEvent, QueryId};
+use libp2p::kad::{GetClosestPeersError, Kademlia, KademliaConfig, KademliaEvent, QueryId};
 use libp2p::mdns::{Mdns, MdnsEvent};
-use libp2p::multiaddr::Protocol;
+use libp2p::multiaddr::{Multiaddr, Protocol};
 use libp2p::ping::{
     handler::{PingFailure, PingSuccess},
     Ping, PingEvent,
@@ -18,7 +18,7 @@ use libp2p::ping::{
 use libp2p::swarm::{NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters};
 use libp2p::NetworkBehaviour;
 use log::{debug, trace, warn};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::{task::Context, task::Poll};
 
 #[derive(NetworkBehaviour)]
@@ -35,6 +35,9 @@ pub struct ForestBehaviour {
     events: Vec<ForestBehaviourEvent>,
     #[behaviour(ignore)]
     peers: HashSet<PeerId>,
+    /// Connected peers with the listen addresses they identified with
+    #[behaviour(ignore)]
+    connected_peers: HashMap<PeerId, Vec<Multiaddr>>,
 }
 
 #[derive(Debug)]
@@ -47,6 +50,11 @@ pub enum ForestBehaviourEvent {
         message: Vec<u8>,
     },
     RPC(PeerId, RPCEvent),
+    /// Result of a Kademlia lookup of a peer, with its known addresses
+    PeerFound {
+        peer_id: PeerId,
+        addrs: Vec<Multiaddr>,
+    },
 }
 
 impl NetworkBehaviourEventProcess<MdnsEvent> for ForestBehaviour {
@@ -75,6 +83,17 @@ impl NetworkBehaviourEventProcess<KademliaEvent> for ForestBehaviour {
             KademliaEvent::Discovered { peer_id, .. } => {
                 self.add_peer(peer_id);
             }
+            KademliaEvent::GetClosestPeersResult(res) => {
+                let key = match res {
+                    Ok(ok) => ok.key,
+                    Err(GetClosestPeersError::Timeout { key, .. }) => key,
+                };
+                if let Ok(peer_id) = PeerId::from_bytes(key) {
+                    let addrs = self.peer_addresses(&peer_id);
+                    self.events
+                        .push(ForestBehaviourEvent::PeerFound { peer_id, addrs });
+                }
+            }
             event => {
                 trace!("kad: {:?}", event);
             }
@@ -131,6 +150,9 @@ impl NetworkBehaviourEventProcess<IdentifyEvent> for ForestBehaviour {
                 trace!("listening_ addresses {:?}", info.listen_addrs);
                 trace!("observed_address {}", observed_addr);
                 trace!("protocols {:?}", info.protocols);
+                if let Some(addrs) = self.connected_peers.get_mut(&peer_id) {
+                    *addrs = info.listen_addrs;
+                }
             }
             IdentifyEvent::Sent { .. } => (),
             IdentifyEvent::Error { .. } => (),
@@ -141,9 +163,11 @@ impl NetworkBehaviourEventProcess<RPCMessage> for ForestBehaviour {
     fn inject_event(&mut self, event: RPCMessage) {
         match event {
             RPCMessage::PeerDialed(peer_id) => {
+                self.connected_peers.insert(peer_id.clone(), Vec::new());
                 self.events.push(ForestBehaviourEvent::PeerDialed(peer_id));
             }
             RPCMessage::PeerDisconnected(peer_id) => {
+                self.connected_peers.remove(&peer_id);
                 self.events
                     .push(ForestBehaviourEvent::PeerDisconnected(peer_id));
             }
@@ -216,6 +240,7 @@ impl ForestBehaviour {
             rpc: RPC::default(),
             events: vec![],
             peers: Default::default(),
+            connected_peers: Default::default(),
         }
     }
 
@@ -253,4 +278,30 @@ impl ForestBehaviour {
     pub fn peers(&self) -> &HashSet<PeerId> {
         &self.peers
     }
+
+    /// Returns the connected peers with the listen addresses they identified with
+    pub fn connected_peers(&self) -> &HashMap<PeerId, Vec<Multiaddr>> {
+        &self.connected_peers
+    }
+
+    /// Returns the addresses of the peer, either identified by the peer or from the routing table
+    pub fn peer_addresses(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        let mut addrs = self
+            .connected_peers
+            .get(peer_id)
+            .cloned()
+            .unwrap_or_default();
+        for addr in libp2p::swarm::NetworkBehaviour::addresses_of_peer(&mut self.kademlia, peer_id)
+        {
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+        addrs
+    }
+
+    /// Looks up the peer in the Kademlia DHT, the result is emitted as a `PeerFound` event.
+    pub fn find_peer(&mut self, peer_id: PeerId) {
+        self.kademlia.get_closest_peers(peer_id);
+    }
 }
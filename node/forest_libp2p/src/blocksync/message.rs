@@ -11,6 +11,15 @@ use std::convert::TryFrom;
 pub const BLOCKS: u64 = 1;
 pub const MESSAGES: u64 = 2;
 
+/// Blocksync response status codes
+pub const OK: u64 = 0;
+/// Only a prefix of the requested chain could be served
+pub const PARTIAL: u64 = 101;
+pub const NOT_FOUND: u64 = 201;
+pub const GO_AWAY: u64 = 202;
+pub const INTERNAL_ERROR: u64 = 203;
+pub const BAD_REQUEST: u64 = 204;
+
 /// The payload that gets sent to another node to request for blocks and messages. It get DagCBOR serialized before sending over the wire.
 #[derive(Clone, Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
 pub struct BlockSyncRequest {
@@ -35,8 +44,10 @@ pub struct BlockSyncResponse {
 }
 
 impl BlockSyncResponse {
+    /// Converts the response into full tipsets. Partial responses return the tipsets that
+    /// were served.
     pub fn into_result(self) -> Result<Vec<FullTipset>, String> {
-        if self.status != 0 {
+        if self.status != OK && self.status != PARTIAL {
             // TODO implement a better error type than string if needed to be handled differently
             return Err(format!("Status {}: {}", self.status, self.message));
         }
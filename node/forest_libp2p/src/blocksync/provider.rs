@@ -0,0 +1,149 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{
+    BlockSyncRequest, BlockSyncResponse, TipsetBundle, BAD_REQUEST, BLOCKS, INTERNAL_ERROR,
+    MESSAGES, NOT_FOUND, OK, PARTIAL,
+};
+use chain::{block_messages_from_cids, read_msg_cids, tipset_from_keys, Error as ChainError};
+use forest_blocks::{Tipset, TipsetKeys};
+use forest_cid::Cid;
+use ipld_blockstore::BlockStore;
+use std::collections::HashMap;
+
+/// Maximum amount of tipsets served for a single request
+pub const MAX_REQUEST_LENGTH: u64 = 800;
+
+/// Builds the response to a blocksync request from the local store. Walks back from the
+/// requested start tipset for the requested length, including the headers and messages
+/// based on the request options.
+pub fn make_blocksync_response<DB>(db: &DB, request: &BlockSyncRequest) -> BlockSyncResponse
+where
+    DB: BlockStore,
+{
+    if request.start.is_empty() {
+        return error_response(BAD_REQUEST, "no cids given in blocksync request");
+    }
+    if request.request_len == 0 || request.request_len > MAX_REQUEST_LENGTH {
+        return error_response(
+            BAD_REQUEST,
+            &format!(
+                "invalid request length {}, must be between 1 and {}",
+                request.request_len, MAX_REQUEST_LENGTH
+            ),
+        );
+    }
+    if request.options & (BLOCKS | MESSAGES) == 0 {
+        return error_response(BAD_REQUEST, "no options set in blocksync request");
+    }
+
+    let mut curr = match tipset_from_keys(db, &TipsetKeys::new(request.start.clone())) {
+        Ok(ts) => ts,
+        Err(e) => return error_response(NOT_FOUND, &e.to_string()),
+    };
+
+    let mut chain = Vec::new();
+    loop {
+        let mut bundle = TipsetBundle {
+            blocks: Vec::new(),
+            bls_msgs: Vec::new(),
+            bls_msg_includes: Vec::new(),
+            secp_msgs: Vec::new(),
+            secp_msg_includes: Vec::new(),
+        };
+        if request.options & BLOCKS != 0 {
+            bundle.blocks = curr.blocks().to_vec();
+        }
+        if request.options & MESSAGES != 0 {
+            if let Err(e) = gather_messages(db, &curr, &mut bundle) {
+                return partial_response(chain, INTERNAL_ERROR, &e.to_string());
+            }
+        } else {
+            // Include indexes are still expected for each block when messages are not requested
+            bundle.bls_msg_includes = vec![Vec::new(); curr.blocks().len()];
+            bundle.secp_msg_includes = vec![Vec::new(); curr.blocks().len()];
+        }
+        chain.push(bundle);
+
+        if chain.len() as u64 >= request.request_len || curr.epoch() == 0 {
+            break;
+        }
+        curr = match tipset_from_keys(db, curr.parents()) {
+            Ok(ts) => ts,
+            Err(e) => return partial_response(chain, NOT_FOUND, &e.to_string()),
+        };
+    }
+
+    BlockSyncResponse {
+        chain,
+        status: OK,
+        message: String::new(),
+    }
+}
+
+/// Loads the messages of all blocks in the tipset into the bundle. Messages are
+/// de-duplicated across blocks and referenced by index for each block.
+fn gather_messages<DB>(db: &DB, ts: &Tipset, bundle: &mut TipsetBundle) -> Result<(), ChainError>
+where
+    DB: BlockStore,
+{
+    let mut bls_indexes: HashMap<Cid, u64> = HashMap::new();
+    let mut secp_indexes: HashMap<Cid, u64> = HashMap::new();
+
+    for header in ts.blocks() {
+        let (bls_cids, secp_cids) = read_msg_cids(db, header.messages())?;
+        let (bls_msgs, secp_msgs) = block_messages_from_cids(db, &bls_cids, &secp_cids)?;
+
+        bundle.bls_msg_includes.push(include_messages(
+            bls_cids,
+            bls_msgs,
+            &mut bls_indexes,
+            &mut bundle.bls_msgs,
+        ));
+        bundle.secp_msg_includes.push(include_messages(
+            secp_cids,
+            secp_msgs,
+            &mut secp_indexes,
+            &mut bundle.secp_msgs,
+        ));
+    }
+    Ok(())
+}
+
+/// Adds messages that are not yet in the bundle and returns the indexes of all messages
+fn include_messages<T>(
+    cids: Vec<Cid>,
+    msgs: Vec<T>,
+    indexes: &mut HashMap<Cid, u64>,
+    bundle_msgs: &mut Vec<T>,
+) -> Vec<u64> {
+    cids.into_iter()
+        .zip(msgs.into_iter())
+        .map(|(cid, msg)| {
+            *indexes.entry(cid).or_insert_with(|| {
+                bundle_msgs.push(msg);
+                bundle_msgs.len() as u64 - 1
+            })
+        })
+        .collect()
+}
+
+/// Returns the tipsets gathered so far, or an error response if none were gathered
+fn partial_response(chain: Vec<TipsetBundle>, status: u64, message: &str) -> BlockSyncResponse {
+    if chain.is_empty() {
+        return error_response(status, message);
+    }
+    BlockSyncResponse {
+        chain,
+        status: PARTIAL,
+        message: message.to_owned(),
+    }
+}
+
+fn error_response(status: u64, message: &str) -> BlockSyncResponse {
+    BlockSyncResponse {
+        chain: Vec::new(),
+        status,
+        message: message.to_owned(),
+    }
+}
@@ -5,6 +5,7 @@ use clock::ChainEpoch;
 use forest_cid::Cid;
 use forest_encoding::tuple::*;
 use num_bigint::BigUint;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Hello message https://filecoin-project.github.io/specs/#hello-spec
 #[derive(Clone, Debug, PartialEq, Default, Serialize_tuple, Deserialize_tuple)]
@@ -25,6 +26,36 @@ pub struct HelloResponse {
     pub sent: i64,
 }
 
+impl HelloResponse {
+    /// Creates a response to a hello request which arrived at the given time
+    pub fn new(arrival: SystemTime) -> Self {
+        Self {
+            arrival: unix_nanos(arrival),
+            sent: unix_nanos(SystemTime::now()),
+        }
+    }
+
+    /// Returns the round trip latency of a hello request sent and answered at the given
+    /// times, excluding the time the remote peer took to respond. Returns `None` if the
+    /// timestamps of the response give a negative or overflowing latency.
+    pub fn latency(&self, sent: SystemTime, received: SystemTime) -> Option<Duration> {
+        let round_trip = unix_nanos(received).checked_sub(unix_nanos(sent))?;
+        let processing = self.sent.checked_sub(self.arrival)?;
+        let latency = round_trip.checked_sub(processing)?;
+        if latency < 0 {
+            return None;
+        }
+        Some(Duration::from_nanos(latency as u64))
+    }
+}
+
+/// Converts a system time into unix nanoseconds
+fn unix_nanos(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,4 +72,51 @@ mod tests {
         let msg: HelloMessage = from_slice(&bz).unwrap();
         assert_eq!(msg, orig_msg);
     }
+
+    #[test]
+    fn hello_response_latency() {
+        let sent = UNIX_EPOCH + Duration::from_millis(1000);
+        let received = UNIX_EPOCH + Duration::from_millis(1300);
+        let res = HelloResponse {
+            arrival: unix_nanos(UNIX_EPOCH + Duration::from_millis(5000)),
+            sent: unix_nanos(UNIX_EPOCH + Duration::from_millis(5100)),
+        };
+        assert_eq!(
+            res.latency(sent, received),
+            Some(Duration::from_millis(200))
+        );
+
+        // Clock differences resulting in a negative latency are invalid
+        let res = HelloResponse {
+            arrival: 0,
+            sent: unix_nanos(received),
+        };
+        assert_eq!(res.latency(sent, received), None);
+    }
+
+    #[test]
+    fn hello_response_latency_overflow() {
+        let sent = UNIX_EPOCH + Duration::from_millis(1000);
+        let received = UNIX_EPOCH + Duration::from_millis(1300);
+        let res = HelloResponse {
+            arrival: i64::MIN,
+            sent: i64::MAX,
+        };
+        assert_eq!(res.latency(sent, received), None);
+
+        let res = HelloResponse {
+            arrival: i64::MAX,
+            sent: i64::MIN,
+        };
+        assert_eq!(res.latency(sent, received), None);
+
+        let res = HelloResponse {
+            arrival: i64::MIN,
+            sent: i64::MIN,
+        };
+        assert_eq!(
+            res.latency(sent, received),
+            Some(Duration::from_millis(300))
+        );
+    }
 }
@@ -1,36 +1,63 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use super::blocksync::BlockSyncResponse;
-use super::hello::HelloMessage;
+use super::blocksync::{make_blocksync_response, BlockSyncResponse, GO_AWAY};
+use super::hello::{HelloMessage, HelloResponse};
 use super::rpc::{RPCEvent, RPCRequest, RPCResponse};
 use super::{ForestBehaviour, ForestBehaviourEvent, Libp2pConfig};
 use async_std::stream;
 use async_std::sync::{channel, Receiver, Sender};
+use async_std::task;
+use forest_blocks::GossipBlock;
+use forest_cid::Cid;
+use forest_encoding::from_slice;
+use forest_message::SignedMessage;
 use futures::select;
 use futures_util::stream::StreamExt;
+use ipld_blockstore::BlockStore;
 use libp2p::{
     core,
     core::muxing::StreamMuxerBox,
     core::transport::boxed::Boxed,
     gossipsub::{Topic, TopicHash},
     identity::{ed25519, Keypair},
-    mplex, secio, yamux, PeerId, Swarm, Transport,
+    mplex, secio, yamux, Multiaddr, PeerId, Swarm, Transport,
 };
 use log::{debug, info, trace, warn};
+use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use utils::read_file_to_vec;
 
-const PUBSUB_TOPICS: [&str; 2] = ["/fil/blocks", "/fil/msgs"];
+/// Gossipsub topic prefix for blocks, suffixed with the network name
+pub const PUBSUB_BLOCK_STR: &str = "/fil/blocks";
+/// Gossipsub topic prefix for messages, suffixed with the network name
+pub const PUBSUB_MSG_STR: &str = "/fil/msgs";
+
+const PUBSUB_TOPICS: [&str; 2] = [PUBSUB_BLOCK_STR, PUBSUB_MSG_STR];
+
+/// Duration peers on a chain with a different genesis are banned for
+const GENESIS_MISMATCH_BAN: Duration = Duration::from_secs(10 * 60);
+
+/// Maximum amount of blocksync responses built at the same time
+const MAX_BLOCKSYNC_RESPONSES: usize = 16;
+/// Maximum amount of blocksync responses built at the same time for a single peer
+const MAX_PEER_BLOCKSYNC_RESPONSES: usize = 2;
+
+/// Decoded gossipsub messages received on the subscribed topics
+#[derive(Clone, Debug)]
+pub enum PubsubMessage {
+    Block(GossipBlock),
+    Message(SignedMessage),
+}
 
 /// Events emitted by this Service
 #[derive(Clone, Debug)]
 pub enum NetworkEvent {
     PubsubMessage {
         source: PeerId,
-        topics: Vec<TopicHash>,
-        message: Vec<u8>,
+        message: PubsubMessage,
     },
     RPCRequest {
         req_id: usize,
@@ -44,9 +71,106 @@ pub enum NetworkEvent {
         source: PeerId,
         message: HelloMessage,
     },
+    /// Response to a hello request sent to a peer, with the measured latency to the peer
+    HelloResponse {
+        source: PeerId,
+        latency: Duration,
+    },
     PeerDialed {
         peer_id: PeerId,
     },
+    PeerDisconnected {
+        peer_id: PeerId,
+    },
+}
+
+/// Requests to inspect and manage the swarm, answered on the channel of the request
+#[derive(Clone, Debug)]
+pub enum NetRPCRequest {
+    /// Returns the connected peers with their addresses
+    Peers(Sender<Vec<(PeerId, Vec<Multiaddr>)>>),
+    /// Returns the local peer ID and the addresses listened on
+    AddrsListen(Sender<(PeerId, Vec<Multiaddr>)>),
+    /// Dials the peer on the given addresses
+    Connect {
+        addrs: Vec<Multiaddr>,
+        response: Sender<Result<(), String>>,
+    },
+    /// Closes the connections to the peer
+    Disconnect {
+        peer_id: PeerId,
+        response: Sender<()>,
+    },
+    /// Looks up the addresses of the peer in the Kademlia DHT
+    FindPeer {
+        peer_id: PeerId,
+        response: Sender<Vec<Multiaddr>>,
+    },
+}
+
+/// Peers banned from the swarm until a given time, after which they are unbanned
+#[derive(Debug, Default)]
+struct TempBans {
+    bans: HashMap<PeerId, Instant>,
+}
+
+impl TempBans {
+    /// Records the ban of the peer until the given time
+    fn ban(&mut self, peer_id: PeerId, until: Instant) {
+        self.bans.insert(peer_id, until);
+    }
+
+    /// Returns true if the peer has a ban which has not been lifted yet
+    fn contains(&self, peer_id: &PeerId) -> bool {
+        self.bans.contains_key(peer_id)
+    }
+
+    /// Removes and returns the peers whose ban has expired at the given time
+    fn take_expired(&mut self, now: Instant) -> Vec<PeerId> {
+        let expired: Vec<PeerId> = self
+            .bans
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        for peer_id in &expired {
+            self.bans.remove(peer_id);
+        }
+        expired
+    }
+}
+
+/// Blocksync responses being built, limited in total and per peer so requests can't occupy
+/// all threads reading from the store
+#[derive(Debug, Default)]
+struct InflightResponses {
+    total: usize,
+    peers: HashMap<PeerId, usize>,
+}
+
+impl InflightResponses {
+    /// Records a response being built for the peer. Returns false if the limits are reached,
+    /// in which case the request should be rejected.
+    fn start(&mut self, peer_id: &PeerId) -> bool {
+        let peer_count = self.peers.get(peer_id).copied().unwrap_or_default();
+        if self.total >= MAX_BLOCKSYNC_RESPONSES || peer_count >= MAX_PEER_BLOCKSYNC_RESPONSES {
+            return false;
+        }
+        self.total += 1;
+        self.peers.insert(peer_id.clone(), peer_count + 1);
+        true
+    }
+
+    /// Records a response for the peer being done
+    fn finish(&mut self, peer_id: &PeerId) {
+        if let Some(count) = self.peers.get_mut(peer_id) {
+            *count -= 1;
+            if *count == 0 {
+                self.peers.remove(peer_id);
+            }
+            self.total -= 1;
+        }
+    }
 }
 
 /// Events into this Service
@@ -54,20 +178,35 @@ pub enum NetworkEvent {
 pub enum NetworkMessage {
     PubsubMessage { topic: Topic, message: Vec<u8> },
     RPC { peer_id: PeerId, event: RPCEvent },
+    NetRPC(NetRPCRequest),
 }
 /// The Libp2pService listens to events from the Libp2p swarm.
-pub struct Libp2pService {
+pub struct Libp2pService<DB> {
     pub swarm: Swarm<ForestBehaviour>,
+    /// Store used to serve incoming blocksync requests
+    db: Arc<DB>,
 
     network_receiver_in: Receiver<NetworkMessage>,
     network_sender_in: Sender<NetworkMessage>,
     network_receiver_out: Receiver<NetworkEvent>,
     network_sender_out: Sender<NetworkEvent>,
+    network_name: String,
+    /// Genesis of the local chain, peers on a different chain are disconnected
+    genesis_cid: Cid,
 }
 
-impl Libp2pService {
+impl<DB> Libp2pService<DB>
+where
+    DB: BlockStore + Send + Sync + 'static,
+{
     /// Constructs a Libp2pService
-    pub fn new(config: Libp2pConfig, net_keypair: Keypair, network_name: &str) -> Self {
+    pub fn new(
+        config: Libp2pConfig,
+        db: Arc<DB>,
+        net_keypair: Keypair,
+        network_name: &str,
+        genesis_cid: Cid,
+    ) -> Self {
         let peer_id = PeerId::from(net_keypair.public());
 
         let transport = build_transport(net_keypair.clone());
@@ -93,10 +232,13 @@ impl Libp2pService {
         let (network_sender_out, network_receiver_out) = channel(20);
         Libp2pService {
             swarm,
+            db,
             network_receiver_in,
             network_sender_in,
             network_receiver_out,
             network_sender_out,
+            network_name: network_name.to_owned(),
+            genesis_cid,
         }
     }
 
@@ -105,6 +247,19 @@ impl Libp2pService {
         let mut swarm_stream = self.swarm.fuse();
         let mut network_stream = self.network_receiver_in.fuse();
         let mut interval = stream::interval(Duration::from_secs(10)).fuse();
+        let block_topic =
+            TopicHash::from_raw(format!("{}/{}", PUBSUB_BLOCK_STR, self.network_name));
+        let msg_topic = TopicHash::from_raw(format!("{}/{}", PUBSUB_MSG_STR, self.network_name));
+        // Send times of outgoing hello requests, used to measure peer latency
+        let mut hello_requests: HashMap<PeerId, SystemTime> = HashMap::new();
+        // Pending Kademlia lookups of peers requested through the NetRPC messages
+        let mut find_peer_requests: HashMap<PeerId, Vec<Sender<Vec<Multiaddr>>>> = HashMap::new();
+        // Blocksync responses are built off the swarm loop, and sent once they are ready
+        let (blocksync_sender, blocksync_receiver) =
+            channel::<(PeerId, usize, BlockSyncResponse)>(20);
+        let mut blocksync_stream = blocksync_receiver.fuse();
+        let mut blocksync_inflight = InflightResponses::default();
+        let mut temp_bans = TempBans::default();
 
         loop {
             select! {
@@ -118,6 +273,10 @@ impl Libp2pService {
                         }
                         ForestBehaviourEvent::PeerDisconnected(peer_id) => {
                             debug!("Peer disconnected, {:?}", peer_id);
+                            hello_requests.remove(&peer_id);
+                            self.network_sender_out.send(NetworkEvent::PeerDisconnected{
+                                peer_id
+                            }).await;
                         }
                         ForestBehaviourEvent::GossipMessage {
                             source,
@@ -125,15 +284,47 @@ impl Libp2pService {
                             message,
                         } => {
                             debug!("Got a Gossip Message from {:?}", source);
-                            self.network_sender_out.send(NetworkEvent::PubsubMessage {
-                                source,
-                                topics,
-                                message
-                            }).await;
+                            if topics.contains(&block_topic) {
+                                match from_slice::<GossipBlock>(&message) {
+                                    Ok(b) => {
+                                        self.network_sender_out.send(NetworkEvent::PubsubMessage {
+                                            source,
+                                            message: PubsubMessage::Block(b),
+                                        }).await;
+                                    }
+                                    Err(e) => warn!("Failed to decode gossiped block from {}: {}", source, e),
+                                }
+                            } else if topics.contains(&msg_topic) {
+                                match from_slice::<SignedMessage>(&message) {
+                                    Ok(m) => {
+                                        self.network_sender_out.send(NetworkEvent::PubsubMessage {
+                                            source,
+                                            message: PubsubMessage::Message(m),
+                                        }).await;
+                                    }
+                                    Err(e) => warn!("Failed to decode gossiped message from {}: {}", source, e),
+                                }
+                            } else {
+                                warn!("Received gossip message on unhandled topics: {:?}", topics);
+                            }
                         }
                         ForestBehaviourEvent::RPC(peer_id, event) => {
                             debug!("RPC event {:?}", event);
                             match event {
+                                RPCEvent::Response(_, RPCResponse::Hello(res)) => {
+                                    match hello_requests.remove(&peer_id) {
+                                        Some(sent) => match res.latency(sent, SystemTime::now()) {
+                                            Some(latency) => {
+                                                self.network_sender_out.send(NetworkEvent::HelloResponse {
+                                                    source: peer_id,
+                                                    latency,
+                                                }).await;
+                                            }
+                                            None => debug!("Invalid hello response timestamps from {}", peer_id),
+                                        },
+                                        None => debug!("Unrequested hello response from {}", peer_id),
+                                    }
+                                }
                                 RPCEvent::Response(req_id, res) => {
                                     self.network_sender_out.send(NetworkEvent::RPCResponse {
                                         req_id,
@@ -141,20 +332,45 @@ impl Libp2pService {
                                     }).await;
                                 }
                                 RPCEvent::Request(req_id, RPCRequest::BlockSync(r)) => {
-                                    // TODO implement handling incoming blocksync requests
-                                    swarm_stream.get_mut().send_rpc(peer_id, RPCEvent::Response(1, RPCResponse::BlockSync(BlockSyncResponse {
-                                        chain: vec![],
-                                        status: 203,
-                                        message: "handling requests not implemented".to_owned(),
-                                    })));
+                                    if blocksync_inflight.start(&peer_id) {
+                                        let db = Arc::clone(&self.db);
+                                        let sender = blocksync_sender.clone();
+                                        task::spawn(async move {
+                                            let response = task::spawn_blocking(move || make_blocksync_response(db.as_ref(), &r)).await;
+                                            sender.send((peer_id, req_id, response)).await;
+                                        });
+                                    } else {
+                                        debug!("Rejecting blocksync request from {}, too many requests in flight", peer_id);
+                                        let response = BlockSyncResponse {
+                                            chain: Vec::new(),
+                                            status: GO_AWAY,
+                                            message: "Too many requests in flight".to_owned(),
+                                        };
+                                        swarm_stream.get_mut().send_rpc(peer_id, RPCEvent::Response(req_id, RPCResponse::BlockSync(response)));
+                                    }
                                 }
                                 RPCEvent::Request(req_id, RPCRequest::Hello(message)) => {
-                                    self.network_sender_out.send(NetworkEvent::Hello{
-                                        message, source: peer_id}).await;
+                                    let arrival = SystemTime::now();
+                                    if message.genesis_hash != self.genesis_cid {
+                                        warn!("Banning peer {} with different genesis: {}", peer_id, message.genesis_hash);
+                                        let swarm = swarm_stream.get_mut();
+                                        swarm.remove_peer(&peer_id);
+                                        Swarm::ban_peer_id(swarm, peer_id.clone());
+                                        temp_bans.ban(peer_id, Instant::now() + GENESIS_MISMATCH_BAN);
+                                    } else {
+                                        swarm_stream.get_mut().send_rpc(peer_id.clone(), RPCEvent::Response(req_id, RPCResponse::Hello(HelloResponse::new(arrival))));
+                                        self.network_sender_out.send(NetworkEvent::Hello{
+                                            message, source: peer_id}).await;
+                                    }
                                 }
                                 RPCEvent::Error(req_id, err) => info!("Error with request {}: {:?}", req_id, err),
                             }
                         }
+                        ForestBehaviourEvent::PeerFound { peer_id, addrs } => {
+                            for response in find_peer_requests.remove(&peer_id).unwrap_or_default() {
+                                response.send(addrs.clone()).await;
+                            }
+                        }
                     }
                     None => {break;}
                 },
@@ -164,13 +380,60 @@ impl Libp2pService {
                             swarm_stream.get_mut().publish(&topic, message);
                         }
                         NetworkMessage::RPC{peer_id, event} => {
+                            if let RPCEvent::Request(_, RPCRequest::Hello(_)) = &event {
+                                hello_requests.insert(peer_id.clone(), SystemTime::now());
+                            }
                             swarm_stream.get_mut().send_rpc(peer_id, event);
                         }
+                        NetworkMessage::NetRPC(request) => {
+                            let swarm = swarm_stream.get_mut();
+                            match request {
+                                NetRPCRequest::Peers(response) => {
+                                    let peers = swarm.connected_peers().iter().map(|(p, a)| (p.clone(), a.clone())).collect();
+                                    response.send(peers).await;
+                                }
+                                NetRPCRequest::AddrsListen(response) => {
+                                    let peer_id = Swarm::local_peer_id(swarm).clone();
+                                    let addrs = Swarm::listeners(swarm).cloned().collect();
+                                    response.send((peer_id, addrs)).await;
+                                }
+                                NetRPCRequest::Connect { addrs, response } => {
+                                    let mut res = Err("no address to dial".to_owned());
+                                    for addr in addrs {
+                                        res = Swarm::dial_addr(swarm, addr.clone()).map_err(|e| format!("failed to dial {}: {}", addr, e));
+                                        if res.is_ok() {
+                                            break;
+                                        }
+                                    }
+                                    response.send(res).await;
+                                }
+                                NetRPCRequest::Disconnect { peer_id, response } => {
+                                    // Banning closes the connections to the peer, which can then dial or be dialed again
+                                    Swarm::ban_peer_id(swarm, peer_id.clone());
+                                    if !temp_bans.contains(&peer_id) {
+                                        Swarm::unban_peer_id(swarm, peer_id);
+                                    }
+                                    response.send(()).await;
+                                }
+                                NetRPCRequest::FindPeer { peer_id, response } => {
+                                    find_peer_requests.entry(peer_id.clone()).or_default().push(response);
+                                    swarm.find_peer(peer_id);
+                                }
+                            }
+                        }
                     }
                     None => {break;}
                 },
+                blocksync_response = blocksync_stream.next() => if let Some((peer_id, req_id, response)) = blocksync_response {
+                    blocksync_inflight.finish(&peer_id);
+                    swarm_stream.get_mut().send_rpc(peer_id, RPCEvent::Response(req_id, RPCResponse::BlockSync(response)));
+                },
                 interval_event = interval.next() => if interval_event.is_some() {
                     info!("Peers connected: {}", swarm_stream.get_ref().peers().len());
+                    for peer_id in temp_bans.take_expired(Instant::now()) {
+                        debug!("Lifting ban of peer {}", peer_id);
+                        Swarm::unban_peer_id(swarm_stream.get_mut(), peer_id);
+                    }
                 }
             };
         }
@@ -225,3 +488,63 @@ pub fn get_keypair(path: &str) -> Option<Keypair> {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_bans_expire() {
+        let now = Instant::now();
+        let short = PeerId::random();
+        let long = PeerId::random();
+        let mut bans = TempBans::default();
+        bans.ban(short.clone(), now + Duration::from_secs(1));
+        bans.ban(long.clone(), now + GENESIS_MISMATCH_BAN);
+
+        assert!(bans.take_expired(now).is_empty());
+        assert!(bans.contains(&short));
+
+        assert_eq!(
+            bans.take_expired(now + Duration::from_secs(1)),
+            vec![short.clone()]
+        );
+        assert!(!bans.contains(&short));
+        assert!(bans.contains(&long));
+
+        assert_eq!(
+            bans.take_expired(now + GENESIS_MISMATCH_BAN),
+            vec![long.clone()]
+        );
+        assert!(!bans.contains(&long));
+    }
+
+    #[test]
+    fn inflight_responses_limited() {
+        let mut inflight = InflightResponses::default();
+        let peer = PeerId::random();
+        for _ in 0..MAX_PEER_BLOCKSYNC_RESPONSES {
+            assert!(inflight.start(&peer));
+        }
+        assert!(!inflight.start(&peer));
+
+        // Other peers are served until the total limit is reached
+        let mut others = Vec::new();
+        while inflight.total < MAX_BLOCKSYNC_RESPONSES {
+            let other = PeerId::random();
+            assert!(inflight.start(&other));
+            others.push(other);
+        }
+        assert!(!inflight.start(&PeerId::random()));
+
+        // Finished responses free up room for the peer and in total
+        inflight.finish(&peer);
+        assert!(inflight.start(&peer));
+        assert!(!inflight.start(&PeerId::random()));
+        for other in &others {
+            inflight.finish(other);
+        }
+        assert!(inflight.start(&PeerId::random()));
+        assert!(!inflight.start(&peer));
+    }
+}
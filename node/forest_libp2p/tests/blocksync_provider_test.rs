@@ -0,0 +1,145 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crypto::{Signature, Signer};
+use db::MemoryDB;
+use forest_address::Address;
+use forest_blocks::{BlockHeader, TipsetKeys, TxMeta};
+use forest_cid::{multihash::Blake2b256, Cid};
+use forest_libp2p::blocksync::*;
+use forest_message::{SignedMessage, UnsignedMessage};
+use ipld_amt::Amt;
+use ipld_blockstore::BlockStore;
+use std::error::Error;
+
+/// Test struct to generate one byte signature for testing
+struct DummySigner;
+impl Signer for DummySigner {
+    fn sign_bytes(&self, _: Vec<u8>, _: &Address) -> Result<Signature, Box<dyn Error>> {
+        Ok(Signature::new_secp256k1(vec![0u8]))
+    }
+}
+
+struct TestChain {
+    db: MemoryDB,
+    msg: SignedMessage,
+    genesis: BlockHeader,
+    head: Vec<BlockHeader>,
+}
+
+/// Builds a genesis block and a tipset of two blocks on top, which both include the same message
+fn setup_chain() -> TestChain {
+    let db = MemoryDB::default();
+
+    let umsg = UnsignedMessage::builder()
+        .to(Address::new_id(1))
+        .from(Address::new_id(2))
+        .build()
+        .unwrap();
+    let msg = SignedMessage::new(umsg, &DummySigner).unwrap();
+    let msg_cid = db.put(&msg, Blake2b256).unwrap();
+
+    let meta = TxMeta {
+        bls_message_root: Amt::<Cid, _>::new_from_slice(&db, &[]).unwrap(),
+        secp_message_root: Amt::new_from_slice(&db, &[msg_cid]).unwrap(),
+    };
+    let meta_cid = db.put(&meta, Blake2b256).unwrap();
+
+    let genesis = BlockHeader::builder()
+        .miner_address(Address::new_id(0))
+        .messages(meta_cid.clone())
+        .build_and_validate()
+        .unwrap();
+    let head: Vec<BlockHeader> = (1..3)
+        .map(|miner| {
+            BlockHeader::builder()
+                .epoch(1)
+                .parents(TipsetKeys::new(vec![genesis.cid().clone()]))
+                .miner_address(Address::new_id(miner))
+                .messages(meta_cid.clone())
+                .build_and_validate()
+                .unwrap()
+        })
+        .collect();
+    for header in head.iter().chain(std::iter::once(&genesis)) {
+        db.put(header, Blake2b256).unwrap();
+    }
+
+    TestChain {
+        db,
+        msg,
+        genesis,
+        head,
+    }
+}
+
+fn request(start: &[BlockHeader], request_len: u64, options: u64) -> BlockSyncRequest {
+    BlockSyncRequest {
+        start: start.iter().map(|h| h.cid().clone()).collect(),
+        request_len,
+        options,
+    }
+}
+
+#[test]
+fn serve_blocks_and_messages() {
+    let chain = setup_chain();
+    let res = make_blocksync_response(&chain.db, &request(&chain.head, 5, BLOCKS | MESSAGES));
+    assert_eq!(res.status, OK);
+    assert_eq!(res.chain.len(), 2);
+
+    // Message included in both blocks is only sent once
+    let head_bundle = &res.chain[0];
+    assert_eq!(head_bundle.secp_msgs, vec![chain.msg.clone()]);
+    assert_eq!(head_bundle.secp_msg_includes, vec![vec![0], vec![0]]);
+    assert_eq!(head_bundle.bls_msg_includes, vec![Vec::<u64>::new(); 2]);
+
+    let tipsets = res.into_result().unwrap();
+    assert_eq!(tipsets[1].blocks()[0].header(), &chain.genesis);
+    assert_eq!(tipsets[0].blocks()[0].secp_msgs(), &[chain.msg]);
+}
+
+#[test]
+fn serve_blocks_only() {
+    let chain = setup_chain();
+    let res = make_blocksync_response(&chain.db, &request(&chain.head, 1, BLOCKS));
+    assert_eq!(res.status, OK);
+    assert_eq!(res.chain.len(), 1);
+    assert!(res.chain[0].secp_msgs.is_empty());
+
+    let tipsets = res.into_result().unwrap();
+    assert_eq!(tipsets[0].blocks().len(), 2);
+}
+
+#[test]
+fn invalid_and_missing_requests() {
+    let chain = setup_chain();
+    let res = make_blocksync_response(&chain.db, &request(&chain.head, 0, BLOCKS));
+    assert_eq!(res.status, BAD_REQUEST);
+    let res = make_blocksync_response(&chain.db, &request(&chain.head, 1, 0));
+    assert_eq!(res.status, BAD_REQUEST);
+    let res = make_blocksync_response(&chain.db, &request(&[], 1, BLOCKS));
+    assert_eq!(res.status, BAD_REQUEST);
+
+    let unknown = BlockHeader::builder()
+        .epoch(5)
+        .miner_address(Address::new_id(5))
+        .build_and_validate()
+        .unwrap();
+    let res = make_blocksync_response(&chain.db, &request(&[unknown.clone()], 1, BLOCKS));
+    assert_eq!(res.status, NOT_FOUND);
+    assert!(res.chain.is_empty());
+
+    // Tipset whose parent is not stored is served partially
+    let orphan = BlockHeader::builder()
+        .epoch(6)
+        .parents(TipsetKeys::new(vec![unknown.cid().clone()]))
+        .miner_address(Address::new_id(6))
+        .build_and_validate()
+        .unwrap();
+    chain.db.put(&orphan, Blake2b256).unwrap();
+    let res = make_blocksync_response(&chain.db, &request(&[orphan], 3, BLOCKS));
+    assert_eq!(res.status, PARTIAL);
+    assert_eq!(res.chain.len(), 1);
+    assert_eq!(res.into_result().unwrap().len(), 1);
+}
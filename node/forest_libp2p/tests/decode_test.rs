@@ -3,7 +3,8 @@
 
 use crypto::{Signature, Signer};
 use forest_address::Address;
-use forest_blocks::{Block, BlockHeader, FullTipset};
+use forest_blocks::{Block, BlockHeader, FullTipset, GossipBlock};
+use forest_encoding::{from_slice, Cbor};
 use forest_libp2p::blocksync::{BlockSyncResponse, TipsetBundle};
 use forest_message::{SignedMessage, UnsignedMessage};
 use num_bigint::BigUint;
@@ -124,3 +125,24 @@ fn tipset_bundle_to_full_tipset() {
         "Invalid includes index vector should return error"
     );
 }
+
+#[test]
+fn gossip_block_round_trip() {
+    let header = BlockHeader::builder()
+        .miner_address(Address::new_id(0))
+        .build_and_validate()
+        .unwrap();
+    let msg = UnsignedMessage::builder()
+        .to(Address::new_id(1))
+        .from(Address::new_id(2))
+        .build()
+        .unwrap();
+    let block = GossipBlock {
+        header,
+        bls_messages: vec![msg.cid().unwrap()],
+        secpk_messages: Vec::new(),
+    };
+
+    let bz = block.marshal_cbor().unwrap();
+    assert_eq!(from_slice::<GossipBlock>(&bz).unwrap(), block);
+}
@@ -0,0 +1,251 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use lazy_static::lazy_static;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Size in bytes of the secret API tokens are signed with
+const SECRET_LEN: usize = 32;
+
+/// Permission level of an API token or required by an RPC method. Each level grants
+/// the permissions of the levels below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    Read,
+    Write,
+    Sign,
+    Admin,
+}
+
+impl Access {
+    /// All permission levels, ordered from the lowest
+    pub const ALL: [Access; 4] = [Access::Read, Access::Write, Access::Sign, Access::Admin];
+
+    /// Returns the permissions granted by the access level, which are included in tokens
+    pub fn permissions(self) -> Vec<String> {
+        Self::ALL
+            .iter()
+            .filter(|a| **a <= self)
+            .map(|a| a.to_string())
+            .collect()
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Sign => "sign",
+            Access::Admin => "admin",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Access {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|a| a.to_string() == s)
+            .copied()
+            .ok_or_else(|| Error::UnknownPermission(s.to_owned()))
+    }
+}
+
+/// Authentication error
+#[derive(Debug, PartialEq, Error)]
+pub enum Error {
+    #[error("Invalid API token: {0}")]
+    InvalidToken(String),
+    #[error("Unknown permission: {0}")]
+    UnknownPermission(String),
+    #[error("Missing permission to call method {method}, requires {access} access")]
+    MissingPermission { method: String, access: Access },
+}
+
+/// Claims of an API token, compatible with Lotus tokens
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    #[serde(rename = "Allow")]
+    allow: Vec<String>,
+}
+
+lazy_static! {
+    /// Access required to call each RPC method. Methods which are not listed require admin access.
+    pub static ref ACCESS_MAP: HashMap<&'static str, Access> = {
+        let mut access = HashMap::new();
+
+        // Chain API
+        access.insert("Filecoin.ChainGetMessage", Access::Read);
+        access.insert("Filecoin.ChainGetObj", Access::Read);
+        access.insert("Filecoin.ChainHasObj", Access::Read);
+        access.insert("Filecoin.ChainGetBlockMessages", Access::Read);
+        access.insert("Filecoin.ChainGetTipsetByHeight", Access::Read);
+        access.insert("Filecoin.ChainGetGenesis", Access::Read);
+        access.insert("Filecoin.ChainTipsetWeight", Access::Read);
+        access.insert("Filecoin.ChainGetTipset", Access::Read);
+        access.insert("Filecoin.GetRandomness", Access::Read);
+        access.insert("Filecoin.ChainGetBlock", Access::Read);
+        access.insert("Filecoin.ChainHead", Access::Read);
+        access.insert("Filecoin.ChainNotify", Access::Read);
+
+        // State API
+        access.insert("Filecoin.StateGetActor", Access::Read);
+        access.insert("Filecoin.StateLookupID", Access::Read);
+        access.insert("Filecoin.StateAccountKey", Access::Read);
+        access.insert("Filecoin.StateMinerPower", Access::Read);
+        access.insert("Filecoin.StateMinerInfo", Access::Read);
+        access.insert("Filecoin.StateMinerSectors", Access::Read);
+        access.insert("Filecoin.StateMarketDeals", Access::Read);
+        access.insert("Filecoin.StateReadState", Access::Read);
+        access.insert("Filecoin.StateNetworkName", Access::Read);
+        access.insert("Filecoin.StateCall", Access::Read);
+        access.insert("Filecoin.StateReplay", Access::Read);
+        access.insert("Filecoin.StateCompute", Access::Read);
+        access.insert("Filecoin.StateSearchMsg", Access::Read);
+        access.insert("Filecoin.StateWaitMsg", Access::Read);
+
+        // Net API
+        access.insert("Filecoin.NetPeers", Access::Read);
+        access.insert("Filecoin.NetConnect", Access::Write);
+        access.insert("Filecoin.NetAddrsListen", Access::Read);
+        access.insert("Filecoin.NetDisconnect", Access::Write);
+        access.insert("Filecoin.NetFindPeer", Access::Read);
+
+        // Sync API
+        access.insert("Filecoin.SyncState", Access::Read);
+        access.insert("Filecoin.SyncCheckBad", Access::Read);
+        access.insert("Filecoin.SyncMarkBad", Access::Admin);
+        access.insert("Filecoin.SyncUnmarkBad", Access::Admin);
+
+        // Message pool API
+        access.insert("Filecoin.MpoolPush", Access::Write);
+        access.insert("Filecoin.MpoolPending", Access::Read);
+        access.insert("Filecoin.MpoolGetNonce", Access::Read);
+        access.insert("Filecoin.MpoolPushMessage", Access::Sign);
+
+        // Wallet API
+        access.insert("Filecoin.WalletNew", Access::Write);
+        access.insert("Filecoin.WalletList", Access::Write);
+        access.insert("Filecoin.WalletHas", Access::Write);
+        access.insert("Filecoin.WalletBalance", Access::Read);
+        access.insert("Filecoin.WalletSign", Access::Sign);
+        access.insert("Filecoin.WalletSignMessage", Access::Sign);
+        access.insert("Filecoin.WalletVerify", Access::Read);
+        access.insert("Filecoin.WalletExport", Access::Admin);
+        access.insert("Filecoin.WalletImport", Access::Admin);
+        access.insert("Filecoin.WalletDefaultAddress", Access::Write);
+        access.insert("Filecoin.WalletSetDefault", Access::Admin);
+
+        // Discovery
+        access.insert("rpc.discover", Access::Read);
+
+        access
+    };
+}
+
+/// Generates a new random secret to sign API tokens with
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0; SECRET_LEN];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// Creates an API token granting the given access, signed with the secret
+pub fn create_token(access: Access, secret: &[u8]) -> Result<String, Error> {
+    let claims = Claims {
+        allow: access.permissions(),
+    };
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret),
+    )
+    .map_err(|e| Error::InvalidToken(e.to_string()))
+}
+
+/// Verifies the signature of an API token and returns the permissions it grants
+pub fn verify_token(token: &str, secret: &[u8]) -> Result<Vec<String>, Error> {
+    // Tokens do not expire
+    let validation = Validation {
+        validate_exp: false,
+        ..Default::default()
+    };
+    let data = decode::<Claims>(token, &DecodingKey::from_secret(secret), &validation)
+        .map_err(|e| Error::InvalidToken(e.to_string()))?;
+    Ok(data.claims.allow)
+}
+
+/// Checks that the optional bearer token grants the access required to call the method.
+/// Methods requiring read access can be called without a token.
+pub fn check_access(method: &str, token: Option<&str>, secret: &[u8]) -> Result<(), Error> {
+    let access = ACCESS_MAP.get(method).copied().unwrap_or(Access::Admin);
+    if access == Access::Read {
+        return Ok(());
+    }
+    let permissions = match token {
+        Some(token) => verify_token(token, secret)?,
+        None => Vec::new(),
+    };
+    if permissions.contains(&access.to_string()) {
+        Ok(())
+    } else {
+        Err(Error::MissingPermission {
+            method: method.to_owned(),
+            access,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_permissions() {
+        let secret = generate_secret();
+        let token = create_token(Access::Sign, &secret).unwrap();
+        assert_eq!(
+            verify_token(&token, &secret).unwrap(),
+            vec!["read", "write", "sign"]
+        );
+        assert!(verify_token(&token, &generate_secret()).is_err());
+        assert_eq!("admin".parse(), Ok(Access::Admin));
+        assert!("root".parse::<Access>().is_err());
+    }
+
+    #[test]
+    fn method_access() {
+        let secret = generate_secret();
+        let write_token = create_token(Access::Write, &secret).unwrap();
+        let admin_token = create_token(Access::Admin, &secret).unwrap();
+
+        // Read methods do not require a token
+        assert!(check_access("Filecoin.ChainHead", None, &secret).is_ok());
+
+        // Signing requires a token with sign access
+        assert!(check_access("Filecoin.WalletSign", Some(&write_token), &secret).is_err());
+        assert!(check_access("Filecoin.WalletSign", Some(&admin_token), &secret).is_ok());
+
+        // Unlisted methods require admin access
+        assert_eq!(
+            check_access("Filecoin.Unknown", Some(&write_token), &secret),
+            Err(Error::MissingPermission {
+                method: "Filecoin.Unknown".to_owned(),
+                access: Access::Admin
+            })
+        );
+        assert!(check_access("Filecoin.Unknown", None, &secret).is_err());
+        assert!(check_access("Filecoin.Unknown", Some(&admin_token), &secret).is_ok());
+        assert!(check_access("Filecoin.Unknown", Some("invalid"), &secret).is_err());
+    }
+}
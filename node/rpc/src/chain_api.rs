@@ -1,6 +1,7 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::schema::{object, JsonSchema};
 use crate::State;
 use blocks::{
     header::json::BlockHeaderJson, tipset_json::TipsetJson, BlockHeader, Tipset, TipsetKeys,
@@ -12,12 +13,14 @@ use crypto::DomainSeparationTag;
 
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use message::{
-    signed_message,
+    signed_message::{self, json::SignedMessageJson},
     unsigned_message::{self, json::UnsignedMessageJson},
     SignedMessage, UnsignedMessage,
 };
 use num_traits::FromPrimitive;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wallet::KeyStore;
 
 #[derive(Serialize, Deserialize)]
 pub(crate) struct BlockMessages {
@@ -29,6 +32,16 @@ pub(crate) struct BlockMessages {
     pub cids: Vec<Cid>,
 }
 
+impl JsonSchema for BlockMessages {
+    fn schema() -> Value {
+        object(vec![
+            ("BlsMessages", Vec::<UnsignedMessageJson>::schema()),
+            ("SecpkMessages", Vec::<SignedMessageJson>::schema()),
+            ("Cids", Vec::<CidJson>::schema()),
+        ])
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub(crate) struct Message {
@@ -38,8 +51,11 @@ pub(crate) struct Message {
     message: UnsignedMessage,
 }
 
-pub(crate) async fn chain_get_message<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_get_message<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
     Params(params): Params<(CidJson,)>,
 ) -> Result<UnsignedMessageJson, JsonRpcError> {
     let (CidJson(msg_cid),) = params;
@@ -50,8 +66,11 @@ pub(crate) async fn chain_get_message<DB: BlockStore + Send + Sync + 'static>(
     Ok(UnsignedMessageJson(ret))
 }
 
-pub(crate) async fn chain_read_obj<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_read_obj<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
     Params(params): Params<(CidJson,)>,
 ) -> Result<Vec<u8>, JsonRpcError> {
     let (CidJson(obj_cid),) = params;
@@ -62,16 +81,22 @@ pub(crate) async fn chain_read_obj<DB: BlockStore + Send + Sync + 'static>(
     Ok(ret)
 }
 
-pub(crate) async fn chain_has_obj<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_has_obj<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
     Params(params): Params<(CidJson,)>,
 ) -> Result<bool, JsonRpcError> {
     let (CidJson(obj_cid),) = params;
     Ok(data.store.get_bytes(&obj_cid)?.is_some())
 }
 
-pub(crate) async fn chain_block_messages<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_block_messages<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
     Params(params): Params<(CidJson,)>,
 ) -> Result<BlockMessages, JsonRpcError> {
     let (CidJson(blk_cid),) = params;
@@ -96,8 +121,11 @@ pub(crate) async fn chain_block_messages<DB: BlockStore + Send + Sync + 'static>
     Ok(ret)
 }
 
-pub(crate) async fn chain_get_tipset_by_height<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_get_tipset_by_height<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
     Params(params): Params<(ChainEpoch, TipsetKeys)>,
 ) -> Result<TipsetJson, JsonRpcError> {
     let (height, tsk) = params;
@@ -106,24 +134,33 @@ pub(crate) async fn chain_get_tipset_by_height<DB: BlockStore + Send + Sync + 's
     Ok(TipsetJson(tss))
 }
 
-pub(crate) async fn chain_get_genesis<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_get_genesis<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
 ) -> Result<Option<TipsetJson>, JsonRpcError> {
     let genesis = chain::genesis(data.store.as_ref())?.ok_or("can't find genesis tipset")?;
     let gen_ts = Tipset::new(vec![genesis])?;
     Ok(Some(TipsetJson(gen_ts)))
 }
 
-pub(crate) async fn chain_head<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_head<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
 ) -> Result<TipsetJson, JsonRpcError> {
     let heaviest =
         chain::get_heaviest_tipset(data.store.as_ref())?.ok_or("can't find heaviest tipset")?;
     Ok(TipsetJson(heaviest))
 }
 
-pub(crate) async fn chain_tipset_weight<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_tipset_weight<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
     Params(params): Params<(TipsetKeys,)>,
 ) -> Result<String, JsonRpcError> {
     let (tsk,) = params;
@@ -131,8 +168,11 @@ pub(crate) async fn chain_tipset_weight<DB: BlockStore + Send + Sync + 'static>(
     Ok(ts.weight().to_str_radix(10))
 }
 
-pub(crate) async fn chain_get_block<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_get_block<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
     Params(params): Params<(CidJson,)>,
 ) -> Result<BlockHeaderJson, JsonRpcError> {
     let (CidJson(blk_cid),) = params;
@@ -144,8 +184,11 @@ pub(crate) async fn chain_get_block<DB: BlockStore + Send + Sync + 'static>(
     Ok(BlockHeaderJson(blk))
 }
 
-pub(crate) async fn chain_get_tipset<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_get_tipset<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
     Params(params): Params<(TipsetKeys,)>,
 ) -> Result<TipsetJson, JsonRpcError> {
     let (tsk,) = params;
@@ -153,8 +196,11 @@ pub(crate) async fn chain_get_tipset<DB: BlockStore + Send + Sync + 'static>(
     Ok(TipsetJson(ts))
 }
 
-pub(crate) async fn chain_get_randomness<DB: BlockStore + Send + Sync + 'static>(
-    data: Data<State<DB>>,
+pub(crate) async fn chain_get_randomness<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
     Params(params): Params<(TipsetKeys, i64, ChainEpoch, Vec<u8>)>,
 ) -> Result<[u8; 32], JsonRpcError> {
     let (tsk, pers, epoch, entropy) = params;
@@ -1,58 +1,196 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub mod auth;
 mod chain_api;
+mod mpool_api;
+mod net_api;
+mod openrpc;
+mod schema;
+mod state_api;
+mod sync_api;
+mod wallet_api;
+mod websocket;
 
+use async_std::sync::{Mutex, RwLock, Sender};
+use async_std::task;
+use blockchain::message_pool::{MessagePool, MpoolProvider};
 use blockstore::BlockStore;
+use chain::HeadChange;
+use chain_sync::{BadBlockCache, SyncStatus};
+use flo_stream::{MessagePublisher, Publisher, Subscriber};
+use forest_libp2p::NetworkMessage;
+use futures::StreamExt;
 use jsonrpc_v2::{Data, MapRouter, RequestObject, Server};
+use serde_json::Value;
+use state_manager::StateManager;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tide::{Request, Response, StatusCode};
+use wallet::{KeyStore, Wallet};
+
+// A cap on the head changes buffered for each subscriber
+const SINK_CAP: usize = 1000;
+
+/// Registers the methods on the server along with the OpenRPC document describing them, which
+/// is served by `rpc.discover`
+macro_rules! register_methods {
+    ($server:expr, $($name:expr => $handler:expr,)*) => {{
+        let mut doc = openrpc::OpenRpc::default();
+        $(doc.add_method($name, &$handler);)*
+        $server.with_data(Data::new(doc))$(.with_method($name, $handler))*
+    }};
+}
 
 /// This is where you store persistant data, or at least access to stateful data.
-pub struct State<DB: BlockStore + Send + Sync + 'static> {
+pub struct State<DB, KS>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
     pub store: Arc<DB>,
+    pub state_manager: Arc<StateManager<DB>>,
+    pub mpool: Arc<MessagePool<MpoolProvider<DB>>>,
+    pub wallet: Arc<RwLock<Wallet<KS>>>,
+    pub network_send: Sender<NetworkMessage>,
+    pub sync_state: Arc<RwLock<SyncStatus>>,
+    pub bad_blocks: Arc<BadBlockCache>,
+    pub head_changes: Arc<HeadChanges>,
 }
 
-async fn handle_json_rpc(mut req: Request<Server<MapRouter>>) -> tide::Result {
-    let call: RequestObject = req.body_json().await?;
-    let res = req.state().handle(call).await;
+/// Fans out the head changes of the chain store to the subscribers of the RPC server
+pub struct HeadChanges {
+    publisher: Mutex<Publisher<HeadChange>>,
+}
+
+impl HeadChanges {
+    /// Forwards the head changes received from the chain store subscription
+    pub fn new(mut chain_changes: Subscriber<HeadChange>) -> Arc<Self> {
+        let head_changes = Arc::new(Self {
+            publisher: Mutex::new(Publisher::new(SINK_CAP)),
+        });
+        let publisher = Arc::clone(&head_changes);
+        task::spawn(async move {
+            while let Some(change) = chain_changes.next().await {
+                publisher.publisher.lock().await.publish(change).await;
+            }
+        });
+        head_changes
+    }
+
+    /// Subscribes to the head changes applied from now on
+    pub async fn subscribe(&self) -> Subscriber<HeadChange> {
+        self.publisher.lock().await.subscribe()
+    }
+}
+
+/// State of the http server handling RPC requests
+struct RpcServer {
+    rpc: Arc<Server<MapRouter>>,
+    /// Secret API tokens are signed with
+    jwt_secret: Vec<u8>,
+}
+
+async fn handle_json_rpc(mut req: Request<RpcServer>) -> tide::Result {
+    let call: Value = req.body_json().await?;
+
+    // Check the bearer token grants access to the method before handling the call
+    let method = call["method"].as_str().unwrap_or_default();
+    let token = req
+        .header("Authorization")
+        .map(|values| values.last().as_str().trim_start_matches("Bearer ").trim());
+    if let Err(e) = auth::check_access(method, token, &req.state().jwt_secret) {
+        return Ok(Response::new(StatusCode::Unauthorized).body_string(e.to_string()));
+    }
+
+    let call: RequestObject = serde_json::from_value(call)?;
+    let res = req.state().rpc.handle(call).await;
     Ok(Response::new(StatusCode::Ok).body_json(&res)?)
 }
 
-pub async fn start_rpc<DB: BlockStore + Send + Sync + 'static>(store: Arc<DB>) {
-    let rpc = Server::new()
-        .with_data(Data::new(State { store }))
-        .with_method(
-            "Filecoin.ChainGetMessage",
-            chain_api::chain_get_message::<DB>,
-        )
-        .with_method("Filecoin.ChainGetObj", chain_api::chain_read_obj::<DB>)
-        .with_method("Filecoin.ChainHasObj", chain_api::chain_has_obj::<DB>)
-        .with_method(
-            "Filecoin.ChainGetBlockMessages",
-            chain_api::chain_block_messages::<DB>,
-        )
-        .with_method(
-            "Filecoin.ChainGetTipsetByHeight",
-            chain_api::chain_get_tipset_by_height::<DB>,
-        )
-        .with_method(
-            "Filecoin.ChainGetGenesis",
-            chain_api::chain_get_genesis::<DB>,
-        )
-        .with_method(
-            "Filecoin.ChainTipsetWeight",
-            chain_api::chain_tipset_weight::<DB>,
-        )
-        .with_method("Filecoin.ChainGetTipset", chain_api::chain_get_tipset::<DB>)
-        .with_method(
-            "Filecoin.GetRandomness",
-            chain_api::chain_get_randomness::<DB>,
-        )
-        .with_method("Filecoin.ChainGetBlock", chain_api::chain_get_block::<DB>)
-        .with_method("Filecoin.ChainHead", chain_api::chain_head::<DB>)
-        .finish_unwrapped();
-    let mut app = tide::Server::with_state(rpc);
+/// Starts the RPC server on the given address, and the WebSocket RPC server supporting
+/// subscriptions on the given WebSocket address. Calls to methods requiring more than read
+/// access must include an API token signed with the secret.
+pub async fn start_rpc<DB, KS>(
+    state: State<DB, KS>,
+    jwt_secret: Vec<u8>,
+    rpc_endpoint: SocketAddr,
+    ws_endpoint: SocketAddr,
+) where
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    let store = Arc::clone(&state.store);
+    let head_changes = Arc::clone(&state.head_changes);
+    let rpc = register_methods!(
+        Server::new().with_data(Data::new(state)),
+        "Filecoin.ChainGetMessage" => chain_api::chain_get_message::<DB, KS>,
+        "Filecoin.ChainGetObj" => chain_api::chain_read_obj::<DB, KS>,
+        "Filecoin.ChainHasObj" => chain_api::chain_has_obj::<DB, KS>,
+        "Filecoin.ChainGetBlockMessages" => chain_api::chain_block_messages::<DB, KS>,
+        "Filecoin.ChainGetTipsetByHeight" => chain_api::chain_get_tipset_by_height::<DB, KS>,
+        "Filecoin.ChainGetGenesis" => chain_api::chain_get_genesis::<DB, KS>,
+        "Filecoin.ChainTipsetWeight" => chain_api::chain_tipset_weight::<DB, KS>,
+        "Filecoin.ChainGetTipset" => chain_api::chain_get_tipset::<DB, KS>,
+        "Filecoin.GetRandomness" => chain_api::chain_get_randomness::<DB, KS>,
+        "Filecoin.ChainGetBlock" => chain_api::chain_get_block::<DB, KS>,
+        "Filecoin.ChainHead" => chain_api::chain_head::<DB, KS>,
+        // State API
+        "Filecoin.StateGetActor" => state_api::state_get_actor::<DB, KS>,
+        "Filecoin.StateLookupID" => state_api::state_lookup_id::<DB, KS>,
+        "Filecoin.StateAccountKey" => state_api::state_account_key::<DB, KS>,
+        "Filecoin.StateMinerPower" => state_api::state_miner_power::<DB, KS>,
+        "Filecoin.StateMinerInfo" => state_api::state_miner_info::<DB, KS>,
+        "Filecoin.StateMinerSectors" => state_api::state_miner_sectors::<DB, KS>,
+        "Filecoin.StateMarketDeals" => state_api::state_market_deals::<DB, KS>,
+        "Filecoin.StateReadState" => state_api::state_read_state::<DB, KS>,
+        "Filecoin.StateNetworkName" => state_api::state_network_name::<DB, KS>,
+        "Filecoin.StateCall" => state_api::state_call::<DB, KS>,
+        "Filecoin.StateReplay" => state_api::state_replay::<DB, KS>,
+        "Filecoin.StateCompute" => state_api::state_compute::<DB, KS>,
+        "Filecoin.StateSearchMsg" => state_api::state_search_msg::<DB, KS>,
+        "Filecoin.StateWaitMsg" => state_api::state_wait_msg::<DB, KS>,
+        // Net API
+        "Filecoin.NetPeers" => net_api::net_peers::<DB, KS>,
+        "Filecoin.NetConnect" => net_api::net_connect::<DB, KS>,
+        "Filecoin.NetAddrsListen" => net_api::net_addrs_listen::<DB, KS>,
+        "Filecoin.NetDisconnect" => net_api::net_disconnect::<DB, KS>,
+        "Filecoin.NetFindPeer" => net_api::net_find_peer::<DB, KS>,
+        // Sync API
+        "Filecoin.SyncState" => sync_api::sync_state::<DB, KS>,
+        "Filecoin.SyncCheckBad" => sync_api::sync_check_bad::<DB, KS>,
+        "Filecoin.SyncMarkBad" => sync_api::sync_mark_bad::<DB, KS>,
+        "Filecoin.SyncUnmarkBad" => sync_api::sync_unmark_bad::<DB, KS>,
+        // Message pool API
+        "Filecoin.MpoolPush" => mpool_api::mpool_push::<DB, KS>,
+        "Filecoin.MpoolPending" => mpool_api::mpool_pending::<DB, KS>,
+        "Filecoin.MpoolGetNonce" => mpool_api::mpool_get_nonce::<DB, KS>,
+        "Filecoin.MpoolPushMessage" => mpool_api::mpool_push_message::<DB, KS>,
+        // Wallet API
+        "Filecoin.WalletNew" => wallet_api::wallet_new::<DB, KS>,
+        "Filecoin.WalletList" => wallet_api::wallet_list::<DB, KS>,
+        "Filecoin.WalletHas" => wallet_api::wallet_has::<DB, KS>,
+        "Filecoin.WalletBalance" => wallet_api::wallet_balance::<DB, KS>,
+        "Filecoin.WalletSign" => wallet_api::wallet_sign::<DB, KS>,
+        "Filecoin.WalletSignMessage" => wallet_api::wallet_sign_message::<DB, KS>,
+        "Filecoin.WalletVerify" => wallet_api::wallet_verify::<DB, KS>,
+        "Filecoin.WalletExport" => wallet_api::wallet_export::<DB, KS>,
+        "Filecoin.WalletImport" => wallet_api::wallet_import::<DB, KS>,
+        "Filecoin.WalletDefaultAddress" => wallet_api::wallet_default_address::<DB, KS>,
+        "Filecoin.WalletSetDefault" => wallet_api::wallet_set_default::<DB, KS>,
+        // Discovery
+        "rpc.discover" => openrpc::rpc_discover,
+    )
+    .finish();
+    task::spawn(websocket::start_ws(
+        store,
+        Arc::clone(&rpc),
+        jwt_secret.clone(),
+        head_changes,
+        ws_endpoint,
+    ));
+
+    let mut app = tide::Server::with_state(RpcServer { rpc, jwt_secret });
     app.at("/api").post(handle_json_rpc);
-    app.listen("127.0.0.1:8080").await.unwrap();
+    app.listen(rpc_endpoint).await.unwrap();
 }
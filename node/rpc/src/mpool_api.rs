@@ -0,0 +1,92 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::State;
+use address::json::AddressJson;
+use blockstore::BlockStore;
+use cid::json::CidJson;
+use encoding::Cbor;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use message::{
+    signed_message::json::SignedMessageJson, unsigned_message::json::UnsignedMessageJson, Message,
+    SignedMessage,
+};
+use num_bigint::BigUint;
+use num_traits::Zero;
+use wallet::KeyStore;
+
+/// Gas limit set on messages pushed through the node without a gas limit
+const DEFAULT_GAS_LIMIT: u64 = 10_000;
+/// Gas price set on messages pushed through the node without a gas price
+const DEFAULT_GAS_PRICE: u64 = 1;
+
+/// Adds a signed message to the message pool and publishes it to the network
+pub(crate) async fn mpool_push<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(SignedMessageJson,)>,
+) -> Result<CidJson, JsonRpcError> {
+    let (SignedMessageJson(smsg),) = params;
+    let cid = data.mpool.push(smsg).await?;
+    Ok(CidJson(cid))
+}
+
+/// Returns the pending messages of the message pool, only from the sender if given
+pub(crate) async fn mpool_pending<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(Option<AddressJson>,)>,
+) -> Result<Vec<SignedMessageJson>, JsonRpcError> {
+    let (addr,) = params;
+    let pending = match addr {
+        Some(AddressJson(addr)) => data.mpool.pending_for(&addr).await,
+        None => data.mpool.pending().await,
+    };
+    Ok(pending.into_iter().map(SignedMessageJson).collect())
+}
+
+/// Returns the next nonce of the address, accounting for pending messages
+pub(crate) async fn mpool_get_nonce<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson,)>,
+) -> Result<u64, JsonRpcError> {
+    let (AddressJson(addr),) = params;
+    Ok(data.mpool.get_nonce(&addr).await?)
+}
+
+/// Assigns the next nonce of the sender to the message, fills in default gas values, signs
+/// it with the key of the sender in the node wallet and pushes it to the message pool
+pub(crate) async fn mpool_push_message<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(UnsignedMessageJson,)>,
+) -> Result<SignedMessageJson, JsonRpcError> {
+    let (UnsignedMessageJson(mut msg),) = params;
+    if msg.sequence() != 0 {
+        return Err("message nonce must be 0, it is assigned by the node".into());
+    }
+    if msg.gas_limit() == 0 {
+        msg.set_gas_limit(DEFAULT_GAS_LIMIT);
+    }
+    if msg.gas_price().is_zero() {
+        msg.set_gas_price(BigUint::from(DEFAULT_GAS_PRICE));
+    }
+
+    // The wallet stays locked until the message is pushed, so concurrent calls are not
+    // assigned the same nonce
+    let mut wallet = data.wallet.write().await;
+    msg.set_sequence(data.mpool.get_nonce(msg.from()).await?);
+    let sig = wallet.sign(msg.from(), &msg.marshal_cbor()?)?;
+    let smsg = SignedMessage::new_from_parts(msg, sig)?;
+    data.mpool.push(smsg.clone()).await?;
+    Ok(SignedMessageJson(smsg))
+}
@@ -0,0 +1,213 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::schema::{array, object, string, JsonSchema};
+use crate::State;
+use async_std::sync::{channel, Sender};
+use blockstore::BlockStore;
+use forest_libp2p::{NetRPCRequest, NetworkMessage};
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use libp2p::multiaddr::{Multiaddr, Protocol};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wallet::KeyStore;
+
+/// Peer ID and addresses of a peer
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct AddrInfo {
+    #[serde(rename = "ID")]
+    id: String,
+    addrs: Vec<String>,
+}
+
+impl JsonSchema for AddrInfo {
+    fn schema() -> Value {
+        object(vec![
+            ("ID", string("Peer ID")),
+            ("Addrs", array(string("Multiaddress"))),
+        ])
+    }
+}
+
+impl AddrInfo {
+    fn new(peer_id: PeerId, addrs: Vec<Multiaddr>) -> Self {
+        Self {
+            id: peer_id.to_base58(),
+            addrs: addrs.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+}
+
+/// Parses a peer ID given as parameter
+fn parse_peer_id(peer_id: &str) -> Result<PeerId, String> {
+    peer_id
+        .parse()
+        .map_err(|_| format!("invalid peer ID: {}", peer_id))
+}
+
+/// Returns the peer ID and the addresses to dial it on, ending with the peer ID
+fn dial_addrs(info: &AddrInfo) -> Result<(PeerId, Vec<Multiaddr>), String> {
+    let peer_id = parse_peer_id(&info.id)?;
+    let addrs = info
+        .addrs
+        .iter()
+        .map(|addr| {
+            // Dialing the address only succeeds if the peer has the given ID
+            let mut parsed: Multiaddr = addr
+                .parse()
+                .map_err(|e| format!("invalid multiaddr {}: {}", addr, e))?;
+            match parsed.iter().last() {
+                Some(Protocol::P2p(_)) => (),
+                _ => parsed.push(Protocol::P2p(peer_id.clone().into())),
+            }
+            Ok(parsed)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok((peer_id, addrs))
+}
+
+/// Sends the request to the network service and waits for its response
+async fn request<T>(
+    network_send: &Sender<NetworkMessage>,
+    request: impl FnOnce(Sender<T>) -> NetRPCRequest,
+) -> Result<T, JsonRpcError> {
+    let (tx, rx) = channel(1);
+    network_send.send(NetworkMessage::NetRPC(request(tx))).await;
+    Ok(rx.recv().await?)
+}
+
+/// Returns the connected peers with their addresses
+pub(crate) async fn net_peers<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+) -> Result<Vec<AddrInfo>, JsonRpcError> {
+    let peers = request(&data.network_send, NetRPCRequest::Peers).await?;
+    Ok(peers
+        .into_iter()
+        .map(|(peer_id, addrs)| AddrInfo::new(peer_id, addrs))
+        .collect())
+}
+
+/// Dials the peer on its addresses
+pub(crate) async fn net_connect<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddrInfo,)>,
+) -> Result<(), JsonRpcError> {
+    let (info,) = params;
+    let (_, addrs) = dial_addrs(&info)?;
+    let res = request(&data.network_send, |response| NetRPCRequest::Connect {
+        addrs,
+        response,
+    })
+    .await?;
+    Ok(res?)
+}
+
+/// Returns the local peer ID and the addresses listened on
+pub(crate) async fn net_addrs_listen<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+) -> Result<AddrInfo, JsonRpcError> {
+    let (peer_id, addrs) = request(&data.network_send, NetRPCRequest::AddrsListen).await?;
+    Ok(AddrInfo::new(peer_id, addrs))
+}
+
+/// Closes the connections to the peer
+pub(crate) async fn net_disconnect<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(String,)>,
+) -> Result<(), JsonRpcError> {
+    let (peer_id,) = params;
+    let peer_id = parse_peer_id(&peer_id)?;
+    request(&data.network_send, |response| NetRPCRequest::Disconnect {
+        peer_id,
+        response,
+    })
+    .await
+}
+
+/// Looks up the addresses of the peer in the Kademlia DHT
+pub(crate) async fn net_find_peer<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(String,)>,
+) -> Result<AddrInfo, JsonRpcError> {
+    let (peer_id,) = params;
+    let peer_id = parse_peer_id(&peer_id)?;
+    let addrs = request(&data.network_send, |response| NetRPCRequest::FindPeer {
+        peer_id: peer_id.clone(),
+        response,
+    })
+    .await?;
+    if addrs.is_empty() {
+        return Err(format!("peer {} not found", peer_id).into());
+    }
+    Ok(AddrInfo::new(peer_id, addrs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::tests::assert_schema;
+
+    #[test]
+    fn addr_info_schema() {
+        assert_schema(&AddrInfo {
+            id: PeerId::random().to_base58(),
+            addrs: vec!["/ip4/127.0.0.1/tcp/1347".to_owned()],
+        });
+    }
+
+    #[test]
+    fn connect_addrs() {
+        let peer_id = PeerId::random();
+        let p2p_addr = format!("/ip4/127.0.0.1/tcp/1348/p2p/{}", peer_id.to_base58());
+        let info = AddrInfo {
+            id: peer_id.to_base58(),
+            addrs: vec!["/ip4/127.0.0.1/tcp/1347".to_owned(), p2p_addr.clone()],
+        };
+        let (parsed, addrs) = dial_addrs(&info).unwrap();
+        assert_eq!(parsed, peer_id);
+
+        // Addresses are suffixed with the peer ID, unless they already end with it
+        let addrs: Vec<String> = addrs.iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            addrs,
+            vec![
+                format!("/ip4/127.0.0.1/tcp/1347/p2p/{}", peer_id.to_base58()),
+                p2p_addr
+            ]
+        );
+    }
+
+    #[test]
+    fn connect_invalid_params() {
+        let peer_id = PeerId::random().to_base58();
+        let info = |id: &str, addr: &str| AddrInfo {
+            id: id.to_owned(),
+            addrs: vec![addr.to_owned()],
+        };
+
+        assert_eq!(
+            dial_addrs(&info("invalid", "/ip4/127.0.0.1/tcp/1347")).err(),
+            Some("invalid peer ID: invalid".to_owned())
+        );
+        assert!(dial_addrs(&info(&peer_id, "127.0.0.1:1347")).is_err());
+        assert!(parse_peer_id(&peer_id).is_ok());
+        assert!(parse_peer_id("").is_err());
+    }
+}
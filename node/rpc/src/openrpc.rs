@@ -0,0 +1,168 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::schema::{JsonSchema, ParamsSchema};
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::future::Future;
+
+/// Version of the OpenRPC specification the document follows
+const OPENRPC_VERSION: &str = "1.2.6";
+
+/// Method handler with parameter and result types described by JSON schemas. The marker type
+/// distinguishes handlers taking parameters from those which don't.
+pub(crate) trait Handler<M> {
+    /// Returns the schemas of the parameters, in order
+    fn params() -> Vec<Value>;
+    /// Returns the schema of the result
+    fn result() -> Value;
+}
+
+impl<F, S, R, Fut> Handler<(S,)> for F
+where
+    F: Fn(Data<S>) -> Fut,
+    Fut: Future<Output = Result<R, JsonRpcError>>,
+    R: JsonSchema,
+{
+    fn params() -> Vec<Value> {
+        Vec::new()
+    }
+    fn result() -> Value {
+        R::schema()
+    }
+}
+
+impl<F, S, P, R, Fut> Handler<(S, P)> for F
+where
+    F: Fn(Data<S>, Params<P>) -> Fut,
+    Fut: Future<Output = Result<R, JsonRpcError>>,
+    P: ParamsSchema,
+    R: JsonSchema,
+{
+    fn params() -> Vec<Value> {
+        P::schemas()
+    }
+    fn result() -> Value {
+        R::schema()
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct Info {
+    title: &'static str,
+    version: &'static str,
+}
+
+/// Named parameter or result of a method
+#[derive(Clone, Serialize)]
+struct ContentDescriptor {
+    name: String,
+    required: bool,
+    schema: Value,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Method {
+    name: String,
+    params: Vec<ContentDescriptor>,
+    result: ContentDescriptor,
+    param_structure: &'static str,
+}
+
+/// OpenRPC document describing the methods of the RPC server
+#[derive(Clone, Serialize)]
+pub(crate) struct OpenRpc {
+    openrpc: &'static str,
+    info: Info,
+    methods: Vec<Method>,
+}
+
+impl Default for OpenRpc {
+    fn default() -> Self {
+        Self {
+            openrpc: OPENRPC_VERSION,
+            info: Info {
+                title: "Forest RPC API",
+                version: env!("CARGO_PKG_VERSION"),
+            },
+            methods: Vec::new(),
+        }
+    }
+}
+
+impl OpenRpc {
+    /// Describes the method from the parameter and result types of its handler. Parameters
+    /// are given by position and named after it.
+    pub(crate) fn add_method<M, H: Handler<M>>(&mut self, name: &str, _handler: &H) {
+        let params = H::params()
+            .into_iter()
+            .enumerate()
+            .map(|(i, schema)| ContentDescriptor {
+                name: format!("p{}", i + 1),
+                required: true,
+                schema,
+            })
+            .collect();
+        self.methods.push(Method {
+            name: name.to_owned(),
+            params,
+            result: ContentDescriptor {
+                name: format!("{}Result", name),
+                required: true,
+                schema: H::result(),
+            },
+            param_structure: "by-position",
+        });
+    }
+}
+
+impl JsonSchema for OpenRpc {
+    fn schema() -> Value {
+        json!({ "type": "object", "description": "OpenRPC document" })
+    }
+}
+
+/// Returns the OpenRPC document describing the methods of the server
+pub(crate) async fn rpc_discover(data: Data<OpenRpc>) -> Result<OpenRpc, JsonRpcError> {
+    Ok(OpenRpc::clone(&data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::json::CidJson;
+
+    async fn has_obj(_: Data<()>, _: Params<(CidJson, u64)>) -> Result<bool, JsonRpcError> {
+        Ok(true)
+    }
+
+    async fn head(_: Data<()>) -> Result<Option<String>, JsonRpcError> {
+        Ok(None)
+    }
+
+    #[test]
+    fn describe_methods() {
+        let mut doc = OpenRpc::default();
+        doc.add_method("Test.HasObj", &has_obj);
+        doc.add_method("Test.Head", &head);
+        let doc = serde_json::to_value(doc).unwrap();
+
+        assert_eq!(doc["openrpc"], OPENRPC_VERSION);
+        let has_obj = &doc["methods"][0];
+        assert_eq!(has_obj["name"], "Test.HasObj");
+        assert_eq!(has_obj["paramStructure"], "by-position");
+        assert_eq!(has_obj["params"][0]["name"], "p1");
+        assert_eq!(has_obj["params"][0]["schema"], CidJson::schema());
+        assert_eq!(has_obj["params"][1]["schema"], u64::schema());
+        assert_eq!(has_obj["result"]["schema"], json!({ "type": "boolean" }));
+
+        let head = &doc["methods"][1];
+        assert_eq!(head["params"], json!([]));
+        assert_eq!(
+            head["result"]["schema"],
+            json!({ "oneOf": [{ "type": "string" }, { "type": "null" }] })
+        );
+    }
+}
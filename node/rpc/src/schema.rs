@@ -0,0 +1,463 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use address::json::AddressJson;
+use blocks::{header::json::BlockHeaderJson, tipset_json::TipsetJson, TipsetKeys};
+use cid::json::CidJson;
+use crypto::signature::json::SignatureJson;
+use forest_ipld::json::IpldJson;
+use message::{
+    signed_message::json::SignedMessageJson, unsigned_message::json::UnsignedMessageJson,
+};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use vm::actor_state::json::ActorStateJson;
+use wallet::json::KeyInfoJson;
+
+/// Type with a JSON representation which can be described by a JSON schema
+pub(crate) trait JsonSchema {
+    /// Returns the JSON schema of the serialized type
+    fn schema() -> Value;
+}
+
+/// Parameters of a method, given by position as a tuple
+pub(crate) trait ParamsSchema {
+    /// Returns the JSON schema of each parameter
+    fn schemas() -> Vec<Value>;
+}
+
+macro_rules! impl_params_schema {
+    ($($param:ident),+) => {
+        impl<$($param: JsonSchema),+> ParamsSchema for ($($param,)+) {
+            fn schemas() -> Vec<Value> {
+                vec![$(<$param as JsonSchema>::schema()),+]
+            }
+        }
+    };
+}
+
+impl_params_schema!(A);
+impl_params_schema!(A, B);
+impl_params_schema!(A, B, C);
+impl_params_schema!(A, B, C, D);
+
+/// Schema of an object with the given properties, all of which are required
+pub(crate) fn object(properties: Vec<(&str, Value)>) -> Value {
+    let required: Vec<&str> = properties.iter().map(|(name, _)| *name).collect();
+    let properties: Map<String, Value> = properties
+        .into_iter()
+        .map(|(name, schema)| (name.to_owned(), schema))
+        .collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+/// Schema of an array of the given items
+pub(crate) fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+/// Schema of a value which can also be null
+pub(crate) fn nullable(schema: Value) -> Value {
+    json!({ "oneOf": [schema, { "type": "null" }] })
+}
+
+/// Schema of a string with the given description
+pub(crate) fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+/// Schema of an unsigned integer
+pub(crate) fn unsigned() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+/// Schema of a big integer, serialized as a decimal string
+pub(crate) fn big_int() -> Value {
+    string("Decimal integer")
+}
+
+/// Schema of bytes serialized as a base64 string
+pub(crate) fn base64() -> Value {
+    string("Base64 encoded bytes")
+}
+
+/// Schema of a message receipt
+pub(crate) fn message_receipt() -> Value {
+    object(vec![
+        ("ExitCode", unsigned()),
+        ("Return", Vec::<u8>::schema()),
+        ("GasUsed", unsigned()),
+    ])
+}
+
+impl JsonSchema for () {
+    fn schema() -> Value {
+        json!({ "type": "null" })
+    }
+}
+
+impl JsonSchema for bool {
+    fn schema() -> Value {
+        json!({ "type": "boolean" })
+    }
+}
+
+impl JsonSchema for u8 {
+    fn schema() -> Value {
+        json!({ "type": "integer", "minimum": 0, "maximum": 255 })
+    }
+}
+
+impl JsonSchema for u64 {
+    fn schema() -> Value {
+        unsigned()
+    }
+}
+
+impl JsonSchema for i64 {
+    fn schema() -> Value {
+        json!({ "type": "integer" })
+    }
+}
+
+impl JsonSchema for String {
+    fn schema() -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Option<T> {
+    fn schema() -> Value {
+        nullable(T::schema())
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for Vec<T> {
+    fn schema() -> Value {
+        array(T::schema())
+    }
+}
+
+impl JsonSchema for [u8; 32] {
+    fn schema() -> Value {
+        json!({ "type": "array", "items": u8::schema(), "minItems": 32, "maxItems": 32 })
+    }
+}
+
+impl<V: JsonSchema> JsonSchema for HashMap<String, V> {
+    fn schema() -> Value {
+        json!({ "type": "object", "additionalProperties": V::schema() })
+    }
+}
+
+impl JsonSchema for CidJson {
+    fn schema() -> Value {
+        object(vec![("/", string("CID"))])
+    }
+}
+
+impl JsonSchema for AddressJson {
+    fn schema() -> Value {
+        string("Filecoin address")
+    }
+}
+
+impl JsonSchema for TipsetKeys {
+    fn schema() -> Value {
+        // Tipset keys given as parameters are not wrapped for JSON, so the CIDs are raw bytes
+        let mut schema = array(Vec::<u8>::schema());
+        schema["description"] = json!("CIDs of the tipset blocks as raw bytes");
+        schema
+    }
+}
+
+impl JsonSchema for SignatureJson {
+    fn schema() -> Value {
+        object(vec![
+            ("Type", json!({ "type": "integer", "enum": [1, 2] })),
+            ("Data", base64()),
+        ])
+    }
+}
+
+impl JsonSchema for KeyInfoJson {
+    fn schema() -> Value {
+        object(vec![
+            (
+                "Type",
+                json!({ "type": "string", "enum": ["secp256k1", "bls"] }),
+            ),
+            ("PrivateKey", base64()),
+        ])
+    }
+}
+
+impl JsonSchema for UnsignedMessageJson {
+    fn schema() -> Value {
+        object(vec![
+            ("Version", i64::schema()),
+            ("To", AddressJson::schema()),
+            ("From", AddressJson::schema()),
+            ("Nonce", unsigned()),
+            ("Value", big_int()),
+            ("GasPrice", big_int()),
+            ("GasLimit", unsigned()),
+            ("Method", unsigned()),
+            ("Params", base64()),
+        ])
+    }
+}
+
+impl JsonSchema for SignedMessageJson {
+    fn schema() -> Value {
+        object(vec![
+            ("Message", UnsignedMessageJson::schema()),
+            ("Signature", SignatureJson::schema()),
+        ])
+    }
+}
+
+impl JsonSchema for BlockHeaderJson {
+    fn schema() -> Value {
+        let vrf_proof = || object(vec![("VRFProof", base64())]);
+        object(vec![
+            ("Miner", AddressJson::schema()),
+            ("Ticket", vrf_proof()),
+            ("ElectionProof", nullable(vrf_proof())),
+            (
+                "BeaconEntries",
+                array(object(vec![("Round", unsigned()), ("Data", base64())])),
+            ),
+            (
+                "WinPoStProof",
+                array(object(vec![
+                    ("RegisteredProof", i64::schema()),
+                    ("ProofBytes", base64()),
+                ])),
+            ),
+            ("Parents", Vec::<CidJson>::schema()),
+            ("ParentWeight", big_int()),
+            ("Height", unsigned()),
+            ("ParentStateRoot", CidJson::schema()),
+            ("ParentMessageReceipts", CidJson::schema()),
+            ("Messages", CidJson::schema()),
+            ("BLSAggregate", Option::<SignatureJson>::schema()),
+            ("Timestamp", unsigned()),
+            ("BlockSig", Option::<SignatureJson>::schema()),
+            ("ForkSignaling", unsigned()),
+        ])
+    }
+}
+
+impl JsonSchema for TipsetJson {
+    fn schema() -> Value {
+        object(vec![
+            ("Blocks", Vec::<BlockHeaderJson>::schema()),
+            ("Cids", Vec::<CidJson>::schema()),
+            ("Height", unsigned()),
+        ])
+    }
+}
+
+impl JsonSchema for ActorStateJson {
+    fn schema() -> Value {
+        object(vec![
+            ("Code", CidJson::schema()),
+            ("Head", CidJson::schema()),
+            ("Nonce", unsigned()),
+            ("Balance", big_int()),
+        ])
+    }
+}
+
+impl JsonSchema for IpldJson {
+    fn schema() -> Value {
+        json!({ "description": "IPLD data, with links as CIDs and bytes base64 encoded" })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::chain_api::BlockMessages;
+    use address::Address;
+    use beacon::BeaconEntry;
+    use blocks::{BlockHeader, Ticket, Tipset};
+    use cid::{multihash::Identity, Cid};
+    use crypto::{election_proof::ElectionProof, Signature, SignatureType, Signer, VRFProof};
+    use fil_types::{PoStProof, RegisteredPoStProof};
+    use forest_ipld::Ipld;
+    use message::{json::MessageReceiptJson, MessageReceipt, SignedMessage, UnsignedMessage};
+    use serde::Serialize;
+    use std::error::Error;
+    use vm::{ActorState, ExitCode, Serialized, TokenAmount};
+    use wallet::KeyInfo;
+
+    struct DummySigner;
+    impl Signer for DummySigner {
+        fn sign_bytes(&self, _: Vec<u8>, _: &Address) -> Result<Signature, Box<dyn Error>> {
+            Ok(Signature::new_secp256k1(vec![0u8]))
+        }
+    }
+
+    /// Checks the JSON value has the shape described by the schema. Objects must have exactly
+    /// the properties their schema requires.
+    fn check(value: &Value, schema: &Value) -> Result<(), String> {
+        if let Some(variants) = schema["oneOf"].as_array() {
+            return if variants.iter().any(|s| check(value, s).is_ok()) {
+                Ok(())
+            } else {
+                Err(format!("{} matches none of {}", value, schema))
+            };
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                return Err(format!("{} is not one of {:?}", value, values));
+            }
+        }
+        match schema["type"].as_str() {
+            None => Ok(()),
+            Some("null") if value.is_null() => Ok(()),
+            Some("boolean") if value.is_boolean() => Ok(()),
+            Some("string") if value.is_string() => Ok(()),
+            Some("integer") if value.is_u64() => Ok(()),
+            Some("integer") if value.is_i64() && schema["minimum"].is_null() => Ok(()),
+            Some("array") if value.is_array() => value
+                .as_array()
+                .unwrap()
+                .iter()
+                .try_for_each(|item| check(item, &schema["items"])),
+            Some("object") if value.is_object() => {
+                let value = value.as_object().unwrap();
+                if let Some(properties) = schema["properties"].as_object() {
+                    let mut keys: Vec<&str> = value.keys().map(String::as_str).collect();
+                    let mut required: Vec<&str> = schema["required"]
+                        .as_array()
+                        .map(|r| r.iter().filter_map(Value::as_str).collect())
+                        .unwrap_or_default();
+                    keys.sort();
+                    required.sort();
+                    if keys != required {
+                        return Err(format!(
+                            "keys {:?} don't match required properties {:?}",
+                            keys, required
+                        ));
+                    }
+                    properties.iter().try_for_each(|(name, property)| {
+                        check(&value[name], property).map_err(|e| format!("{}: {}", name, e))
+                    })
+                } else if !schema["additionalProperties"].is_null() {
+                    value
+                        .values()
+                        .try_for_each(|v| check(v, &schema["additionalProperties"]))
+                } else {
+                    Ok(())
+                }
+            }
+            Some(ty) => Err(format!("{} is not of type {}", value, ty)),
+        }
+    }
+
+    /// Asserts the serialized value matches the schema of its type
+    pub(crate) fn assert_schema<T: JsonSchema + Serialize>(value: &T) {
+        let json = serde_json::to_value(value).unwrap();
+        if let Err(e) = check(&json, &T::schema()) {
+            panic!("{} doesn't match its schema: {}", json, e);
+        }
+    }
+
+    pub(crate) fn cid(data: &[u8]) -> Cid {
+        Cid::new_from_cbor(data, Identity)
+    }
+
+    pub(crate) fn unsigned_message() -> UnsignedMessage {
+        UnsignedMessage::builder()
+            .to(Address::new_id(1))
+            .from(Address::new_id(2))
+            .sequence(3)
+            .value(TokenAmount::from(4u8))
+            .params(Serialized::new(vec![5, 6]))
+            .build()
+            .unwrap()
+    }
+
+    pub(crate) fn receipt() -> MessageReceipt {
+        MessageReceipt {
+            exit_code: ExitCode::Ok,
+            return_data: Serialized::new(vec![1, 2]),
+            gas_used: 3,
+        }
+    }
+
+    /// Tipset of one block with every optional field of the header set
+    pub(crate) fn tipset() -> Tipset {
+        let header = BlockHeader::builder()
+            .parents(TipsetKeys::new(vec![cid(&[1])]))
+            .weight(10u8.into())
+            .epoch(2)
+            .beacon_entries(vec![BeaconEntry::new(1, vec![2, 3])])
+            .win_post_proof(vec![PoStProof {
+                registered_proof: RegisteredPoStProof::StackedDRGWinning2KiBV1,
+                proof_bytes: vec![4, 5],
+            }])
+            .miner_address(Address::new_id(3))
+            .messages(cid(&[2]))
+            .message_receipts(cid(&[3]))
+            .state_root(cid(&[4]))
+            .signature(Some(Signature::new_secp256k1(vec![6])))
+            .election_proof(Some(ElectionProof {
+                vrfproof: VRFProof::new(vec![7]),
+            }))
+            .timestamp(5)
+            .ticket(Ticket::new(VRFProof::new(vec![8])))
+            .bls_aggregate(Some(Signature::new_bls(vec![9])))
+            .build_and_validate()
+            .unwrap();
+        Tipset::new(vec![header]).unwrap()
+    }
+
+    #[test]
+    fn schemas_match_serialized_values() {
+        let message = unsigned_message();
+        let signed = SignedMessage::new(message.clone(), &DummySigner).unwrap();
+        let tipset = tipset();
+
+        assert_schema(&CidJson(cid(&[1])));
+        assert_schema(&AddressJson(Address::new_id(1)));
+        assert_schema(&SignatureJson(Signature::new_secp256k1(vec![1])));
+        assert_schema(&SignatureJson(Signature::new_bls(vec![1])));
+        assert_schema(&KeyInfoJson(KeyInfo::new(SignatureType::BLS, vec![1])));
+        assert_schema(&KeyInfoJson(KeyInfo::new(
+            SignatureType::Secp256k1,
+            vec![1],
+        )));
+        assert_schema(&UnsignedMessageJson(message.clone()));
+        assert_schema(&SignedMessageJson(signed.clone()));
+        assert_schema(&BlockHeaderJson(tipset.blocks()[0].clone()));
+        assert_schema(&TipsetJson(tipset));
+        assert_schema(&ActorStateJson(ActorState::new(
+            cid(&[1]),
+            cid(&[2]),
+            TokenAmount::from(3u8),
+            4,
+        )));
+        assert_schema(&IpldJson(Ipld::List(vec![Ipld::Integer(1)])));
+        assert_schema(&BlockMessages {
+            bls_msg: vec![message],
+            secp_msg: vec![signed],
+            cids: vec![cid(&[1]), cid(&[2])],
+        });
+
+        let receipt = serde_json::to_value(MessageReceiptJson(receipt())).unwrap();
+        check(&receipt, &message_receipt()).unwrap();
+    }
+
+    #[test]
+    fn check_rejects_mismatched_keys() {
+        let schema = object(vec![("A", unsigned()), ("B", unsigned())]);
+        assert!(check(&json!({ "A": 1, "B": 2 }), &schema).is_ok());
+        assert!(check(&json!({ "A": 1 }), &schema).is_err());
+        assert!(check(&json!({ "A": 1, "B": 2, "C": 3 }), &schema).is_err());
+        assert!(check(&json!({ "A": 1, "B": "2" }), &schema).is_err());
+    }
+}
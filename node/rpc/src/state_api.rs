@@ -0,0 +1,753 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::schema::{array, big_int, message_receipt, object, JsonSchema};
+use crate::State;
+use actor::{
+    market::{DealProposal, DealState},
+    miner::{MinerInfo, SectorOnChainInfo},
+    OptionalEpoch,
+};
+use address::{json::AddressJson, Address};
+use blocks::{Tipset, TipsetKeys};
+use blockstore::BlockStore;
+use cid::{json::CidJson, Cid};
+use clock::ChainEpoch;
+use forest_ipld::{json::IpldJson, Ipld};
+use interpreter::ExecutionTrace;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use libp2p::core::PeerId;
+use message::{
+    unsigned_message::{self, json::UnsignedMessageJson},
+    MessageReceipt, UnsignedMessage,
+};
+use num_bigint::BigUint;
+use serde::Serialize;
+use serde_json::{json, Value};
+use state_manager::{InvocResult, MarketDeal};
+use std::collections::HashMap;
+use std::time::Duration;
+use vm::{actor_state::json::ActorStateJson, DealID};
+use wallet::KeyStore;
+
+/// Time a StateWaitMsg call waits for the message before failing, so abandoned calls end
+const WAIT_MSG_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Raw byte and quality adjusted power
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct Power {
+    raw_byte_power: String,
+    quality_adj_power: String,
+}
+
+impl JsonSchema for Power {
+    fn schema() -> Value {
+        object(vec![
+            ("RawBytePower", big_int()),
+            ("QualityAdjPower", big_int()),
+        ])
+    }
+}
+
+impl Power {
+    fn new((raw, qa): (BigUint, BigUint)) -> Self {
+        Self {
+            raw_byte_power: raw.to_str_radix(10),
+            quality_adj_power: qa.to_str_radix(10),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct MinerPower {
+    miner_power: Power,
+    total_power: Power,
+}
+
+impl JsonSchema for MinerPower {
+    fn schema() -> Value {
+        object(vec![
+            ("MinerPower", Power::schema()),
+            ("TotalPower", Power::schema()),
+        ])
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct MinerInfoJson {
+    #[serde(with = "address::json")]
+    owner: Address,
+    #[serde(with = "address::json")]
+    worker: Address,
+    peer_id: Option<String>,
+    seal_proof_type: i64,
+    sector_size: u64,
+    window_post_partition_sectors: u64,
+}
+
+impl JsonSchema for MinerInfoJson {
+    fn schema() -> Value {
+        object(vec![
+            ("Owner", AddressJson::schema()),
+            ("Worker", AddressJson::schema()),
+            ("PeerId", Option::<String>::schema()),
+            ("SealProofType", i64::schema()),
+            ("SectorSize", u64::schema()),
+            ("WindowPostPartitionSectors", u64::schema()),
+        ])
+    }
+}
+
+impl From<MinerInfo> for MinerInfoJson {
+    fn from(info: MinerInfo) -> Self {
+        Self {
+            owner: info.owner,
+            worker: info.worker,
+            peer_id: PeerId::from_bytes(info.peer_id).ok().map(|p| p.to_string()),
+            seal_proof_type: info.seal_proof_type.into(),
+            sector_size: info.sector_size as u64,
+            window_post_partition_sectors: info.window_post_partition_sectors,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct SectorJson {
+    #[serde(rename = "SectorNumber")]
+    number: u64,
+    seal_proof: i64,
+    #[serde(rename = "SealedCID", with = "cid::json")]
+    sealed_cid: Cid,
+    #[serde(rename = "DealIDs")]
+    deal_ids: Vec<DealID>,
+    activation: ChainEpoch,
+    expiration: ChainEpoch,
+    deal_weight: String,
+    verified_deal_weight: String,
+}
+
+impl JsonSchema for SectorJson {
+    fn schema() -> Value {
+        object(vec![
+            ("SectorNumber", u64::schema()),
+            ("SealProof", i64::schema()),
+            ("SealedCID", CidJson::schema()),
+            ("DealIDs", Vec::<DealID>::schema()),
+            ("Activation", ChainEpoch::schema()),
+            ("Expiration", ChainEpoch::schema()),
+            ("DealWeight", big_int()),
+            ("VerifiedDealWeight", big_int()),
+        ])
+    }
+}
+
+impl From<SectorOnChainInfo> for SectorJson {
+    fn from(sector: SectorOnChainInfo) -> Self {
+        Self {
+            number: sector.info.sector_number,
+            seal_proof: sector.info.registered_proof.into(),
+            sealed_cid: sector.info.sealed_cid,
+            deal_ids: sector.info.deal_ids,
+            activation: sector.activation_epoch,
+            expiration: sector.info.expiration,
+            deal_weight: sector.deal_weight.to_str_radix(10),
+            verified_deal_weight: sector.verified_deal_weight.to_str_radix(10),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct DealProposalJson {
+    #[serde(rename = "PieceCID", with = "cid::json")]
+    piece_cid: Cid,
+    piece_size: u64,
+    verified_deal: bool,
+    #[serde(with = "address::json")]
+    client: Address,
+    #[serde(with = "address::json")]
+    provider: Address,
+    start_epoch: ChainEpoch,
+    end_epoch: ChainEpoch,
+    storage_price_per_epoch: String,
+    provider_collateral: String,
+    client_collateral: String,
+}
+
+impl JsonSchema for DealProposalJson {
+    fn schema() -> Value {
+        object(vec![
+            ("PieceCID", CidJson::schema()),
+            ("PieceSize", u64::schema()),
+            ("VerifiedDeal", bool::schema()),
+            ("Client", AddressJson::schema()),
+            ("Provider", AddressJson::schema()),
+            ("StartEpoch", ChainEpoch::schema()),
+            ("EndEpoch", ChainEpoch::schema()),
+            ("StoragePricePerEpoch", big_int()),
+            ("ProviderCollateral", big_int()),
+            ("ClientCollateral", big_int()),
+        ])
+    }
+}
+
+impl From<DealProposal> for DealProposalJson {
+    fn from(proposal: DealProposal) -> Self {
+        Self {
+            piece_cid: proposal.piece_cid,
+            piece_size: proposal.piece_size.0,
+            verified_deal: proposal.verified_deal,
+            client: proposal.client,
+            provider: proposal.provider,
+            start_epoch: proposal.start_epoch,
+            end_epoch: proposal.end_epoch,
+            storage_price_per_epoch: proposal.storage_price_per_epoch.to_str_radix(10),
+            provider_collateral: proposal.provider_collateral.to_str_radix(10),
+            client_collateral: proposal.client_collateral.to_str_radix(10),
+        }
+    }
+}
+
+/// Deal state epochs, where -1 indicates the epoch is not set
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct DealStateJson {
+    sector_start_epoch: i64,
+    last_updated_epoch: i64,
+    slash_epoch: i64,
+}
+
+impl JsonSchema for DealStateJson {
+    fn schema() -> Value {
+        object(vec![
+            ("SectorStartEpoch", i64::schema()),
+            ("LastUpdatedEpoch", i64::schema()),
+            ("SlashEpoch", i64::schema()),
+        ])
+    }
+}
+
+impl From<DealState> for DealStateJson {
+    fn from(state: DealState) -> Self {
+        let epoch = |e: OptionalEpoch| e.0.map(|e| e as i64).unwrap_or(-1);
+        Self {
+            sector_start_epoch: epoch(state.sector_start_epoch),
+            last_updated_epoch: epoch(state.last_updated_epoch),
+            slash_epoch: epoch(state.slash_epoch),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct MarketDealJson {
+    proposal: DealProposalJson,
+    state: DealStateJson,
+}
+
+impl JsonSchema for MarketDealJson {
+    fn schema() -> Value {
+        object(vec![
+            ("Proposal", DealProposalJson::schema()),
+            ("State", DealStateJson::schema()),
+        ])
+    }
+}
+
+impl From<MarketDeal> for MarketDealJson {
+    fn from(deal: MarketDeal) -> Self {
+        Self {
+            proposal: deal.proposal.into(),
+            state: deal.state.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct ActorReadState {
+    balance: String,
+    state: IpldJson,
+}
+
+impl JsonSchema for ActorReadState {
+    fn schema() -> Value {
+        object(vec![("Balance", big_int()), ("State", IpldJson::schema())])
+    }
+}
+
+/// Gas charged for an operation during the execution of a message
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct GasChargeJson {
+    name: &'static str,
+    total_gas: i64,
+}
+
+impl JsonSchema for GasChargeJson {
+    fn schema() -> Value {
+        object(vec![
+            ("Name", String::schema()),
+            ("TotalGas", i64::schema()),
+        ])
+    }
+}
+
+/// Trace of the execution of a message, with the messages it sent in turn
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct ExecutionTraceJson {
+    #[serde(with = "unsigned_message::json")]
+    msg: UnsignedMessage,
+    #[serde(with = "message::json")]
+    msg_rct: MessageReceipt,
+    error: String,
+    gas_charges: Vec<GasChargeJson>,
+    subcalls: Vec<ExecutionTraceJson>,
+}
+
+impl JsonSchema for ExecutionTraceJson {
+    fn schema() -> Value {
+        // Subcalls are traces as well, which can't be expanded without recursing endlessly
+        let subcall = json!({ "type": "object", "description": "Execution trace" });
+        object(vec![
+            ("Msg", UnsignedMessageJson::schema()),
+            ("MsgRct", message_receipt()),
+            ("Error", String::schema()),
+            ("GasCharges", Vec::<GasChargeJson>::schema()),
+            ("Subcalls", array(subcall)),
+        ])
+    }
+}
+
+impl From<ExecutionTrace> for ExecutionTraceJson {
+    fn from(trace: ExecutionTrace) -> Self {
+        Self {
+            msg: trace.msg,
+            msg_rct: trace.msg_rct,
+            error: trace.error.unwrap_or_default(),
+            gas_charges: trace
+                .gas_charges
+                .into_iter()
+                .map(|charge| GasChargeJson {
+                    name: charge.name,
+                    total_gas: charge.total_gas,
+                })
+                .collect(),
+            subcalls: trace.subcalls.into_iter().map(From::from).collect(),
+        }
+    }
+}
+
+/// Result of executing a message outside of a block
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct InvocResultJson {
+    #[serde(with = "unsigned_message::json")]
+    msg: UnsignedMessage,
+    #[serde(with = "message::json")]
+    msg_rct: MessageReceipt,
+    execution_trace: Option<ExecutionTraceJson>,
+    error: String,
+}
+
+impl JsonSchema for InvocResultJson {
+    fn schema() -> Value {
+        object(vec![
+            ("Msg", UnsignedMessageJson::schema()),
+            ("MsgRct", message_receipt()),
+            ("ExecutionTrace", Option::<ExecutionTraceJson>::schema()),
+            ("Error", String::schema()),
+        ])
+    }
+}
+
+impl From<InvocResult> for InvocResultJson {
+    fn from(res: InvocResult) -> Self {
+        Self {
+            msg: res.msg,
+            msg_rct: res.msg_rct,
+            execution_trace: res.exec_trace.map(From::from),
+            error: res.error.unwrap_or_default(),
+        }
+    }
+}
+
+/// State root resulting from applying messages on top of a tipset, along with the result of
+/// each message
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct ComputeStateOutput {
+    #[serde(with = "cid::json")]
+    root: Cid,
+    trace: Vec<InvocResultJson>,
+}
+
+impl JsonSchema for ComputeStateOutput {
+    fn schema() -> Value {
+        object(vec![
+            ("Root", CidJson::schema()),
+            ("Trace", Vec::<InvocResultJson>::schema()),
+        ])
+    }
+}
+
+/// Receipt of a message along with the tipset it was executed in
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct MsgLookup {
+    #[serde(with = "message::json")]
+    receipt: MessageReceipt,
+    #[serde(rename = "TipSet", with = "blocks::tipset_keys_json")]
+    tipset: TipsetKeys,
+    height: ChainEpoch,
+}
+
+impl JsonSchema for MsgLookup {
+    fn schema() -> Value {
+        object(vec![
+            ("Receipt", message_receipt()),
+            ("TipSet", Vec::<CidJson>::schema()),
+            ("Height", ChainEpoch::schema()),
+        ])
+    }
+}
+
+impl MsgLookup {
+    fn new(ts: Tipset, receipt: MessageReceipt) -> Self {
+        Self {
+            receipt,
+            tipset: ts.key().clone(),
+            height: ts.epoch(),
+        }
+    }
+}
+
+/// Returns the state root of the tipset with the given keys
+fn tipset_state_root<DB: BlockStore>(db: &DB, tsk: &TipsetKeys) -> Result<Cid, JsonRpcError> {
+    let ts = chain::tipset_from_keys(db, tsk)?;
+    Ok(ts.parent_state().clone())
+}
+
+/// Returns the actor at the address in the state of the tipset
+pub(crate) async fn state_get_actor<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, TipsetKeys)>,
+) -> Result<Option<ActorStateJson>, JsonRpcError> {
+    let (AddressJson(addr), tsk) = params;
+    let state_root = tipset_state_root(data.store.as_ref(), &tsk)?;
+    let actor = data.state_manager.get_actor(&addr, &state_root)?;
+    Ok(actor.map(ActorStateJson))
+}
+
+/// Returns the ID address of the address
+pub(crate) async fn state_lookup_id<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, TipsetKeys)>,
+) -> Result<AddressJson, JsonRpcError> {
+    let (AddressJson(addr), tsk) = params;
+    let state_root = tipset_state_root(data.store.as_ref(), &tsk)?;
+    let id_addr = data.state_manager.lookup_id(&addr, &state_root)?;
+    Ok(AddressJson(id_addr))
+}
+
+/// Returns the public key address of the account actor at the address
+pub(crate) async fn state_account_key<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, TipsetKeys)>,
+) -> Result<AddressJson, JsonRpcError> {
+    let (AddressJson(addr), tsk) = params;
+    let state_root = tipset_state_root(data.store.as_ref(), &tsk)?;
+    let key_addr = data.state_manager.resolve_to_key_addr(&addr, &state_root)?;
+    Ok(AddressJson(key_addr))
+}
+
+/// Returns the power claimed by the miner along with the total power of the network
+pub(crate) async fn state_miner_power<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, TipsetKeys)>,
+) -> Result<MinerPower, JsonRpcError> {
+    let (AddressJson(addr), tsk) = params;
+    let state_root = tipset_state_root(data.store.as_ref(), &tsk)?;
+    let id_addr = data.state_manager.lookup_id(&addr, &state_root)?;
+    let miner_power = data.state_manager.get_power(&state_root, &id_addr)?;
+    let total_power = data.state_manager.get_network_power(&state_root)?;
+    Ok(MinerPower {
+        miner_power: Power::new(miner_power),
+        total_power: Power::new(total_power),
+    })
+}
+
+/// Returns the static information of the miner
+pub(crate) async fn state_miner_info<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, TipsetKeys)>,
+) -> Result<MinerInfoJson, JsonRpcError> {
+    let (AddressJson(addr), tsk) = params;
+    let state_root = tipset_state_root(data.store.as_ref(), &tsk)?;
+    let info = data.state_manager.get_miner_info(&state_root, &addr)?;
+    Ok(info.into())
+}
+
+/// Returns all sectors committed by the miner
+pub(crate) async fn state_miner_sectors<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, TipsetKeys)>,
+) -> Result<Vec<SectorJson>, JsonRpcError> {
+    let (AddressJson(addr), tsk) = params;
+    let state_root = tipset_state_root(data.store.as_ref(), &tsk)?;
+    let sectors = data.state_manager.get_miner_sectors(&state_root, &addr)?;
+    Ok(sectors.into_iter().map(SectorJson::from).collect())
+}
+
+/// Returns all deals tracked by the storage market actor, keyed by deal ID
+pub(crate) async fn state_market_deals<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(TipsetKeys,)>,
+) -> Result<HashMap<String, MarketDealJson>, JsonRpcError> {
+    let (tsk,) = params;
+    let state_root = tipset_state_root(data.store.as_ref(), &tsk)?;
+    let deals = data.state_manager.get_market_deals(&state_root)?;
+    Ok(deals
+        .into_iter()
+        .map(|(id, deal)| (id.to_string(), deal.into()))
+        .collect())
+}
+
+/// Returns the balance and decoded state of the actor at the address
+pub(crate) async fn state_read_state<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, TipsetKeys)>,
+) -> Result<ActorReadState, JsonRpcError> {
+    let (AddressJson(addr), tsk) = params;
+    let state_root = tipset_state_root(data.store.as_ref(), &tsk)?;
+    let actor = data
+        .state_manager
+        .get_actor(&addr, &state_root)?
+        .ok_or("actor not found")?;
+    let state: Ipld = data.state_manager.load_actor_state(&addr, &state_root)?;
+    Ok(ActorReadState {
+        balance: actor.balance.to_str_radix(10),
+        state: IpldJson(state),
+    })
+}
+
+/// Returns the name of the network from the init actor
+pub(crate) async fn state_network_name<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(TipsetKeys,)>,
+) -> Result<String, JsonRpcError> {
+    let (tsk,) = params;
+    let state_root = tipset_state_root(data.store.as_ref(), &tsk)?;
+    Ok(data.state_manager.get_network_name(&state_root)?)
+}
+
+/// Executes the message on top of the state of the tipset without persisting any changes.
+/// The heaviest tipset is used if no tipset keys are given.
+pub(crate) async fn state_call<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(UnsignedMessageJson, TipsetKeys)>,
+) -> Result<InvocResultJson, JsonRpcError> {
+    let (UnsignedMessageJson(mut msg), tsk) = params;
+    let ts = if tsk.cids().is_empty() {
+        None
+    } else {
+        Some(chain::tipset_from_keys(data.store.as_ref(), &tsk)?)
+    };
+    let res = data.state_manager.call(&mut msg, ts)?;
+    Ok(res.into())
+}
+
+/// Re-executes a message included in the tipset and returns its receipt and actor error.
+/// The tipset which included the message is searched for if no tipset keys are given.
+pub(crate) async fn state_replay<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(TipsetKeys, CidJson)>,
+) -> Result<InvocResultJson, JsonRpcError> {
+    let (tsk, CidJson(mcid)) = params;
+    let tsk = if tsk.cids().is_empty() {
+        let (ts, _) = data
+            .state_manager
+            .search_for_message(&mcid)?
+            .ok_or("message not found on chain")?;
+        ts.parents().clone()
+    } else {
+        tsk
+    };
+    let ts = chain::tipset_from_keys(data.store.as_ref(), &tsk)?;
+    let res = data.state_manager.replay(&ts, &mcid)?;
+    Ok(res.into())
+}
+
+/// Applies the messages on top of the state of the tipset at the given epoch, returning the
+/// resulting state root and the execution trace of each message
+pub(crate) async fn state_compute<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(ChainEpoch, Vec<UnsignedMessageJson>, TipsetKeys)>,
+) -> Result<ComputeStateOutput, JsonRpcError> {
+    let (epoch, msgs, tsk) = params;
+    let msgs: Vec<UnsignedMessage> = msgs.into_iter().map(|msg| msg.0).collect();
+    let ts = chain::tipset_from_keys(data.store.as_ref(), &tsk)?;
+    let (root, results) = data.state_manager.compute_state(epoch, &msgs, &ts).await?;
+    Ok(ComputeStateOutput {
+        root,
+        trace: results.into_iter().map(From::from).collect(),
+    })
+}
+
+/// Searches the chain for the tipset the message was executed in
+pub(crate) async fn state_search_msg<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(CidJson,)>,
+) -> Result<Option<MsgLookup>, JsonRpcError> {
+    let (CidJson(mcid),) = params;
+    let res = data.state_manager.search_for_message(&mcid)?;
+    Ok(res.map(|(ts, receipt)| MsgLookup::new(ts, receipt)))
+}
+
+/// Waits until the message is executed with the given amount of tipsets on top of it
+pub(crate) async fn state_wait_msg<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(CidJson, u64)>,
+) -> Result<MsgLookup, JsonRpcError> {
+    let (CidJson(mcid), confidence) = params;
+    let (ts, receipt) = data
+        .state_manager
+        .wait_for_message(
+            data.head_changes.subscribe().await,
+            &mcid,
+            confidence,
+            WAIT_MSG_TIMEOUT,
+        )
+        .await?;
+    Ok(MsgLookup::new(ts, receipt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::tests::{assert_schema, cid, receipt, tipset, unsigned_message};
+
+    #[test]
+    fn schemas_match_serialized_values() {
+        let power = || Power::new((BigUint::from(1u8), BigUint::from(2u8)));
+        assert_schema(&MinerPower {
+            miner_power: power(),
+            total_power: power(),
+        });
+        for peer_id in [None, Some(PeerId::random().to_string())].iter().cloned() {
+            assert_schema(&MinerInfoJson {
+                owner: Address::new_id(1),
+                worker: Address::new_id(2),
+                peer_id,
+                seal_proof_type: 3,
+                sector_size: 2048,
+                window_post_partition_sectors: 4,
+            });
+        }
+        assert_schema(&SectorJson {
+            number: 1,
+            seal_proof: 2,
+            sealed_cid: cid(&[1]),
+            deal_ids: vec![3, 4],
+            activation: 5,
+            expiration: 6,
+            deal_weight: "7".to_owned(),
+            verified_deal_weight: "8".to_owned(),
+        });
+        assert_schema(&MarketDealJson {
+            proposal: DealProposalJson {
+                piece_cid: cid(&[1]),
+                piece_size: 2048,
+                verified_deal: true,
+                client: Address::new_id(1),
+                provider: Address::new_id(2),
+                start_epoch: 3,
+                end_epoch: 4,
+                storage_price_per_epoch: "5".to_owned(),
+                provider_collateral: "6".to_owned(),
+                client_collateral: "7".to_owned(),
+            },
+            state: DealStateJson {
+                sector_start_epoch: 1,
+                last_updated_epoch: -1,
+                slash_epoch: -1,
+            },
+        });
+        assert_schema(&ActorReadState {
+            balance: "1".to_owned(),
+            state: IpldJson(Ipld::List(vec![
+                Ipld::Integer(1),
+                Ipld::String("value".to_owned()),
+            ])),
+        });
+
+        let trace = |subcalls| ExecutionTraceJson {
+            msg: unsigned_message(),
+            msg_rct: receipt(),
+            error: String::new(),
+            gas_charges: vec![GasChargeJson {
+                name: "OnChainMessage",
+                total_gas: 1,
+            }],
+            subcalls,
+        };
+        let result = |execution_trace| InvocResultJson {
+            msg: unsigned_message(),
+            msg_rct: receipt(),
+            execution_trace,
+            error: String::new(),
+        };
+        assert_schema(&ComputeStateOutput {
+            root: cid(&[1]),
+            trace: vec![result(None), result(Some(trace(vec![trace(vec![])])))],
+        });
+        assert_schema(&MsgLookup::new(tipset(), receipt()));
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::schema::{nullable, object, JsonSchema};
+use crate::State;
+use blocks::tipset_json::TipsetJson;
+use blockstore::BlockStore;
+use chain_sync::SyncStatus;
+use cid::json::CidJson;
+use clock::ChainEpoch;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wallet::KeyStore;
+
+/// Reason given for blocks marked as bad through the RPC
+const MARKED_BAD_REASON: &str = "manually marked bad";
+
+/// Progress of the syncer towards its current target
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct ActiveSync {
+    base: Option<TipsetJson>,
+    target: Option<TipsetJson>,
+    stage: String,
+    height: ChainEpoch,
+    /// Unix timestamps in seconds
+    start: Option<u64>,
+    end: Option<u64>,
+    message: String,
+}
+
+impl JsonSchema for ActiveSync {
+    fn schema() -> Value {
+        let timestamp = || nullable(json!({ "type": "integer", "description": "Unix timestamp" }));
+        object(vec![
+            ("Base", Option::<TipsetJson>::schema()),
+            ("Target", Option::<TipsetJson>::schema()),
+            ("Stage", String::schema()),
+            ("Height", ChainEpoch::schema()),
+            ("Start", timestamp()),
+            ("End", timestamp()),
+            ("Message", String::schema()),
+        ])
+    }
+}
+
+impl From<SyncStatus> for ActiveSync {
+    fn from(status: SyncStatus) -> Self {
+        let timestamp = |t: Option<SystemTime>| {
+            t.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+        };
+        Self {
+            base: status.base.map(|ts| TipsetJson(ts.as_ref().clone())),
+            target: status.target.map(|ts| TipsetJson(ts.as_ref().clone())),
+            stage: format!("{:?}", status.stage),
+            height: status.epoch,
+            start: timestamp(status.start),
+            end: timestamp(status.end),
+            message: status.message,
+        }
+    }
+}
+
+/// Sync state of the node
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct RPCSyncState {
+    active_syncs: Vec<ActiveSync>,
+}
+
+impl JsonSchema for RPCSyncState {
+    fn schema() -> Value {
+        object(vec![("ActiveSyncs", Vec::<ActiveSync>::schema())])
+    }
+}
+
+/// Returns the stage of the syncer and its progress towards the current target
+pub(crate) async fn sync_state<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+) -> Result<RPCSyncState, JsonRpcError> {
+    let status = data.sync_state.read().await.clone();
+    Ok(RPCSyncState {
+        active_syncs: vec![status.into()],
+    })
+}
+
+/// Returns the reason the block was marked as bad, or an empty string if it isn't
+pub(crate) async fn sync_check_bad<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(CidJson,)>,
+) -> Result<String, JsonRpcError> {
+    let (CidJson(cid),) = params;
+    Ok(data.bad_blocks.peek(&cid).await.unwrap_or_default())
+}
+
+/// Marks the block as bad, so the syncer refuses chains containing it
+pub(crate) async fn sync_mark_bad<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(CidJson,)>,
+) -> Result<(), JsonRpcError> {
+    let (CidJson(cid),) = params;
+    data.bad_blocks.put(cid, MARKED_BAD_REASON.to_owned()).await;
+    Ok(())
+}
+
+/// Removes the block from the bad blocks, so chains containing it can be synced again
+pub(crate) async fn sync_unmark_bad<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(CidJson,)>,
+) -> Result<(), JsonRpcError> {
+    let (CidJson(cid),) = params;
+    data.bad_blocks.remove(&cid).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::tests::{assert_schema, tipset};
+    use chain_sync::SyncState;
+    use std::sync::Arc;
+
+    #[test]
+    fn sync_state_schema() {
+        let ts = Arc::new(tipset());
+        let status = SyncStatus {
+            stage: SyncState::Catchup,
+            base: Some(ts.clone()),
+            target: Some(ts),
+            epoch: 1,
+            start: Some(SystemTime::now()),
+            end: None,
+            message: String::new(),
+        };
+        assert_schema(&RPCSyncState {
+            active_syncs: vec![SyncStatus::default().into(), status.into()],
+        });
+    }
+}
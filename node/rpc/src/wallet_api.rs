@@ -0,0 +1,177 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::State;
+use address::json::AddressJson;
+use blockstore::BlockStore;
+use crypto::{signature::json::SignatureJson, SignatureType};
+use encoding::Cbor;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use message::{
+    signed_message::json::SignedMessageJson, unsigned_message::json::UnsignedMessageJson, Message,
+    SignedMessage,
+};
+use wallet::{json::KeyInfoJson, KeyStore};
+
+/// Parses the key type of a wallet address, either bls or secp256k1
+fn parse_sig_type(sig_type: &str) -> Result<SignatureType, JsonRpcError> {
+    match sig_type {
+        "bls" => Ok(SignatureType::BLS),
+        "secp256k1" => Ok(SignatureType::Secp256k1),
+        _ => Err(format!("unknown key type: {}", sig_type).into()),
+    }
+}
+
+/// Generates a new key of the given type and returns its address
+pub(crate) async fn wallet_new<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(String,)>,
+) -> Result<AddressJson, JsonRpcError> {
+    let (sig_type,) = params;
+    let sig_type = parse_sig_type(&sig_type)?;
+    let addr = data.wallet.write().await.generate_addr(sig_type)?;
+    Ok(AddressJson(addr))
+}
+
+/// Returns all addresses in the wallet
+pub(crate) async fn wallet_list<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+) -> Result<Vec<AddressJson>, JsonRpcError> {
+    let addrs = data.wallet.read().await.list_addrs()?;
+    Ok(addrs.into_iter().map(AddressJson).collect())
+}
+
+/// Returns true if the wallet holds the key of the address
+pub(crate) async fn wallet_has<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson,)>,
+) -> Result<bool, JsonRpcError> {
+    let (AddressJson(addr),) = params;
+    Ok(data.wallet.write().await.has_key(&addr))
+}
+
+/// Returns the balance of the address in the state of the heaviest tipset
+pub(crate) async fn wallet_balance<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson,)>,
+) -> Result<String, JsonRpcError> {
+    let (AddressJson(addr),) = params;
+    let heaviest =
+        chain::get_heaviest_tipset(data.store.as_ref())?.ok_or("can't find heaviest tipset")?;
+    let balance = data
+        .state_manager
+        .get_actor(&addr, heaviest.parent_state())?
+        .map(|actor| actor.balance.to_str_radix(10))
+        .unwrap_or_else(|| "0".to_owned());
+    Ok(balance)
+}
+
+/// Signs the base64 encoded data with the key of the address
+pub(crate) async fn wallet_sign<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, String)>,
+) -> Result<SignatureJson, JsonRpcError> {
+    let (AddressJson(addr), msg) = params;
+    let msg = base64::decode(msg)?;
+    let sig = data.wallet.write().await.sign(&addr, &msg)?;
+    Ok(SignatureJson(sig))
+}
+
+/// Signs the message with the key of its sender
+pub(crate) async fn wallet_sign_message<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, UnsignedMessageJson)>,
+) -> Result<SignedMessageJson, JsonRpcError> {
+    let (AddressJson(addr), UnsignedMessageJson(msg)) = params;
+    if msg.from() != &addr {
+        return Err("message must be signed by its sender".into());
+    }
+    let sig = data
+        .wallet
+        .write()
+        .await
+        .sign(&addr, &msg.marshal_cbor()?)?;
+    let smsg = SignedMessage::new_from_parts(msg, sig)?;
+    Ok(SignedMessageJson(smsg))
+}
+
+/// Returns true if the signature over the base64 encoded data was made by the address
+pub(crate) async fn wallet_verify<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    _data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson, String, SignatureJson)>,
+) -> Result<bool, JsonRpcError> {
+    let (AddressJson(addr), msg, SignatureJson(sig)) = params;
+    let msg = base64::decode(msg)?;
+    Ok(sig.verify(&msg, &addr).is_ok())
+}
+
+/// Returns the private key of the address
+pub(crate) async fn wallet_export<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson,)>,
+) -> Result<KeyInfoJson, JsonRpcError> {
+    let (AddressJson(addr),) = params;
+    let key_info = data.wallet.write().await.export(&addr)?;
+    Ok(KeyInfoJson(key_info))
+}
+
+/// Adds the private key to the wallet and returns its address
+pub(crate) async fn wallet_import<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(KeyInfoJson,)>,
+) -> Result<AddressJson, JsonRpcError> {
+    let (KeyInfoJson(key_info),) = params;
+    let addr = data.wallet.write().await.import(key_info)?;
+    Ok(AddressJson(addr))
+}
+
+/// Returns the default address of the wallet
+pub(crate) async fn wallet_default_address<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+) -> Result<AddressJson, JsonRpcError> {
+    let addr = data.wallet.read().await.get_default()?;
+    Ok(AddressJson(addr))
+}
+
+/// Sets the default address of the wallet
+pub(crate) async fn wallet_set_default<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<State<DB, KS>>,
+    Params(params): Params<(AddressJson,)>,
+) -> Result<(), JsonRpcError> {
+    let (AddressJson(addr),) = params;
+    data.wallet.write().await.set_default(addr)?;
+    Ok(())
+}
@@ -0,0 +1,386 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::{auth, HeadChanges};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task;
+use async_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    Error as WsError, Message,
+};
+use blocks::tipset_json::TipsetJsonRef;
+use blockstore::BlockStore;
+use chain::HeadChange;
+use flo_stream::Subscriber;
+use futures::channel::mpsc::{channel, Sender};
+use futures::{SinkExt, StreamExt};
+use jsonrpc_v2::{MapRouter, RequestObject, Server};
+use log::{debug, error, warn};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Method subscribing to the head changes of the chain
+pub const CHAIN_NOTIFY: &str = "Filecoin.ChainNotify";
+/// Method of the notifications pushing values to a subscription channel, as in Lotus
+const CHANNEL_VAL: &str = "xrpc.ch.val";
+/// Amount of messages queued to be written to a connection. ChainNotify subscriptions of
+/// connections which don't keep up with their queue are closed.
+const OUTGOING_BUFFER: usize = 64;
+
+/// JSON-RPC error code of requests which are not valid JSON
+const PARSE_ERROR: i64 = -32700;
+/// JSON-RPC error code of requests which are not valid request objects
+const INVALID_REQUEST: i64 = -32600;
+/// JSON-RPC error code of failed server side operations, such as authorization
+const SERVER_ERROR: i64 = -32000;
+
+/// Head change event pushed to ChainNotify subscribers
+#[derive(Serialize)]
+struct HeadChangeJson<'a> {
+    #[serde(rename = "Type")]
+    change_type: &'static str,
+    #[serde(rename = "Val")]
+    val: TipsetJsonRef<'a>,
+}
+
+impl<'a> From<&'a HeadChange> for HeadChangeJson<'a> {
+    fn from(change: &'a HeadChange) -> Self {
+        let (change_type, ts) = match change {
+            HeadChange::Current(ts) => ("current", ts),
+            HeadChange::Apply(ts) => ("apply", ts),
+            HeadChange::Revert(ts) => ("revert", ts),
+        };
+        Self {
+            change_type,
+            val: TipsetJsonRef(ts.as_ref()),
+        }
+    }
+}
+
+/// State of the WebSocket server handling RPC requests and subscriptions
+struct WsServer<DB> {
+    store: Arc<DB>,
+    rpc: Arc<Server<MapRouter>>,
+    /// Secret API tokens are signed with
+    jwt_secret: Vec<u8>,
+    /// Head changes of the chain store pushed to the ChainNotify subscriptions
+    head_changes: Arc<HeadChanges>,
+    /// Identifier of the next subscription channel
+    next_chan: AtomicU64,
+}
+
+impl<DB> WsServer<DB>
+where
+    DB: BlockStore + Send + Sync + 'static,
+{
+    /// Handles a request received on the connection, returning the response to send back
+    /// if it isn't pushed on the connection already
+    async fn handle_call(
+        &self,
+        text: &str,
+        token: Option<&str>,
+        tx: &Sender<Message>,
+    ) -> Option<Value> {
+        let call: Value = match serde_json::from_str(text) {
+            Ok(call) => call,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+        let id = call["id"].clone();
+
+        // Check the bearer token grants access to the method before handling the call
+        let method = call["method"].as_str().unwrap_or_default();
+        if let Err(e) = auth::check_access(method, token, &self.jwt_secret) {
+            return Some(error_response(id, SERVER_ERROR, e.to_string()));
+        }
+        if method == CHAIN_NOTIFY {
+            return self.chain_notify(id, tx.clone()).await;
+        }
+
+        let call: RequestObject = match serde_json::from_value(call) {
+            Ok(call) => call,
+            Err(e) => return Some(error_response(id, INVALID_REQUEST, e.to_string())),
+        };
+        serde_json::to_value(self.rpc.handle(call).await).ok()
+    }
+
+    /// Subscribes the connection to head changes. The subscription channel is returned first,
+    /// then the current head is pushed followed by every change applied to it.
+    async fn chain_notify(&self, id: Value, mut tx: Sender<Message>) -> Option<Value> {
+        let current = match chain::get_heaviest_tipset(self.store.as_ref()) {
+            Ok(current) => current,
+            Err(e) => return Some(error_response(id, SERVER_ERROR, e.to_string())),
+        };
+        let chan = self.next_chan.fetch_add(1, Ordering::Relaxed);
+        let subscriber = self.head_changes.subscribe().await;
+
+        let res = json!({ "jsonrpc": "2.0", "id": id, "result": chan });
+        if tx.send(Message::Text(res.to_string())).await.is_err() {
+            return None;
+        }
+        task::spawn(push_head_changes(
+            chan,
+            current.map(|ts| HeadChange::Current(Arc::new(ts))),
+            subscriber,
+            tx,
+        ));
+        None
+    }
+}
+
+/// Pushes head changes to a subscription channel until the connection is closed or the
+/// outgoing queue of the connection is full
+async fn push_head_changes(
+    chan: u64,
+    current: Option<HeadChange>,
+    mut subscriber: Subscriber<HeadChange>,
+    mut tx: Sender<Message>,
+) {
+    if let Some(current) = current {
+        if tx.try_send(notification(chan, &current)).is_err() {
+            debug!("Closed ChainNotify subscription {}", chan);
+            return;
+        }
+    }
+    while let Some(change) = subscriber.next().await {
+        if let Err(e) = tx.try_send(notification(chan, &change)) {
+            if e.is_full() {
+                warn!(
+                    "Closed ChainNotify subscription {} of slow connection",
+                    chan
+                );
+            } else {
+                debug!("Closed ChainNotify subscription {}", chan);
+            }
+            return;
+        }
+    }
+}
+
+/// Notification pushing a head change to a subscription channel
+fn notification(chan: u64, change: &HeadChange) -> Message {
+    let changes = [HeadChangeJson::from(change)];
+    let notif = json!({ "jsonrpc": "2.0", "method": CHANNEL_VAL, "params": [chan, changes] });
+    Message::Text(notif.to_string())
+}
+
+/// JSON-RPC response of a failed request
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Serves requests of a WebSocket connection until it is closed
+async fn handle_connection<DB>(server: Arc<WsServer<DB>>, stream: TcpStream) -> Result<(), WsError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+{
+    // The API token is given once, in the handshake of the connection
+    let mut token = None;
+    let callback = |req: &Request, res: Response| -> Result<Response, ErrorResponse> {
+        token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.trim_start_matches("Bearer ").trim().to_owned());
+        Ok(res)
+    };
+    let ws_stream = async_tungstenite::accept_hdr_async(stream, callback).await?;
+    let (mut sink, mut stream) = ws_stream.split();
+
+    // Responses and subscription notifications are all written to the connection by one task
+    let (mut tx, mut rx) = channel(OUTGOING_BUFFER);
+    let writer = task::spawn(async move {
+        while let Some(msg) = rx.next().await {
+            if let Err(e) = sink.send(msg).await {
+                debug!("Failed to write to WebSocket connection: {}", e);
+                break;
+            }
+        }
+    });
+
+    let mut res = Ok(());
+    while let Some(msg) = stream.next().await {
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(e) => {
+                res = Err(e);
+                break;
+            }
+        };
+        match msg {
+            Message::Text(text) => {
+                // Calls are handled concurrently, so long running calls don't block the
+                // other calls of the connection
+                let server = Arc::clone(&server);
+                let token = token.clone();
+                let mut tx = tx.clone();
+                task::spawn(async move {
+                    if let Some(res) = server.handle_call(&text, token.as_deref(), &tx).await {
+                        if tx.send(Message::Text(res.to_string())).await.is_err() {
+                            debug!("WebSocket connection closed before response was sent");
+                        }
+                    }
+                });
+            }
+            Message::Close(_) => break,
+            _ => (),
+        }
+    }
+
+    // Closing the channel ends the writer and the subscriptions of the connection, responses
+    // of calls still being handled are discarded
+    tx.close_channel();
+    writer.await;
+    res
+}
+
+/// Starts the WebSocket RPC server on the given address. Requests are handled by the same
+/// methods as the http server, with the addition of subscriptions pushed by the server.
+pub(crate) async fn start_ws<DB>(
+    store: Arc<DB>,
+    rpc: Arc<Server<MapRouter>>,
+    jwt_secret: Vec<u8>,
+    head_changes: Arc<HeadChanges>,
+    ws_endpoint: SocketAddr,
+) where
+    DB: BlockStore + Send + Sync + 'static,
+{
+    let server = Arc::new(WsServer {
+        store,
+        rpc,
+        jwt_secret,
+        head_changes,
+        next_chan: AtomicU64::new(0),
+    });
+
+    let listener = match TcpListener::bind(ws_endpoint).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(
+                "Failed to bind WebSocket RPC server to {}: {}",
+                ws_endpoint, e
+            );
+            return;
+        }
+    };
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        match stream {
+            Ok(stream) => {
+                let server = Arc::clone(&server);
+                task::spawn(async move {
+                    if let Err(e) = handle_connection(server, stream).await {
+                        debug!("WebSocket connection closed with error: {}", e);
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to accept WebSocket connection: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use address::Address;
+    use blocks::{BlockHeader, Tipset};
+    use db::MemoryDB;
+    use flo_stream::{MessagePublisher, Publisher};
+
+    fn tipset() -> Arc<Tipset> {
+        let header = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build_and_validate()
+            .unwrap();
+        Arc::new(Tipset::new(vec![header]).unwrap())
+    }
+
+    fn json_message(msg: Message) -> Value {
+        match msg {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            msg => panic!("Unexpected message: {:?}", msg),
+        }
+    }
+
+    #[test]
+    fn head_change_notification() {
+        let ts = tipset();
+        let notif = json_message(notification(3, &HeadChange::Apply(Arc::clone(&ts))));
+        assert_eq!(notif["method"], CHANNEL_VAL);
+        assert_eq!(notif["params"][0], 3);
+
+        let change = &notif["params"][1][0];
+        assert_eq!(change["Type"], "apply");
+        assert_eq!(
+            change["Val"],
+            serde_json::to_value(TipsetJsonRef(ts.as_ref())).unwrap()
+        );
+    }
+
+    #[test]
+    fn chain_notify_call() {
+        let mut publisher = Publisher::new(10);
+        let server = WsServer {
+            store: Arc::new(MemoryDB::default()),
+            rpc: Server::new().finish(),
+            jwt_secret: auth::generate_secret(),
+            head_changes: HeadChanges::new(publisher.subscribe()),
+            next_chan: AtomicU64::new(0),
+        };
+        let (tx, mut rx) = channel(OUTGOING_BUFFER);
+
+        task::block_on(async {
+            let res = server.handle_call("{", None, &tx).await.unwrap();
+            assert_eq!(res["error"]["code"], PARSE_ERROR);
+
+            // Calls without a token granting access to the method are rejected
+            let call =
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "Filecoin.MpoolPush", "params": [] });
+            let res = server
+                .handle_call(&call.to_string(), None, &tx)
+                .await
+                .unwrap();
+            assert_eq!(res["id"], 1);
+            assert_eq!(res["error"]["code"], SERVER_ERROR);
+
+            // The subscription channel is pushed on the connection, followed by head changes
+            let call = json!({ "jsonrpc": "2.0", "id": 2, "method": CHAIN_NOTIFY, "params": [] });
+            assert!(server
+                .handle_call(&call.to_string(), None, &tx)
+                .await
+                .is_none());
+            let res = json_message(rx.next().await.unwrap());
+            assert_eq!(res["id"], 2);
+            assert_eq!(res["result"], 0);
+
+            publisher.publish(HeadChange::Revert(tipset())).await;
+            let notif = json_message(rx.next().await.unwrap());
+            assert_eq!(notif["method"], CHANNEL_VAL);
+            assert_eq!(notif["params"][0], 0);
+            assert_eq!(notif["params"][1][0]["Type"], "revert");
+        });
+    }
+
+    #[test]
+    fn slow_subscriber_closed() {
+        let mut publisher = Publisher::new(10);
+        let subscriber = publisher.subscribe();
+        // Channel with room for a single queued message, which is never read
+        let (tx, mut rx) = channel(0);
+
+        task::block_on(async {
+            let push = task::spawn(push_head_changes(0, None, subscriber, tx));
+            for _ in 0..3 {
+                publisher.publish(HeadChange::Apply(tipset())).await;
+            }
+            // The subscription ends once the queue of the connection is full
+            push.await;
+
+            let notif = json_message(rx.next().await.unwrap());
+            assert_eq!(notif["params"][1][0]["Type"], "apply");
+            assert!(rx.next().await.is_none());
+        });
+    }
+}
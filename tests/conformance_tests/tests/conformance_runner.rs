@@ -0,0 +1,235 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+// Doesn't run these unless feature specified
+#![cfg(feature = "submodule_tests")]
+
+use cid::Cid;
+use clock::ChainEpoch;
+use crypto::DomainSeparationTag;
+use db::MemoryDB;
+use encoding::Cbor;
+use flate2::read::GzDecoder;
+use forest_car::load_car;
+use forest_message::{MessageReceipt, UnsignedMessage};
+use interpreter::{DefaultSyscalls, Rand, VM};
+use ipld_blockstore::BlockStore;
+use serde::{Deserialize, Deserializer};
+use std::error::Error as StdError;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Directory of the test vectors submodule
+const VECTORS_DIR: &str = "../conformance-vectors/corpus";
+
+/// Randomness returned to actors, so executions are deterministic
+const TEST_RANDOMNESS: [u8; 32] = *b"i_am_random_____i_am_random_____";
+
+fn base64_bytes<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    base64::decode(&s).map_err(serde::de::Error::custom)
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct StateTreeVector {
+    #[serde(with = "cid::json")]
+    root_cid: Cid,
+}
+
+#[derive(Deserialize)]
+struct PreConditions {
+    epoch: ChainEpoch,
+    state_tree: StateTreeVector,
+}
+
+#[derive(Deserialize)]
+struct PostConditions {
+    state_tree: StateTreeVector,
+    receipts: Vec<ReceiptVector>,
+}
+
+#[derive(Deserialize)]
+struct MessageVector {
+    #[serde(deserialize_with = "base64_bytes")]
+    bytes: Vec<u8>,
+    epoch: Option<ChainEpoch>,
+}
+
+#[derive(Deserialize)]
+struct ReceiptVector {
+    exit_code: u64,
+    #[serde(rename = "return", deserialize_with = "base64_bytes")]
+    return_data: Vec<u8>,
+    gas_used: u64,
+}
+
+#[derive(Deserialize)]
+struct TestVector {
+    class: String,
+    #[serde(rename = "_meta")]
+    meta: Option<Meta>,
+    /// Gzipped CAR of the blocks of the pre-state
+    #[serde(deserialize_with = "base64_bytes")]
+    car: Vec<u8>,
+    preconditions: PreConditions,
+    apply_messages: Vec<MessageVector>,
+    postconditions: PostConditions,
+}
+
+struct TestRand;
+
+impl Rand for TestRand {
+    fn get_randomness<DB: BlockStore>(
+        &self,
+        _: &DB,
+        _: DomainSeparationTag,
+        _: ChainEpoch,
+        _: &[u8],
+    ) -> Result<[u8; 32], Box<dyn StdError>> {
+        Ok(TEST_RANDOMNESS)
+    }
+}
+
+fn check_receipt(
+    i: usize,
+    receipt: &MessageReceipt,
+    expected: &ReceiptVector,
+) -> Result<(), String> {
+    if receipt.exit_code as u64 != expected.exit_code {
+        return Err(format!(
+            "message {}: exit code {:?} does not match expected {}",
+            i, receipt.exit_code, expected.exit_code
+        ));
+    }
+    if receipt.return_data.bytes() != expected.return_data.as_slice() {
+        return Err(format!("message {}: return data does not match", i));
+    }
+    if receipt.gas_used != expected.gas_used {
+        return Err(format!(
+            "message {}: gas used {} does not match expected {}",
+            i, receipt.gas_used, expected.gas_used
+        ));
+    }
+    Ok(())
+}
+
+/// Applies the messages of the vector on top of its pre-state and checks the receipts and the
+/// resulting state root
+fn execute_message_vector(vector: &TestVector) -> Result<(), Box<dyn StdError>> {
+    let bs = MemoryDB::default();
+    load_car(&bs, BufReader::new(GzDecoder::new(vector.car.as_slice())))?;
+
+    if vector.apply_messages.len() != vector.postconditions.receipts.len() {
+        return Err(format!(
+            "{} messages to apply but {} receipts expected",
+            vector.apply_messages.len(),
+            vector.postconditions.receipts.len()
+        )
+        .into());
+    }
+
+    let mut root = vector.preconditions.state_tree.root_cid.clone();
+    for (i, (msg, expected)) in vector
+        .apply_messages
+        .iter()
+        .zip(vector.postconditions.receipts.iter())
+        .enumerate()
+    {
+        let epoch = msg.epoch.unwrap_or(vector.preconditions.epoch);
+        let unsigned = UnsignedMessage::unmarshal_cbor(&msg.bytes)?;
+
+        let mut vm = VM::new(&root, &bs, epoch, DefaultSyscalls::new(&bs), &TestRand)?;
+        let ret = vm.apply_message(&unsigned)?;
+        root = vm.flush()?;
+
+        check_receipt(i, &ret.msg_receipt, expected)?;
+    }
+
+    let expected_root = &vector.postconditions.state_tree.root_cid;
+    if &root != expected_root {
+        return Err(format!(
+            "state root {} does not match expected {}",
+            root, expected_root
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Collects the paths of all JSON files under the directory. Directories which can't be read
+/// are recorded as failures.
+fn collect_vectors(dir: &Path, vectors: &mut Vec<PathBuf>, failed: &mut Vec<String>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            failed.push(format!("{}: {}", dir.display(), e));
+            return;
+        }
+    };
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                failed.push(format!("{}: {}", dir.display(), e));
+                continue;
+            }
+        };
+        if path.is_dir() {
+            collect_vectors(&path, vectors, failed);
+        } else if path.extension().map_or(false, |ext| ext == "json") {
+            vectors.push(path);
+        }
+    }
+}
+
+/// Reads and parses a test vector file
+fn load_vector(path: &Path) -> Result<TestVector, Box<dyn StdError>> {
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+#[test]
+fn conformance_test_runner() {
+    let mut paths = Vec::new();
+    let mut failed = Vec::new();
+    collect_vectors(Path::new(VECTORS_DIR), &mut paths, &mut failed);
+
+    for path in paths {
+        let vector = match load_vector(&path) {
+            Ok(vector) => vector,
+            Err(e) => {
+                failed.push(format!("{}: failed to parse: {}", path.display(), e));
+                continue;
+            }
+        };
+
+        // Only message class vectors are supported
+        if vector.class != "message" {
+            continue;
+        }
+
+        if let Err(e) = execute_message_vector(&vector) {
+            let id = vector
+                .meta
+                .map(|m| m.id)
+                .unwrap_or_else(|| path.display().to_string());
+            failed.push(format!("{}: {}", id, e));
+        }
+    }
+
+    assert!(
+        failed.is_empty(),
+        "{} conformance vectors failed:\n{}",
+        failed.len(),
+        failed.join("\n")
+    );
+}
@@ -0,0 +1,60 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::Address;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Wrapper for serializing and deserializing an Address from JSON.
+#[derive(Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct AddressJson(#[serde(with = "self")] pub Address);
+
+/// Wrapper for serializing an Address reference to JSON.
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct AddressJsonRef<'a>(#[serde(with = "self")] pub &'a Address);
+
+impl From<AddressJson> for Address {
+    fn from(wrapper: AddressJson) -> Self {
+        wrapper.0
+    }
+}
+
+pub fn serialize<S>(a: &Address, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&a.to_string())
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Address, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let address_as_string: String = Deserialize::deserialize(deserializer)?;
+    address_as_string.parse().map_err(de::Error::custom)
+}
+
+pub mod vec {
+    use super::*;
+    use forest_json_utils::GoVecVisitor;
+    use serde::ser::SerializeSeq;
+
+    pub fn serialize<S>(m: &[Address], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(m.len()))?;
+        for e in m {
+            seq.serialize_element(&AddressJsonRef(e))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Address>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(GoVecVisitor::<Address, AddressJson>::new())
+    }
+}
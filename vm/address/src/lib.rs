@@ -10,6 +10,9 @@ pub use self::network::Network;
 pub use self::payload::{BLSPublicKey, Payload};
 pub use self::protocol::Protocol;
 
+#[cfg(feature = "json")]
+pub mod json;
+
 use data_encoding::Encoding;
 use data_encoding_macro::{internal_new_encoding, new_encoding};
 use encoding::{blake2b_variable, de, ser, serde_bytes, Cbor};
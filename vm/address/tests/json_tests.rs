@@ -0,0 +1,24 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+#![cfg(feature = "json")]
+
+use forest_address::{
+    json::{AddressJson, AddressJsonRef},
+    Address,
+};
+use serde_json::{from_str, to_string};
+
+#[test]
+fn symmetric_json_serialization() {
+    let addr = Address::new_id(1024);
+    let addr_json = r#""t01024""#;
+
+    let AddressJson(addr_d) = from_str(addr_json).unwrap();
+    assert_eq!(&addr_d, &addr, "Deserialized address does not match");
+
+    let ser_addr = to_string(&AddressJsonRef(&addr_d)).unwrap();
+    assert_eq!(ser_addr, addr_json);
+
+    assert!(from_str::<AddressJson>(r#""not an address""#).is_err());
+}
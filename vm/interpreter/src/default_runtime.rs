@@ -4,7 +4,7 @@
 use super::gas_block_store::GasBlockStore;
 use super::gas_syscalls::GasSyscalls;
 use super::gas_tracker::{price_list_by_epoch, GasTracker, PriceList};
-use super::ChainRand;
+use super::{ExecutionTrace, Rand};
 use actor::{
     self, account, ACCOUNT_ACTOR_CODE_ID, CRON_ACTOR_CODE_ID, INIT_ACTOR_CODE_ID,
     MARKET_ACTOR_CODE_ID, MINER_ACTOR_CODE_ID, MULTISIG_ACTOR_CODE_ID, PAYCH_ACTOR_CODE_ID,
@@ -18,7 +18,7 @@ use crypto::DomainSeparationTag;
 use forest_encoding::to_vec;
 use forest_encoding::Cbor;
 use ipld_blockstore::BlockStore;
-use message::{Message, UnsignedMessage};
+use message::{Message, MessageReceipt, UnsignedMessage};
 use num_bigint::BigUint;
 use runtime::{ActorCode, Runtime, Syscalls};
 use state_tree::StateTree;
@@ -29,7 +29,7 @@ use vm::{
 };
 
 /// Implementation of the Runtime trait.
-pub struct DefaultRuntime<'db, 'msg, 'st, 'sys, 'r, BS, SYS> {
+pub struct DefaultRuntime<'db, 'msg, 'st, 'sys, 'r, BS, SYS, R> {
     state: &'st mut StateTree<'db, BS>,
     store: GasBlockStore<'db, BS>,
     syscalls: GasSyscalls<'sys, SYS>,
@@ -40,13 +40,16 @@ pub struct DefaultRuntime<'db, 'msg, 'st, 'sys, 'r, BS, SYS> {
     origin_nonce: u64,
     num_actors_created: u64,
     price_list: PriceList,
-    rand: &'r ChainRand,
+    rand: &'r R,
+    /// Traces of the messages sent by the runtime, if tracing is enabled
+    subcalls: Option<Vec<ExecutionTrace>>,
 }
 
-impl<'db, 'msg, 'st, 'sys, 'r, BS, SYS> DefaultRuntime<'db, 'msg, 'st, 'sys, 'r, BS, SYS>
+impl<'db, 'msg, 'st, 'sys, 'r, BS, SYS, R> DefaultRuntime<'db, 'msg, 'st, 'sys, 'r, BS, SYS, R>
 where
     BS: BlockStore,
     SYS: Syscalls,
+    R: Rand,
 {
     /// Constructs a new Runtime
     #[allow(clippy::too_many_arguments)]
@@ -60,7 +63,7 @@ where
         origin: Address,
         origin_nonce: u64,
         num_actors_created: u64,
-        rand: &'r ChainRand,
+        rand: &'r R,
     ) -> Self {
         let price_list = price_list_by_epoch(epoch);
         let gas_tracker = Rc::new(RefCell::new(GasTracker::new(
@@ -89,13 +92,47 @@ where
             num_actors_created,
             price_list,
             rand,
+            subcalls: None,
         }
     }
 
+    /// Records the gas charges and sends of the execution, to be returned as its trace
+    pub fn enable_trace(&mut self) {
+        self.gas_tracker.borrow_mut().enable_trace();
+        self.subcalls = Some(Vec::new());
+    }
+
+    /// Returns the trace of the execution which had the given result, if tracing is enabled.
+    /// Gas used is counted from the amount used before the execution.
+    pub fn execution_trace(
+        &mut self,
+        res: Result<&Serialized, &ActorError>,
+        gas_start: i64,
+    ) -> Option<ExecutionTrace> {
+        let subcalls = self.subcalls.take()?;
+        let gas_charges = self.gas_tracker.borrow_mut().take_charges()?;
+        let (return_data, exit_code, error) = match res {
+            Ok(ret) => (ret.clone(), ExitCode::Ok, None),
+            Err(e) => (Serialized::default(), e.exit_code(), Some(e.to_string())),
+        };
+        let gas_used = self.gas_used() - gas_start;
+        Some(ExecutionTrace {
+            msg: self.message.clone(),
+            msg_rct: MessageReceipt {
+                return_data,
+                exit_code,
+                gas_used: if gas_used < 0 { 0 } else { gas_used as u64 },
+            },
+            error,
+            gas_charges,
+            subcalls,
+        })
+    }
+
     /// Adds to amount of used
     /// * Will borrow gas tracker RefCell, do not call if any reference to this exists
-    pub fn charge_gas(&mut self, to_use: i64) -> Result<(), ActorError> {
-        self.gas_tracker.borrow_mut().charge_gas(to_use)
+    pub fn charge_gas(&mut self, name: &'static str, to_use: i64) -> Result<(), ActorError> {
+        self.gas_tracker.borrow_mut().charge_gas(name, to_use)
     }
 
     /// Returns gas used by runtime
@@ -156,10 +193,11 @@ where
     }
 }
 
-impl<BS, SYS> Runtime<BS> for DefaultRuntime<'_, '_, '_, '_, '_, BS, SYS>
+impl<BS, SYS, R> Runtime<BS> for DefaultRuntime<'_, '_, '_, '_, '_, BS, SYS, R>
 where
     BS: BlockStore,
     SYS: Syscalls,
+    R: Rand,
 {
     fn message(&self) -> &UnsignedMessage {
         &self.message
@@ -324,12 +362,13 @@ where
             .map_err(|_e| self.abort(ExitCode::ErrPlaceholder, "failed to create snapshot"))?;
 
         let epoch = self.curr_epoch();
-        let send_res = {
+        let gas_start = self.gas_used();
+        let (send_res, trace) = {
             let mut parent = DefaultRuntime::new(
                 self.state,
                 self.store.store,
                 self.syscalls.syscalls,
-                self.gas_used(),
+                gas_start,
                 &msg,
                 epoch,
                 self.origin,
@@ -337,8 +376,16 @@ where
                 self.num_actors_created,
                 self.rand,
             );
-            internal_send::<BS, SYS>(&mut parent, &msg, 0)
+            if self.subcalls.is_some() {
+                parent.enable_trace();
+            }
+            let send_res = internal_send::<BS, SYS, R>(&mut parent, &msg, 0);
+            let trace = parent.execution_trace(send_res.as_ref(), gas_start);
+            (send_res, trace)
         };
+        if let (Some(subcalls), Some(trace)) = (self.subcalls.as_mut(), trace) {
+            subcalls.push(trace);
+        }
         if send_res.is_err() {
             self.state
                 .revert_to_snapshot(&snapshot)
@@ -379,7 +426,7 @@ where
         Ok(addr)
     }
     fn create_actor(&mut self, code_id: &Cid, address: &Address) -> Result<(), ActorError> {
-        self.charge_gas(self.price_list.on_create_actor())?;
+        self.charge_gas("OnCreateActor", self.price_list.on_create_actor())?;
         self.state
             .set_actor(
                 &address,
@@ -393,7 +440,7 @@ where
             })
     }
     fn delete_actor(&mut self, _beneficiary: &Address) -> Result<(), ActorError> {
-        self.charge_gas(self.price_list.on_delete_actor())?;
+        self.charge_gas("OnDeleteActor", self.price_list.on_delete_actor())?;
         let balance = self.get_actor(self.message.to()).map(|act| act.balance)?;
         if !balance.eq(&0u64.into()) {
             return Err(self.abort(
@@ -417,16 +464,18 @@ where
 }
 /// Shared logic between the DefaultRuntime and the Interpreter.
 /// It invokes methods on different Actors based on the Message.
-pub fn internal_send<BS, SYS>(
-    runtime: &mut DefaultRuntime<'_, '_, '_, '_, '_, BS, SYS>,
+pub fn internal_send<BS, SYS, R>(
+    runtime: &mut DefaultRuntime<'_, '_, '_, '_, '_, BS, SYS, R>,
     msg: &UnsignedMessage,
     _gas_cost: i64,
 ) -> Result<Serialized, ActorError>
 where
     BS: BlockStore,
     SYS: Syscalls,
+    R: Rand,
 {
     runtime.charge_gas(
+        "OnMethodInvocation",
         runtime
             .price_list()
             .on_method_invocation(msg.value(), msg.method_num()),
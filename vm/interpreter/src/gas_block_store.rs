@@ -28,7 +28,7 @@ where
         if let Some(bz) = &ret {
             self.gas
                 .borrow_mut()
-                .charge_gas(self.price_list.on_ipld_get(bz.len()))?;
+                .charge_gas("OnIpldGet", self.price_list.on_ipld_get(bz.len()))?;
         }
         Ok(ret)
     }
@@ -50,9 +50,10 @@ where
         S: Serialize,
         T: MultihashDigest,
     {
-        self.gas
-            .borrow_mut()
-            .charge_gas(self.price_list.on_ipld_put(to_vec(obj).unwrap().len()))?;
+        self.gas.borrow_mut().charge_gas(
+            "OnIpldPut",
+            self.price_list.on_ipld_put(to_vec(obj).unwrap().len()),
+        )?;
 
         // TODO investigate if error here should be fatal
         self.store.put(obj, hash)
@@ -32,6 +32,7 @@ where
         self.gas
             .borrow_mut()
             .charge_gas(
+                "OnVerifySignature",
                 self.price_list
                     .on_verify_signature(signature.signature_type(), plaintext.len()),
             )
@@ -41,7 +42,7 @@ where
     fn hash_blake2b(&self, data: &[u8]) -> Result<[u8; 32], Box<dyn StdError>> {
         self.gas
             .borrow_mut()
-            .charge_gas(self.price_list.on_hashing(data.len()))
+            .charge_gas("OnHashing", self.price_list.on_hashing(data.len()))
             .unwrap();
         self.syscalls.hash_blake2b(data)
     }
@@ -52,21 +53,24 @@ where
     ) -> Result<Cid, Box<dyn StdError>> {
         self.gas
             .borrow_mut()
-            .charge_gas(self.price_list.on_compute_unsealed_sector_cid(reg, pieces))
+            .charge_gas(
+                "OnComputeUnsealedSectorCid",
+                self.price_list.on_compute_unsealed_sector_cid(reg, pieces),
+            )
             .unwrap();
         self.syscalls.compute_unsealed_sector_cid(reg, pieces)
     }
     fn verify_seal(&self, vi: &SealVerifyInfo) -> Result<(), Box<dyn StdError>> {
         self.gas
             .borrow_mut()
-            .charge_gas(self.price_list.on_verify_seal(vi))
+            .charge_gas("OnVerifySeal", self.price_list.on_verify_seal(vi))
             .unwrap();
         self.syscalls.verify_seal(vi)
     }
     fn verify_post(&self, vi: &WindowPoStVerifyInfo) -> Result<(), Box<dyn StdError>> {
         self.gas
             .borrow_mut()
-            .charge_gas(self.price_list.on_verify_post(vi))
+            .charge_gas("OnVerifyPost", self.price_list.on_verify_post(vi))
             .unwrap();
         self.syscalls.verify_post(vi)
     }
@@ -78,7 +82,10 @@ where
     ) -> Result<Option<ConsensusFault>, Box<dyn StdError>> {
         self.gas
             .borrow_mut()
-            .charge_gas(self.price_list.on_verify_consensus_fault())
+            .charge_gas(
+                "OnVerifyConsensusFault",
+                self.price_list.on_verify_consensus_fault(),
+            )
             .unwrap();
         self.syscalls.verify_consensus_fault(h1, h2, extra)
     }
@@ -6,9 +6,18 @@ mod price_list;
 pub use self::price_list::{price_list_by_epoch, PriceList};
 use vm::{ActorError, ExitCode};
 
+/// Gas charged for an operation, named after the price list method which priced it
+#[derive(Clone, Debug, PartialEq)]
+pub struct GasCharge {
+    pub name: &'static str,
+    pub total_gas: i64,
+}
+
 pub struct GasTracker {
     gas_available: i64,
     gas_used: i64,
+    /// Charges recorded for execution traces, if enabled
+    charges: Option<Vec<GasCharge>>,
 }
 
 impl GasTracker {
@@ -16,11 +25,28 @@ impl GasTracker {
         Self {
             gas_available,
             gas_used,
+            charges: None,
         }
     }
 
+    /// Records the charges made from now on
+    pub fn enable_trace(&mut self) {
+        self.charges.get_or_insert_with(Vec::new);
+    }
+
+    /// Returns the charges recorded, if tracing is enabled
+    pub fn take_charges(&mut self) -> Option<Vec<GasCharge>> {
+        self.charges.take()
+    }
+
     /// Safely consumes gas
-    pub fn charge_gas(&mut self, to_use: i64) -> Result<(), ActorError> {
+    pub fn charge_gas(&mut self, name: &'static str, to_use: i64) -> Result<(), ActorError> {
+        if let Some(charges) = self.charges.as_mut() {
+            charges.push(GasCharge {
+                name,
+                total_gas: to_use,
+            });
+        }
         if self.gas_used + to_use > self.gas_available {
             self.gas_used = self.gas_available;
             Err(ActorError::new(
@@ -55,10 +81,26 @@ mod tests {
     #[test]
     fn basic_gas_tracker() {
         let mut t = GasTracker::new(20, 10);
-        t.charge_gas(5).unwrap();
+        t.charge_gas("", 5).unwrap();
         assert_eq!(t.gas_used(), 15);
-        t.charge_gas(5).unwrap();
+        t.charge_gas("", 5).unwrap();
         assert_eq!(t.gas_used(), 20);
-        assert!(t.charge_gas(1).is_err())
+        assert!(t.charge_gas("", 1).is_err())
+    }
+
+    #[test]
+    fn traced_charges() {
+        let mut t = GasTracker::new(20, 0);
+        t.charge_gas("OnIpldGet", 5).unwrap();
+        t.enable_trace();
+        t.charge_gas("OnIpldPut", 10).unwrap();
+        assert_eq!(
+            t.take_charges(),
+            Some(vec![GasCharge {
+                name: "OnIpldPut",
+                total_gas: 10
+            }])
+        );
+        assert_eq!(t.take_charges(), None);
     }
 }
@@ -7,8 +7,11 @@ mod gas_block_store;
 mod gas_syscalls;
 mod gas_tracker;
 mod rand;
+mod trace;
 mod vm;
 pub use self::default_runtime::*;
 pub use self::default_syscalls::DefaultSyscalls;
+pub use self::gas_tracker::GasCharge;
 pub use self::rand::*;
+pub use self::trace::ExecutionTrace;
 pub use self::vm::*;
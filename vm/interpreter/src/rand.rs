@@ -7,6 +7,19 @@ use crypto::DomainSeparationTag;
 use ipld_blockstore::BlockStore;
 use std::error::Error;
 
+/// Source of the randomness given to actors
+pub trait Rand {
+    /// Gets 32 bytes of randomness paramaterized by the DomainSeparationTag, ChainEpoch and
+    /// Entropy
+    fn get_randomness<DB: BlockStore>(
+        &self,
+        db: &DB,
+        pers: DomainSeparationTag,
+        round: ChainEpoch,
+        entropy: &[u8],
+    ) -> Result<[u8; 32], Box<dyn Error>>;
+}
+
 /// Allows for deriving the randomness from a particular tipset
 #[derive(Debug, Clone)]
 pub struct ChainRand {
@@ -18,9 +31,11 @@ impl ChainRand {
     pub fn new(blks: TipsetKeys) -> Self {
         Self { blks }
     }
+}
 
+impl Rand for ChainRand {
     /// Gets 32 bytes of randomness  paramaterized by the DomainSeparationTag, ChainEpoch, Entropy, and Tipset
-    pub fn get_randomness<DB: BlockStore>(
+    fn get_randomness<DB: BlockStore>(
         &self,
         db: &DB,
         pers: DomainSeparationTag,
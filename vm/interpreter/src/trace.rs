@@ -0,0 +1,16 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::gas_tracker::GasCharge;
+use message::{MessageReceipt, UnsignedMessage};
+
+/// Trace of the execution of a message, along with the messages it sent in turn
+#[derive(Clone, PartialEq)]
+pub struct ExecutionTrace {
+    pub msg: UnsignedMessage,
+    pub msg_rct: MessageReceipt,
+    pub error: Option<String>,
+    /// Gas charged while executing the message, excluding the messages it sent
+    pub gas_charges: Vec<GasCharge>,
+    pub subcalls: Vec<ExecutionTrace>,
+}
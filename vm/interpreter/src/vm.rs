@@ -1,7 +1,8 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use super::{gas_tracker::price_list_by_epoch, internal_send, ChainRand, DefaultRuntime};
+use super::gas_tracker::{price_list_by_epoch, GasCharge};
+use super::{internal_send, DefaultRuntime, ExecutionTrace, Rand};
 use actor::{
     cron, reward, ACCOUNT_ACTOR_CODE_ID, CRON_ACTOR_ADDR, REWARD_ACTOR_ADDR, SYSTEM_ACTOR_ADDR,
 };
@@ -20,29 +21,37 @@ use std::collections::HashSet;
 use std::error::Error as StdError;
 use vm::{ActorError, ExitCode, Serialized};
 
+/// Callback called with the Cid of each message applied in a tipset and the result of
+/// applying it
+pub type ApplyCallback<'a> =
+    &'a mut dyn FnMut(&Cid, &UnsignedMessage, &ApplyRet) -> Result<(), String>;
+
 /// Interpreter which handles execution of state transitioning messages and returns receipts
 /// from the vm execution.
-pub struct VM<'db, 'r, DB, SYS> {
+pub struct VM<'db, 'r, DB, SYS, R> {
     state: StateTree<'db, DB>,
     // TODO revisit handling buffered store specifically in VM
     store: &'db DB,
     epoch: ChainEpoch,
     syscalls: SYS,
-    rand: &'r ChainRand,
+    rand: &'r R,
+    /// Whether execution traces of the messages applied are recorded
+    trace: bool,
     // TODO: missing fields
 }
 
-impl<'db, 'r, DB, SYS> VM<'db, 'r, DB, SYS>
+impl<'db, 'r, DB, SYS, R> VM<'db, 'r, DB, SYS, R>
 where
     DB: BlockStore,
     SYS: Syscalls,
+    R: Rand,
 {
     pub fn new(
         root: &Cid,
         store: &'db DB,
         epoch: ChainEpoch,
         syscalls: SYS,
-        rand: &'r ChainRand,
+        rand: &'r R,
     ) -> Result<Self, String> {
         let state = StateTree::new_from_root(store, root)?;
         Ok(VM {
@@ -51,9 +60,16 @@ where
             epoch,
             syscalls,
             rand,
+            trace: false,
         })
     }
 
+    /// Records the execution traces of the messages applied from now on, which are returned
+    /// in their `ApplyRet`
+    pub fn enable_trace(&mut self) {
+        self.trace = true;
+    }
+
     /// Flush stores in VM and return state root.
     pub fn flush(&mut self) -> Result<Cid, String> {
         self.state.flush()
@@ -66,9 +82,12 @@ where
 
     /// Apply all messages from a tipset
     /// Returns the receipts from the transactions.
+    /// The callback is called with the Cid of each message as included in the blocks and
+    /// the result of applying it.
     pub fn apply_tip_set_messages(
         &mut self,
         tipset: &FullTipset,
+        mut callback: Option<ApplyCallback>,
     ) -> Result<Vec<MessageReceipt>, Box<dyn StdError>> {
         let mut receipts = Vec::new();
         let mut processed = HashSet::<Cid>::default();
@@ -77,31 +96,34 @@ where
             let mut penalty = BigUint::zero();
             let mut gas_reward = BigUint::zero();
 
-            let mut process_msg = |msg: &UnsignedMessage| -> Result<(), Box<dyn StdError>> {
-                let cid = msg.cid()?;
-                // Ensure no duplicate processing of a message
-                if processed.contains(&cid) {
-                    return Ok(());
-                }
-                let ret = self.apply_message(msg)?;
-
-                // Update totals
-                gas_reward += msg.gas_price() * ret.msg_receipt.gas_used;
-                penalty += ret.penalty;
-                receipts.push(ret.msg_receipt);
-
-                // Add callback here if needed in future
-
-                // Add processed Cid to set of processed messages
-                processed.insert(cid);
-                Ok(())
-            };
+            let mut process_msg =
+                |msg: &UnsignedMessage, msg_cid: Cid| -> Result<(), Box<dyn StdError>> {
+                    let cid = msg.cid()?;
+                    // Ensure no duplicate processing of a message
+                    if processed.contains(&cid) {
+                        return Ok(());
+                    }
+                    let ret = self.apply_message(msg)?;
+
+                    if let Some(cb) = callback.as_mut() {
+                        cb(&msg_cid, msg, &ret)?;
+                    }
+
+                    // Update totals
+                    gas_reward += msg.gas_price() * ret.msg_receipt.gas_used;
+                    penalty += ret.penalty;
+                    receipts.push(ret.msg_receipt);
+
+                    // Add processed Cid to set of processed messages
+                    processed.insert(cid);
+                    Ok(())
+                };
 
             for msg in block.bls_msgs() {
-                process_msg(msg)?;
+                process_msg(msg, msg.cid()?)?;
             }
             for msg in block.secp_msgs() {
-                process_msg(msg.message())?;
+                process_msg(msg.message(), msg.cid()?)?;
             }
 
             // Generate reward transaction for the miner of the block
@@ -198,7 +220,7 @@ where
 
     /// Applies the state transition for a single message
     /// Returns ApplyRet structure which contains the message receipt and some meta data.
-    fn apply_message(&mut self, msg: &UnsignedMessage) -> Result<ApplyRet, String> {
+    pub fn apply_message(&mut self, msg: &UnsignedMessage) -> Result<ApplyRet, String> {
         check_message(msg)?;
 
         let pl = price_list_by_epoch(self.epoch());
@@ -296,14 +318,32 @@ where
         let snapshot = self.state.snapshot()?;
 
         // scoped to deal with mutable reference borrowing
-        let (ret_data, gas_used, act_err) = {
+        let (ret_data, gas_used, act_err, exec_trace) = {
             let (ret_data, mut rt, act_err) = self.send(msg, msg_gas_cost as i64);
-            rt.charge_gas(rt.price_list().on_chain_return_value(ret_data.len()))
-                .map_err(|e| e.to_string())?;
-            (ret_data, rt.gas_used(), act_err)
+            rt.charge_gas(
+                "OnChainReturnValue",
+                rt.price_list().on_chain_return_value(ret_data.len()),
+            )
+            .map_err(|e| e.to_string())?;
+            let res = match &act_err {
+                Some(err) => Err(err),
+                None => Ok(&ret_data),
+            };
+            let exec_trace = rt.execution_trace(res, 0).map(|mut trace| {
+                // The cost of the message itself is charged before the runtime is created
+                trace.gas_charges.insert(
+                    0,
+                    GasCharge {
+                        name: "OnChainMessage",
+                        total_gas: msg_gas_cost as i64,
+                    },
+                );
+                trace
+            });
+            (ret_data, rt.gas_used(), act_err, exec_trace)
         };
 
-        if let Some(err) = act_err {
+        if let Some(err) = &act_err {
             if err.is_fatal() {
                 return Err(format!("Fatal send actor error occurred, err: {:?}", err));
             };
@@ -333,15 +373,21 @@ where
             return Err("Gas handling math is wrong".to_owned());
         }
 
-        Ok(ApplyRet::new(
+        let exit_code = act_err
+            .as_ref()
+            .map(|e| e.exit_code())
+            .unwrap_or(ExitCode::Ok);
+        let mut ret = ApplyRet::new(
             MessageReceipt {
                 return_data: ret_data,
-                exit_code: ExitCode::Ok,
+                exit_code,
                 gas_used,
             },
             BigUint::zero(),
-            None,
-        ))
+            act_err,
+        );
+        ret.exec_trace = exec_trace;
+        Ok(ret)
     }
     /// Instantiates a new Runtime, and calls internal_send to do the execution.
     fn send<'m>(
@@ -350,7 +396,7 @@ where
         gas_cost: i64,
     ) -> (
         Serialized,
-        DefaultRuntime<'db, 'm, '_, '_, '_, DB, SYS>,
+        DefaultRuntime<'db, 'm, '_, '_, '_, DB, SYS, R>,
         Option<ActorError>,
     ) {
         let mut rt = DefaultRuntime::new(
@@ -365,6 +411,9 @@ where
             0,
             self.rand,
         );
+        if self.trace {
+            rt.enable_trace();
+        }
 
         let ser = match internal_send(&mut rt, msg, gas_cost) {
             Ok(ser) => ser,
@@ -374,13 +423,13 @@ where
     }
 }
 
-// TODO remove allow dead_code
-#[allow(dead_code)]
 /// Apply message return data
 pub struct ApplyRet {
-    msg_receipt: MessageReceipt,
-    penalty: BigUint,
-    act_error: Option<ActorError>,
+    pub msg_receipt: MessageReceipt,
+    pub penalty: BigUint,
+    pub act_error: Option<ActorError>,
+    /// Trace of the execution, if the VM records them
+    pub exec_trace: Option<ExecutionTrace>,
 }
 
 impl ApplyRet {
@@ -389,6 +438,7 @@ impl ApplyRet {
             msg_receipt,
             penalty,
             act_error,
+            exec_trace: None,
         }
     }
 }
@@ -0,0 +1,74 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use actor::{init, ACCOUNT_ACTOR_CODE_ID, INIT_ACTOR_ADDR, REWARD_ACTOR_ADDR};
+use address::Address;
+use blocks::TipsetKeys;
+use cid::multihash::{Blake2b256, Identity};
+use db::MemoryDB;
+use interpreter::{ChainRand, DefaultSyscalls, VM};
+use ipld_blockstore::BlockStore;
+use ipld_hamt::Hamt;
+use message::UnsignedMessage;
+use state_tree::StateTree;
+use vm::{ActorState, ExitCode, Serialized};
+
+#[test]
+fn failed_message_receipt() {
+    let store = MemoryDB::default();
+    let mut state = StateTree::new(&store);
+
+    let e_cid = Hamt::<String, _>::new_with_bit_width(&store, 5)
+        .flush()
+        .unwrap();
+    let init_state = init::State::new(e_cid, "test".to_owned());
+    let init_cid = store.put(&init_state, Blake2b256).unwrap();
+    let init_act = ActorState::new(
+        ACCOUNT_ACTOR_CODE_ID.clone(),
+        init_cid.clone(),
+        Default::default(),
+        0,
+    );
+    state.set_actor(&INIT_ACTOR_ADDR, init_act.clone()).unwrap();
+    state.set_actor(&REWARD_ACTOR_ADDR, init_act).unwrap();
+
+    let sender = Address::new_id(100);
+    let sender_state_cid = store
+        .put(
+            &actor::account::State {
+                address: sender.clone(),
+            },
+            Identity,
+        )
+        .unwrap();
+    let sender_act = ActorState::new(
+        ACCOUNT_ACTOR_CODE_ID.clone(),
+        sender_state_cid,
+        10_000_000u64.into(),
+        0,
+    );
+    let sender = state.register_new_address(&sender, sender_act).unwrap();
+    let root = state.flush().unwrap();
+
+    let rand = ChainRand::new(TipsetKeys::new(vec![]));
+    let mut vm = VM::new(&root, &store, 0, DefaultSyscalls::new(&store), &rand).unwrap();
+
+    // The account actor has no method 10, so the call fails
+    let message = UnsignedMessage::builder()
+        .to(sender.clone())
+        .from(sender)
+        .method_num(10)
+        .value(1u8.into())
+        .gas_price(1u8.into())
+        .gas_limit(1_000_000)
+        .params(Serialized::default())
+        .build()
+        .unwrap();
+    let ret = vm.apply_message(&message).unwrap();
+
+    assert_eq!(ret.msg_receipt.exit_code, ExitCode::SysErrInvalidMethod);
+    assert_eq!(
+        ret.act_error.map(|e| e.exit_code()),
+        Some(ExitCode::SysErrInvalidMethod)
+    );
+}
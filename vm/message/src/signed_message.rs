@@ -25,6 +25,20 @@ impl SignedMessage {
         Ok(SignedMessage { message, signature })
     }
 
+    /// Generate a signed message from an unsigned message and an existing signature.
+    /// The signature is verified against the sender of the message.
+    pub fn new_from_parts(message: UnsignedMessage, signature: Signature) -> Result<Self, String> {
+        let smsg = SignedMessage { message, signature };
+        smsg.verify()?;
+        Ok(smsg)
+    }
+
+    /// Verifies that the signature was produced by the sender over the message bytes.
+    pub fn verify(&self) -> Result<(), String> {
+        let bz = self.message.marshal_cbor().map_err(|e| e.to_string())?;
+        self.signature.verify(&bz, self.from())
+    }
+
     /// Returns reference to the unsigned message.
     pub fn message(&self) -> &UnsignedMessage {
         &self.message
@@ -64,6 +64,18 @@ impl UnsignedMessage {
     pub fn builder() -> MessageBuilder {
         MessageBuilder::default()
     }
+    /// Sets the sequence of the message
+    pub fn set_sequence(&mut self, sequence: u64) {
+        self.sequence = sequence;
+    }
+    /// Sets the price paid per unit of gas
+    pub fn set_gas_price(&mut self, gas_price: TokenAmount) {
+        self.gas_price = gas_price;
+    }
+    /// Sets the maximum amount of gas the message can use
+    pub fn set_gas_limit(&mut self, gas_limit: u64) {
+        self.gas_limit = gas_limit;
+    }
 }
 
 impl Serialize for UnsignedMessage {